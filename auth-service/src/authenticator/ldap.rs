@@ -0,0 +1,288 @@
+use common::errors::AppError;
+use ldap3::{LdapConnAsync, LdapConnSettings, LdapError, Scope, SearchEntry, ldap_escape};
+use sqlx::PgPool;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use super::Outcome;
+use crate::config::LdapConfig;
+use crate::db::organizations::{Membership, Organization};
+use crate::db::queries::User;
+use crate::handlers::membership_role;
+use crate::identity;
+use crate::webhooks::{self, UserEvent};
+
+/// `auth_source` of accounts provisioned from the directory
+const SOURCE: &str = "ldap";
+
+/// LDAP result code for a failed bind
+const INVALID_CREDENTIALS: u32 = 49;
+
+/// What the directory says about a user who bound successfully
+struct DirectoryUser {
+    email: Option<String>,
+    groups: Vec<String>,
+}
+
+/// Binds users against an LDAP or Active Directory server. The user's entry
+/// is found with a search, then their password is checked by binding as it.
+/// Accounts are created on first login and their email and role follow the
+/// directory on every login after that.
+pub struct LdapAuthenticator {
+    config: LdapConfig,
+}
+
+impl LdapAuthenticator {
+    pub fn new(config: LdapConfig) -> Self {
+        Self { config }
+    }
+
+    pub async fn authenticate(
+        &self,
+        pool: &PgPool,
+        username: &str,
+        password: &str,
+    ) -> Result<Outcome, AppError> {
+        let existing = User::find_by_username(pool, username)
+            .await
+            .map_err(|e| AppError::database(format!("Database error: {}", e)))?;
+        if existing.as_ref().is_some_and(|u| u.auth_source != SOURCE) {
+            return Ok(Outcome::Unknown);
+        }
+
+        // An empty password would make the bind unauthenticated, which servers accept
+        if password.is_empty() {
+            return Ok(Outcome::Rejected);
+        }
+
+        let timeout = Duration::from_secs(self.config.timeout_seconds);
+        let lookup = tokio::time::timeout(timeout, self.bind(username, password))
+            .await
+            .map_err(LdapError::from)
+            .and_then(|result| result);
+        let directory_user = match lookup {
+            Ok(Some(directory_user)) => directory_user,
+            Ok(None) if existing.is_some() => return Ok(Outcome::Rejected),
+            Ok(None) => return Ok(Outcome::Unknown),
+            Err(e) => {
+                warn!(error = %e, "LDAP authentication failed");
+                return Err(AppError::http(503, "Directory is unavailable"));
+            }
+        };
+
+        let Some(role) = self.role_for(&directory_user.groups) else {
+            info!(username = %username, "Directory user is in no group that grants a role");
+            return Ok(Outcome::Rejected);
+        };
+        let Some(email) = directory_user.email else {
+            warn!(username = %username, "Directory entry has no email address");
+            return Ok(Outcome::Rejected);
+        };
+
+        let user = self
+            .provision(pool, existing, username, &email, role)
+            .await?;
+        Ok(Outcome::Authenticated(user))
+    }
+
+    /// Find the user's entry and bind as it. `None` when there is no single
+    /// matching entry or the password is wrong.
+    async fn bind(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<Option<DirectoryUser>, LdapError> {
+        let settings = LdapConnSettings::new()
+            .set_conn_timeout(Duration::from_secs(self.config.timeout_seconds))
+            .set_starttls(self.config.starttls);
+        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &self.config.url).await?;
+        ldap3::drive!(conn);
+
+        if let Some(bind_dn) = &self.config.bind_dn {
+            ldap.simple_bind(bind_dn, &self.config.bind_password)
+                .await?
+                .success()?;
+        }
+
+        let filter = self
+            .config
+            .user_filter
+            .replace("{username}", &ldap_escape(username));
+        let attributes = [
+            self.config.email_attribute.as_str(),
+            self.config.group_attribute.as_str(),
+        ];
+        let (entries, _) = ldap
+            .search(
+                &self.config.search_base,
+                Scope::Subtree,
+                &filter,
+                attributes,
+            )
+            .await?
+            .success()?;
+
+        let mut entries = entries.into_iter();
+        let (Some(entry), None) = (entries.next(), entries.next()) else {
+            let _ = ldap.unbind().await;
+            return Ok(None);
+        };
+        let entry = SearchEntry::construct(entry);
+
+        let bound = ldap.simple_bind(&entry.dn, password).await?;
+        let _ = ldap.unbind().await;
+        match bound.rc {
+            0 => {}
+            INVALID_CREDENTIALS => return Ok(None),
+            _ => {
+                bound.success()?;
+            }
+        }
+
+        // Attribute names come back in the server's spelling
+        let attribute = |name: &str| {
+            entry
+                .attrs
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, values)| values.clone())
+                .unwrap_or_default()
+        };
+
+        Ok(Some(DirectoryUser {
+            email: attribute(&self.config.email_attribute).into_iter().next(),
+            groups: attribute(&self.config.group_attribute),
+        }))
+    }
+
+    /// Role granted by the first configured group the user is in
+    fn role_for(&self, groups: &[String]) -> Option<&str> {
+        self.config
+            .group_roles
+            .iter()
+            .find(|(dn, _)| groups.iter().any(|g| g.eq_ignore_ascii_case(dn)))
+            .map(|(_, role)| role.as_str())
+            .or(self.config.default_role.as_deref())
+    }
+
+    /// Create the local account on first login, otherwise bring its email and
+    /// role in line with the directory
+    async fn provision(
+        &self,
+        pool: &PgPool,
+        existing: Option<User>,
+        username: &str,
+        email: &str,
+        role: &str,
+    ) -> Result<User, AppError> {
+        let organization = Organization::find_by_slug(pool, &self.config.organization)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to get organization: {}", e)))?
+            .ok_or_else(|| AppError::internal("LDAP organization is missing"))?;
+        let email = identity::normalize_email(email).map_err(AppError::validation)?;
+        let email = email.as_str();
+
+        let Some(user) = existing else {
+            let username = identity::normalize_username(username).map_err(AppError::validation)?;
+            let username = username.as_str();
+
+            let mut tx = pool
+                .begin()
+                .await
+                .map_err(|e| AppError::database(format!("Failed to start transaction: {}", e)))?;
+
+            // Directory accounts have no local password
+            let user = User::create(&mut *tx, username, email, "!", role)
+                .await
+                .map_err(|e| {
+                    if e.to_string().contains("unique") {
+                        AppError::validation("Email already belongs to another account")
+                    } else {
+                        AppError::database(format!("Failed to create user: {}", e))
+                    }
+                })?;
+            User::set_auth_source(&mut *tx, user.id, SOURCE)
+                .await
+                .map_err(|e| AppError::database(format!("Failed to create user: {}", e)))?;
+            Organization::set_member(&mut *tx, organization.id, user.id, membership_role(role))
+                .await
+                .map_err(|e| {
+                    AppError::database(format!("Failed to add organization member: {}", e))
+                })?;
+            webhooks::enqueue(
+                &mut *tx,
+                UserEvent::Created,
+                &user,
+                serde_json::json!({ "organization_id": organization.id, "auth_source": SOURCE }),
+            )
+            .await
+            .map_err(|e| AppError::database(format!("Failed to queue webhook: {}", e)))?;
+
+            tx.commit()
+                .await
+                .map_err(|e| AppError::database(format!("Failed to create user: {}", e)))?;
+
+            info!(user_id = %user.id, role = %role, "Provisioned user from directory");
+            return Ok(User {
+                auth_source: SOURCE.to_string(),
+                ..user
+            });
+        };
+
+        if user.email != email {
+            User::update_profile(pool, user.id, &user.username, email)
+                .await
+                .map_err(|e| AppError::database(format!("Failed to update user: {}", e)))?;
+        }
+
+        // Roles follow the directory; changes are queued like an admin's
+        let previous_member_role = Membership::find(pool, organization.id, user.id)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to get membership: {}", e)))?
+            .map(|m| m.role);
+        let mut changes = Vec::new();
+        if user.role != role {
+            changes.push(serde_json::json!({
+                "role": role,
+                "previous_role": user.role,
+                "organization_id": null,
+            }));
+        }
+        if previous_member_role.as_deref() != Some(membership_role(role)) {
+            changes.push(serde_json::json!({
+                "role": membership_role(role),
+                "previous_role": previous_member_role,
+                "organization_id": organization.id,
+            }));
+        }
+
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|e| AppError::database(format!("Failed to start transaction: {}", e)))?;
+        if user.role != role {
+            User::update_role(&mut *tx, user.id, role)
+                .await
+                .map_err(|e| AppError::database(format!("Failed to update user role: {}", e)))?;
+        }
+        Organization::set_member(&mut *tx, organization.id, user.id, membership_role(role))
+            .await
+            .map_err(|e| {
+                AppError::database(format!("Failed to update organization member: {}", e))
+            })?;
+        for details in changes {
+            webhooks::enqueue(&mut *tx, UserEvent::RoleChanged, &user, details)
+                .await
+                .map_err(|e| AppError::database(format!("Failed to queue webhook: {}", e)))?;
+        }
+        tx.commit()
+            .await
+            .map_err(|e| AppError::database(format!("Failed to update user role: {}", e)))?;
+
+        Ok(User {
+            email: email.to_string(),
+            role: role.to_string(),
+            ..user
+        })
+    }
+}
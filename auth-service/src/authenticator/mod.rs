@@ -0,0 +1,109 @@
+//! Login backends, tried in the order configured by `AUTH_BACKENDS`.
+//!
+//! Every account belongs to exactly one backend, recorded in `users.auth_source`.
+//! A backend only decides for its own accounts and for usernames no backend
+//! owns yet, so a directory entry can never take over a local account.
+
+mod ldap;
+
+use common::errors::AppError;
+use sqlx::PgPool;
+
+use crate::config::Config;
+use crate::db::queries::User;
+
+pub use ldap::LdapAuthenticator;
+
+/// What one backend concluded about a login attempt
+pub enum Outcome {
+    /// Credentials verified; carries the local account, provisioned if new
+    Authenticated(User),
+    /// The account belongs to this backend and the credentials are wrong
+    Rejected,
+    /// Not this backend's account; the next backend decides
+    Unknown,
+}
+
+pub enum Authenticator {
+    /// bcrypt against `users.password_hash`
+    Local,
+    Ldap(Box<LdapAuthenticator>),
+}
+
+impl Authenticator {
+    async fn authenticate(
+        &self,
+        pool: &PgPool,
+        username: &str,
+        password: &str,
+    ) -> Result<Outcome, AppError> {
+        match self {
+            Self::Local => authenticate_local(pool, username, password).await,
+            Self::Ldap(ldap) => ldap.authenticate(pool, username, password).await,
+        }
+    }
+}
+
+async fn authenticate_local(
+    pool: &PgPool,
+    username: &str,
+    password: &str,
+) -> Result<Outcome, AppError> {
+    let user = User::find_by_username(pool, username)
+        .await
+        .map_err(|e| AppError::database(format!("Database error: {}", e)))?;
+
+    let Some(user) = user.filter(|u| u.auth_source == "local") else {
+        return Ok(Outcome::Unknown);
+    };
+
+    // Erased accounts have no usable hash and fail like a wrong password
+    if bcrypt::verify(password, &user.password_hash).unwrap_or(false) {
+        Ok(Outcome::Authenticated(user))
+    } else {
+        Ok(Outcome::Rejected)
+    }
+}
+
+pub struct AuthenticatorChain {
+    authenticators: Vec<Authenticator>,
+}
+
+impl AuthenticatorChain {
+    pub fn from_config(config: &Config) -> Self {
+        let authenticators = config
+            .auth_backends
+            .iter()
+            .map(|name| match name.as_str() {
+                "local" => Authenticator::Local,
+                "ldap" => Authenticator::Ldap(Box::new(LdapAuthenticator::new(
+                    config
+                        .ldap
+                        .clone()
+                        .expect("LDAP_URL must be set to use the ldap backend"),
+                ))),
+                other => panic!("Unknown authentication backend '{}'", other),
+            })
+            .collect();
+
+        Self { authenticators }
+    }
+
+    /// The account `username` signs in to, or `None` if the credentials are wrong
+    pub async fn authenticate(
+        &self,
+        pool: &PgPool,
+        username: &str,
+        password: &str,
+    ) -> Result<Option<User>, AppError> {
+        for authenticator in &self.authenticators {
+            match authenticator.authenticate(pool, username, password).await? {
+                Outcome::Authenticated(user) => return Ok(Some(user)),
+                Outcome::Rejected => return Ok(None),
+                Outcome::Unknown => continue,
+            }
+        }
+
+        Ok(None)
+    }
+}
@@ -0,0 +1,559 @@
+//! Operator command-line tool for the auth service.
+//!
+//! Works directly against the service's database, configured from the same
+//! environment as the server. Every command prints a JSON document on stdout;
+//! failures print `{"error": ...}` on stderr and exit non-zero.
+
+use auth_service::config::Config;
+use auth_service::db::audit::AuditEntry;
+use auth_service::db::migrations;
+use auth_service::db::organizations::{Membership, Organization};
+use auth_service::db::queries::User;
+use auth_service::db::quotas::QuotaRow;
+use auth_service::db::sessions::{NewSession, Session};
+use auth_service::handlers::membership_role;
+use auth_service::identity;
+use auth_service::jwt::{JwtService, TokenGrant};
+use auth_service::permissions;
+use auth_service::pii::{self, Keyring, Reencryptor};
+use auth_service::tokens;
+use auth_service::webhooks::{self, UserEvent};
+use common::models::UserResponse;
+use common::validation;
+use serde_json::json;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::process::ExitCode;
+use uuid::Uuid;
+
+const USAGE: &str = "\
+Usage: auth-admin <command> [options]
+
+Commands:
+  users list
+  users collisions
+  users create --username <name> --email <email> [--role <role>]
+               [--organization <slug>] [--password-stdin]
+  users disable <user>
+  users enable <user>
+  users set-role <user> <role> [--organization <slug>]
+  users reset-password <user> [--password-stdin]
+  migrate
+  pii reencrypt
+  token <user> [--audience <audience>] [--organization <slug>]
+
+<user> is a username or user ID. Without --password-stdin a random password
+is generated and printed once. Configuration is read from the environment
+(DATABASE_URL, JWT_SECRET, ...), as for the server.
+
+Migrations cannot be rolled back: the schema is one idempotent migration
+without versions. Restore a database backup to return to an earlier state.";
+
+/// Positional arguments and `--flag [value]` options of one invocation
+struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, Option<String>>,
+}
+
+impl Args {
+    /// Options listed in `switches` take no value
+    fn parse(raw: impl Iterator<Item = String>, switches: &[&str]) -> Result<Self, String> {
+        let mut positional = Vec::new();
+        let mut options = HashMap::new();
+        let mut raw = raw.peekable();
+
+        while let Some(arg) = raw.next() {
+            match arg.strip_prefix("--") {
+                Some(name) if switches.contains(&name) => {
+                    options.insert(name.to_string(), None);
+                }
+                Some(name) => {
+                    let value = raw
+                        .next_if(|v| !v.starts_with("--"))
+                        .ok_or_else(|| format!("--{} requires a value", name))?;
+                    options.insert(name.to_string(), Some(value));
+                }
+                None => positional.push(arg),
+            }
+        }
+
+        Ok(Self {
+            positional,
+            options,
+        })
+    }
+
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options.get(name).and_then(|v| v.as_deref())
+    }
+
+    fn required(&self, name: &str) -> Result<&str, String> {
+        self.option(name)
+            .ok_or_else(|| format!("--{} is required", name))
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.options.contains_key(name)
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1), &["password-stdin", "yes", "help"]) {
+        Ok(args) => args,
+        Err(e) => return usage_error(&e),
+    };
+    if args.flag("help") || args.positional.is_empty() {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    let config = Config::from_env();
+    match Keyring::from_config(&config) {
+        Ok(Some(keyring)) => pii::install(keyring),
+        Ok(None) => {}
+        Err(e) => return failure(e),
+    }
+    let pool = match PgPool::connect(&config.database_url).await {
+        Ok(pool) => pool,
+        Err(e) => return failure(format!("Failed to connect to database: {}", e)),
+    };
+
+    let command: Vec<&str> = args.positional.iter().map(String::as_str).collect();
+    let result = match command.as_slice() {
+        ["users", "list"] => list_users(&pool).await,
+        ["users", "collisions"] => list_collisions(&pool).await,
+        ["users", "create"] => create_user(&pool, &args).await,
+        ["users", "disable", user] => set_active(&pool, user, false).await,
+        ["users", "enable", user] => set_active(&pool, user, true).await,
+        ["users", "set-role", user, role] => set_role(&pool, user, role, &args).await,
+        ["users", "reset-password", user] => reset_password(&pool, &config, user, &args).await,
+        ["migrate"] => migrate(&pool).await,
+        ["pii", "reencrypt"] => reencrypt(&pool, &config).await,
+        ["token", user] => mint_token(&pool, &config, user, &args).await,
+        _ => return usage_error(&format!("Unknown command: {}", command.join(" "))),
+    };
+
+    match result {
+        Ok(output) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&output).unwrap_or_default()
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => failure(e),
+    }
+}
+
+fn failure(message: String) -> ExitCode {
+    eprintln!("{}", json!({ "error": message }));
+    ExitCode::FAILURE
+}
+
+fn usage_error(message: &str) -> ExitCode {
+    eprintln!("{}", json!({ "error": message }));
+    eprintln!("\n{}", USAGE);
+    ExitCode::from(2)
+}
+
+/// Look a user up by ID, falling back to their username
+async fn find_user(pool: &PgPool, user: &str) -> Result<User, String> {
+    let found = match Uuid::parse_str(user) {
+        Ok(id) => User::find_by_id(pool, id).await,
+        Err(_) => User::find_by_username(pool, user).await,
+    };
+
+    found
+        .map_err(|e| format!("Failed to get user: {}", e))?
+        .ok_or_else(|| format!("User not found: {}", user))
+}
+
+async fn find_organization(pool: &PgPool, slug: &str) -> Result<Organization, String> {
+    Organization::find_by_slug(pool, slug)
+        .await
+        .map_err(|e| format!("Failed to get organization: {}", e))?
+        .ok_or_else(|| format!("Organization not found: {}", slug))
+}
+
+/// The password from the first line of stdin, or a generated one that is
+/// returned so it can be shown to the operator
+fn read_password(args: &Args) -> Result<(String, Option<String>), String> {
+    if !args.flag("password-stdin") {
+        let password = tokens::generate_opaque_token();
+        return Ok((password.clone(), Some(password)));
+    }
+
+    let mut line = String::new();
+    std::io::stdin()
+        .read_line(&mut line)
+        .map_err(|e| format!("Failed to read password: {}", e))?;
+    let password = line.trim_end_matches(['\r', '\n']).to_string();
+    validation::password(&password).map_err(|e| format!("Password: {}", e))?;
+
+    Ok((password, None))
+}
+
+fn hash_password(password: &str) -> Result<String, String> {
+    bcrypt::hash(password, bcrypt::DEFAULT_COST)
+        .map_err(|e| format!("Password hashing failed: {}", e))
+}
+
+/// Operator changes are audited without an actor, marked as coming from this tool
+async fn audit(
+    pool: &PgPool,
+    action: &str,
+    target: Uuid,
+    mut details: serde_json::Value,
+) -> Result<(), String> {
+    details["source"] = json!("auth-admin");
+    AuditEntry::record(pool, None, action, Some(target), None, details)
+        .await
+        .map_err(|e| format!("Failed to write audit log: {}", e))
+}
+
+async fn list_users(pool: &PgPool) -> Result<serde_json::Value, String> {
+    let users = User::list_all(pool)
+        .await
+        .map_err(|e| format!("Failed to list users: {}", e))?;
+
+    let users: Vec<UserResponse> = users.into_iter().map(Into::into).collect();
+    Ok(json!(users))
+}
+
+/// Accounts that keep usernames or emails from being unique regardless of case
+async fn list_collisions(pool: &PgPool) -> Result<serde_json::Value, String> {
+    let collisions = migrations::identity_collisions(pool)
+        .await
+        .map_err(|e| format!("Failed to check users: {}", e))?;
+
+    Ok(json!(collisions))
+}
+
+async fn create_user(pool: &PgPool, args: &Args) -> Result<serde_json::Value, String> {
+    let username = identity::normalize_username(args.required("username")?)?;
+    let email = identity::normalize_email(args.required("email")?)?;
+    let role = args.option("role").unwrap_or("user");
+    permissions::require_role(pool, role)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let slug = args
+        .option("organization")
+        .unwrap_or(Organization::DEFAULT_SLUG);
+    let organization = find_organization(pool, slug).await?;
+    let (password, generated) = read_password(args)?;
+    let password_hash = hash_password(&password)?;
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+    let user = User::create(&mut *tx, &username, &email, &password_hash, role)
+        .await
+        .map_err(|e| {
+            if e.to_string().contains("unique") {
+                "Username or email already exists".to_string()
+            } else {
+                format!("Failed to create user: {}", e)
+            }
+        })?;
+
+    Organization::set_member(&mut *tx, organization.id, user.id, membership_role(role))
+        .await
+        .map_err(|e| format!("Failed to add organization member: {}", e))?;
+
+    webhooks::enqueue(
+        &mut *tx,
+        UserEvent::Created,
+        &user,
+        json!({ "organization_id": organization.id }),
+    )
+    .await
+    .map_err(|e| format!("Failed to queue webhook: {}", e))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| format!("Failed to create user: {}", e))?;
+
+    audit(
+        pool,
+        "user.create",
+        user.id,
+        json!({ "role": role, "organization_id": organization.id }),
+    )
+    .await?;
+
+    Ok(json!({
+        "user": UserResponse::from(user),
+        "organization": organization.slug,
+        "password": generated,
+    }))
+}
+
+async fn set_active(pool: &PgPool, user: &str, active: bool) -> Result<serde_json::Value, String> {
+    let user = find_user(pool, user).await?;
+
+    User::set_active(pool, user.id, active)
+        .await
+        .map_err(|e| format!("Failed to update user: {}", e))?;
+
+    // A disabled account keeps nothing it signed in with
+    let revoked_sessions = if active {
+        0
+    } else {
+        Session::revoke_all_for_user(pool, user.id, None)
+            .await
+            .map_err(|e| format!("Failed to revoke sessions: {}", e))?
+    };
+
+    let action = if active {
+        "user.enable"
+    } else {
+        "user.disable"
+    };
+    audit(pool, action, user.id, json!({})).await?;
+
+    let user = find_user(pool, &user.id.to_string()).await?;
+    Ok(json!({
+        "user": UserResponse::from(user),
+        "revoked_sessions": revoked_sessions,
+    }))
+}
+
+/// Change a user's platform role, or their role within `--organization`.
+/// super_admin is only ever a platform role.
+async fn set_role(
+    pool: &PgPool,
+    user: &str,
+    role: &str,
+    args: &Args,
+) -> Result<serde_json::Value, String> {
+    permissions::require_role(pool, role)
+        .await
+        .map_err(|e| e.to_string())?;
+    let user = find_user(pool, user).await?;
+    let organization = match args.option("organization") {
+        Some(slug) => Some(find_organization(pool, slug).await?),
+        None => None,
+    };
+
+    let platform_role_changes = role == "super_admin" || user.role == "super_admin";
+    let mut previous_role = user.role.clone();
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+    if platform_role_changes || organization.is_none() {
+        User::update_role(&mut *tx, user.id, role)
+            .await
+            .map_err(|e| format!("Failed to update user role: {}", e))?;
+    }
+
+    if let Some(organization) = organization.as_ref().filter(|_| role != "super_admin") {
+        let membership = Membership::find(pool, organization.id, user.id)
+            .await
+            .map_err(|e| format!("Failed to get membership: {}", e))?
+            .ok_or_else(|| format!("Not a member of organization '{}'", organization.slug))?;
+
+        Organization::set_member(&mut *tx, organization.id, user.id, role)
+            .await
+            .map_err(|e| format!("Failed to update user role: {}", e))?;
+        if !platform_role_changes {
+            previous_role = membership.role;
+        }
+    }
+
+    let organization_id = organization.as_ref().map(|o| o.id);
+    if role != previous_role {
+        webhooks::enqueue(
+            &mut *tx,
+            UserEvent::RoleChanged,
+            &user,
+            json!({
+                "role": role,
+                "previous_role": previous_role,
+                "organization_id": organization_id,
+            }),
+        )
+        .await
+        .map_err(|e| format!("Failed to queue webhook: {}", e))?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| format!("Failed to update user role: {}", e))?;
+
+    audit(
+        pool,
+        "user.role_change",
+        user.id,
+        json!({
+            "role": role,
+            "previous_role": previous_role,
+            "organization_id": organization_id,
+        }),
+    )
+    .await?;
+
+    let user = find_user(pool, &user.id.to_string()).await?;
+    Ok(json!({
+        "user": UserResponse::from(user),
+        "previous_role": previous_role,
+        "organization": organization.map(|o| o.slug),
+    }))
+}
+
+async fn reset_password(
+    pool: &PgPool,
+    config: &Config,
+    user: &str,
+    args: &Args,
+) -> Result<serde_json::Value, String> {
+    let user = find_user(pool, user).await?;
+    let (password, generated) = read_password(args)?;
+
+    let keep_previous = config.previous_passwords_kept(&user.role);
+    User::update_password(pool, user.id, &hash_password(&password)?, keep_previous)
+        .await
+        .map_err(|e| format!("Failed to update password: {}", e))?;
+
+    let revoked_sessions = Session::revoke_all_for_user(pool, user.id, None)
+        .await
+        .map_err(|e| format!("Failed to revoke sessions: {}", e))?;
+
+    audit(pool, "password.reset", user.id, json!({})).await?;
+
+    Ok(json!({
+        "user_id": user.id,
+        "password": generated,
+        "revoked_sessions": revoked_sessions,
+    }))
+}
+
+async fn migrate(pool: &PgPool) -> Result<serde_json::Value, String> {
+    migrations::run_migrations(pool)
+        .await
+        .map_err(|e| format!("Migration failed: {}", e))?;
+
+    Ok(json!({ "migrated": true }))
+}
+
+/// Seal all personal data under the active key now, rather than waiting for
+/// the server's background job
+async fn reencrypt(pool: &PgPool, config: &Config) -> Result<serde_json::Value, String> {
+    if !pii::enabled() {
+        return Err("No PII keys are configured".to_string());
+    }
+
+    let report = Reencryptor::new(pool.clone(), config)
+        .run_once()
+        .await
+        .map_err(|e| format!("Re-encryption failed: {}", e))?;
+
+    Ok(json!(report))
+}
+
+/// Issue an access token for a user, as a login would. The token is bound to
+/// a new session so it can be listed and revoked like any other.
+async fn mint_token(
+    pool: &PgPool,
+    config: &Config,
+    user: &str,
+    args: &Args,
+) -> Result<serde_json::Value, String> {
+    let user = find_user(pool, user).await?;
+    if !user.is_active {
+        return Err("Account is disabled".to_string());
+    }
+
+    let audience = args.option("audience");
+    let audiences = match audience {
+        Some(aud) if !config.token_audiences.iter().any(|a| a == aud) => {
+            return Err(format!("Unknown audience: {}", aud));
+        }
+        Some(aud) => vec![aud.to_string()],
+        None => config.token_audiences.clone(),
+    };
+
+    let memberships = Membership::list_for_user(pool, user.id)
+        .await
+        .map_err(|e| format!("Failed to get memberships: {}", e))?;
+    let membership = match args.option("organization") {
+        Some(slug) => match memberships.into_iter().find(|m| m.slug == slug) {
+            Some(m) => Some(m),
+            None if user.role == "super_admin" => None,
+            None => return Err(format!("Not a member of organization '{}'", slug)),
+        },
+        None => memberships.into_iter().next(),
+    };
+    let organization_id = match (&membership, args.option("organization")) {
+        (Some(m), _) => Some(m.organization_id),
+        (None, Some(slug)) => Some(find_organization(pool, slug).await?.id),
+        (None, None) => None,
+    };
+    let organization_role = membership.map(|m| m.role);
+
+    // Super admins keep their platform role in every organization
+    let role = match &organization_role {
+        Some(role) if user.role != "super_admin" => role.clone(),
+        _ => user.role.clone(),
+    };
+
+    let permissions = User::get_permissions(pool, user.id, &role, organization_id)
+        .await
+        .map_err(|e| format!("Failed to get permissions: {}", e))?;
+    let quota = QuotaRow::effective(pool, user.id, &role)
+        .await
+        .map_err(|e| format!("Failed to get quota: {}", e))?;
+
+    let ttl = config.token_ttl(audience);
+    let expires_at = chrono::Utc::now() + ttl;
+    let session = Session::create(
+        pool,
+        NewSession {
+            user_id: user.id,
+            organization_id,
+            user_agent: Some("auth-admin"),
+            ip_address: None,
+            // The session is never refreshed; its refresh token is discarded
+            refresh_token_hash: &tokens::hash_token(&tokens::generate_opaque_token()),
+            expires_at,
+            impersonator_id: None,
+        },
+    )
+    .await
+    .map_err(|e| format!("Failed to create session: {}", e))?;
+
+    let grant = TokenGrant {
+        user_id: user.id,
+        role,
+        permissions,
+        audience: audiences,
+        session_id: Some(session.id),
+        actor: None,
+        organization_id,
+        organization_role,
+        quota: quota.map(|q| q.quota()),
+    };
+    let token = JwtService::new(config)
+        .generate_token(grant, ttl)
+        .map_err(|e| format!("JWT generation failed: {}", e))?;
+
+    audit(
+        pool,
+        "token.issue",
+        user.id,
+        json!({ "session_id": session.id, "audience": audience }),
+    )
+    .await?;
+
+    Ok(json!({
+        "token": token,
+        "session_id": session.id,
+        "expires_at": expires_at.to_rfc3339(),
+    }))
+}
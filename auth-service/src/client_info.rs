@@ -0,0 +1,44 @@
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header::USER_AGENT, request::Parts},
+};
+use common::client_ip::client_ip;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use crate::handlers::AppState;
+
+/// Where a request came from, as recorded on sessions
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    /// The peer address, or the client a trusted proxy forwarded the request
+    /// for; never taken from headers the client controls
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl FromRequestParts<AppState> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        let user_agent = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|h| h.to_str().ok())
+            .map(String::from);
+
+        Ok(Self {
+            ip_address: client_ip(peer, &parts.headers, &state.config.trusted_proxies)
+                .map(|ip| ip.to_string()),
+            user_agent,
+        })
+    }
+}
@@ -1,20 +1,438 @@
+use common::client_ip;
+use ipnet::IpNet;
+use std::collections::HashMap;
 use std::env;
+use std::time::Duration;
+
+/// Most passwords `PASSWORD_HISTORY` can remember
+const MAX_PASSWORD_HISTORY: u64 = 24;
 
 pub struct Config {
     pub database_url: String,
+    /// Read replica that read-only admin queries are sent to; everything uses
+    /// the primary when unset
+    pub database_replica_url: Option<String>,
+    /// Connections each pool (primary and replica) may open
+    pub database_max_connections: u32,
+    /// Connections each pool keeps open even when idle
+    pub database_min_connections: u32,
+    /// How long a query waits for a free connection before failing
+    pub database_acquire_timeout_seconds: u64,
+    /// How long an idle connection above the minimum is kept
+    pub database_idle_timeout_seconds: u64,
+    /// Statements running longer are cancelled by the server; 0 disables the limit
+    pub database_statement_timeout_seconds: u64,
     pub jwt_secret: String,
+    pub jwt_issuer: String,
+    /// Audience this service requires on tokens presented to its own API
+    pub jwt_audience: String,
+    /// Every audience a token may be issued for
+    pub token_audiences: Vec<String>,
+    pub access_token_ttl_seconds: u64,
+    /// Per-audience overrides of `access_token_ttl_seconds`
+    pub audience_token_ttls: HashMap<String, u64>,
+    pub jwt_leeway_seconds: u64,
+    pub refresh_token_ttl_seconds: u64,
+    pub impersonation_ttl_seconds: u64,
+    pub invitation_ttl_seconds: u64,
+    /// Roles allowed to sign in with an emailed link; magic links are disabled when empty
+    pub magic_link_roles: Vec<String>,
+    /// Days a password stays valid, per account role; roles not listed never expire
+    pub password_max_age_days: HashMap<String, u64>,
+    /// Most recent passwords, the current one included, that may not be
+    /// reused, per account role
+    pub password_history: HashMap<String, u64>,
+    /// How long a login held for an expired password can be finished
+    pub password_change_ttl_seconds: u64,
+    pub magic_link_ttl_seconds: u64,
+    /// Links that may be requested for one address per `magic_link_rate_window_seconds`
+    pub magic_link_rate_limit: i64,
+    pub magic_link_rate_window_seconds: u64,
+    /// Bearer token accepted by the SCIM provisioning API; SCIM is disabled when unset
+    pub scim_token: Option<String>,
+    /// Slug of the organization SCIM provisions users and groups into
+    pub scim_organization: String,
+    /// Base URL of the auth service used in links sent to users
+    pub public_url: String,
+    pub mail_from: String,
+    /// Directory outgoing mail is written to; mail is only logged when unset
+    pub mail_outbox_dir: Option<String>,
+    /// Login backends tried in order, e.g. `local,ldap`
+    pub auth_backends: Vec<String>,
+    /// Directory settings, present when `LDAP_URL` is set
+    pub ldap: Option<LdapConfig>,
+    /// Allow browser clients to receive their tokens as cookies
+    pub session_cookies: bool,
+    pub cookie_secure: bool,
+    /// `SameSite` attribute of session cookies: `Strict`, `Lax` or `None`
+    pub cookie_same_site: String,
+    pub cookie_domain: Option<String>,
+    /// MaxMind GeoIP2/GeoLite2 City database used to locate logins
+    pub geoip_database: Option<String>,
+    /// Number of recent logins a new one is compared with
+    pub risk_history_size: i64,
+    /// Speed between two logins' locations above which travel is impossible
+    pub risk_max_travel_kmh: f64,
+    /// Failed attempts since the last login that make a success suspicious
+    pub risk_failed_attempts: i64,
+    pub risk_failure_window_seconds: u64,
+    /// How users hear about suspicious logins: `email`, `log` or `none`
+    pub risk_notifier: String,
+    /// Signals that require an emailed code before the login completes
+    pub risk_step_up: Vec<String>,
+    pub step_up_code_ttl_seconds: u64,
+    /// How long a login held for accepting new terms of service can be finished
+    pub consent_ttl_seconds: u64,
+    /// Proxies whose `X-Forwarded-For` is believed; the peer address is the
+    /// client's when empty
+    pub trusted_proxies: Vec<IpNet>,
+    /// Origins allowed to make credentialed requests; any origin may make
+    /// uncredentialed ones when empty
+    pub cors_allowed_origins: Vec<String>,
+    /// Weather service asked for quota usage; usage is not reported when unset
+    pub weather_service_url: Option<String>,
+    /// Audience of the token usage lookups are made with
+    pub weather_service_audience: String,
+    /// How often the webhook outbox is checked for due deliveries
+    pub webhook_poll_interval_seconds: u64,
+    pub webhook_timeout_seconds: u64,
+    /// Attempts before a webhook delivery is dead-lettered
+    pub webhook_max_attempts: i32,
+    /// Delay before the first retry, doubled for each further one
+    pub webhook_retry_base_seconds: u64,
+    /// Allow `http` webhook URLs and ones reaching internal addresses, for development
+    pub webhook_allow_insecure: bool,
+    /// How long the response to a request with an `Idempotency-Key` is replayed
+    pub idempotency_ttl_seconds: u64,
+    /// `<version>:<base64 key>` entries of the keys personal data is encrypted
+    /// with; personal data is stored in plaintext when neither this nor
+    /// `pii_key_file` is set
+    pub pii_encryption_keys: Option<String>,
+    /// File with one `<version>:<base64 key>` entry per line, and optionally
+    /// an `index:<base64 key>` entry for the blind index key
+    pub pii_key_file: Option<String>,
+    /// Version new values are encrypted with; the highest one when unset
+    pub pii_active_key_version: Option<u32>,
+    /// Base64 key of the blind index emails are looked up by
+    pub pii_index_key: Option<String>,
+    /// How often rows under other key versions, or still in plaintext, are re-encrypted
+    pub pii_reencrypt_interval_seconds: u64,
+    pub pii_reencrypt_batch_size: i64,
+    /// How long a role's permissions are cached; 0 disables the cache
+    pub permission_cache_ttl_seconds: u64,
     pub port: u16,
 }
 
+/// Directory the `ldap` login backend binds against
+#[derive(Clone)]
+pub struct LdapConfig {
+    pub url: String,
+    pub starttls: bool,
+    /// Service account used to search for users; searches anonymously when unset
+    pub bind_dn: Option<String>,
+    pub bind_password: String,
+    pub search_base: String,
+    /// Search filter where `{username}` stands for the escaped login name
+    pub user_filter: String,
+    pub email_attribute: String,
+    pub group_attribute: String,
+    /// Group DNs and the role their members receive, highest priority first
+    pub group_roles: Vec<(String, String)>,
+    /// Role of users in none of `group_roles`; such users are refused when unset
+    pub default_role: Option<String>,
+    /// Slug of the organization directory users are provisioned into
+    pub organization: String,
+    pub timeout_seconds: u64,
+}
+
+impl LdapConfig {
+    fn from_env() -> Option<Self> {
+        let url = env::var("LDAP_URL").ok().filter(|s| !s.is_empty())?;
+
+        Some(Self {
+            url,
+            starttls: env::var("LDAP_STARTTLS").is_ok_and(|s| s == "true" || s == "1"),
+            bind_dn: env::var("LDAP_BIND_DN").ok().filter(|s| !s.is_empty()),
+            bind_password: env::var("LDAP_BIND_PASSWORD").unwrap_or_default(),
+            search_base: env::var("LDAP_SEARCH_BASE").expect("LDAP_SEARCH_BASE must be set"),
+            user_filter: env::var("LDAP_USER_FILTER")
+                .unwrap_or_else(|_| "(uid={username})".to_string()),
+            email_attribute: env::var("LDAP_EMAIL_ATTRIBUTE")
+                .unwrap_or_else(|_| "mail".to_string()),
+            group_attribute: env::var("LDAP_GROUP_ATTRIBUTE")
+                .unwrap_or_else(|_| "memberOf".to_string()),
+            group_roles: env::var("LDAP_GROUP_ROLES")
+                .map(|s| parse_group_roles(&s))
+                .unwrap_or_default(),
+            default_role: match env::var("LDAP_DEFAULT_ROLE") {
+                Ok(role) => Some(role).filter(|r| !r.is_empty()),
+                Err(_) => Some("user".to_string()),
+            },
+            organization: env::var("LDAP_ORGANIZATION").unwrap_or_else(|_| "default".to_string()),
+            timeout_seconds: env::var("LDAP_TIMEOUT_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(5),
+        })
+    }
+}
+
 impl Config {
     pub fn from_env() -> Self {
+        let jwt_audience = env::var("JWT_AUDIENCE").unwrap_or_else(|_| "auth-service".to_string());
+
+        let mut token_audiences = env::var("JWT_TOKEN_AUDIENCES")
+            .map(|s| parse_list(&s))
+            .unwrap_or_else(|_| vec!["weather-service".to_string(), "time-service".to_string()]);
+        if !token_audiences.contains(&jwt_audience) {
+            token_audiences.insert(0, jwt_audience.clone());
+        }
+
         Self {
             database_url: env::var("DATABASE_URL").expect("DATABASE_URL must be set"),
+            database_replica_url: env::var("DATABASE_REPLICA_URL")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            database_max_connections: env::var("DATABASE_MAX_CONNECTIONS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(10),
+            database_min_connections: env::var("DATABASE_MIN_CONNECTIONS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0),
+            database_acquire_timeout_seconds: env::var("DATABASE_ACQUIRE_TIMEOUT_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(5),
+            database_idle_timeout_seconds: env::var("DATABASE_IDLE_TIMEOUT_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(600),
+            database_statement_timeout_seconds: env::var("DATABASE_STATEMENT_TIMEOUT_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0),
             jwt_secret: env::var("JWT_SECRET").unwrap_or_else(|_| "jwt-secret".to_string()),
+            jwt_issuer: env::var("JWT_ISSUER").unwrap_or_else(|_| "auth-service".to_string()),
+            jwt_audience,
+            token_audiences,
+            access_token_ttl_seconds: env::var("ACCESS_TOKEN_TTL_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(86400), // 24 hours default
+            audience_token_ttls: env::var("JWT_AUDIENCE_TTLS")
+                .map(|s| parse_amounts(&s))
+                .unwrap_or_default(),
+            jwt_leeway_seconds: env::var("JWT_LEEWAY_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(60),
+            refresh_token_ttl_seconds: env::var("REFRESH_TOKEN_TTL_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(30 * 86400), // 30 days default
+            impersonation_ttl_seconds: env::var("IMPERSONATION_TTL_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(900), // 15 minutes default
+            invitation_ttl_seconds: env::var("INVITATION_TTL_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(7 * 86400), // 7 days default
+            magic_link_roles: env::var("MAGIC_LINK_ROLES")
+                .map(|s| parse_list(&s))
+                .unwrap_or_default(),
+            password_max_age_days: env::var("PASSWORD_MAX_AGE_DAYS")
+                .map(|s| parse_amounts(&s))
+                .unwrap_or_default(),
+            password_history: env::var("PASSWORD_HISTORY")
+                .map(|s| parse_amounts(&s))
+                .unwrap_or_default(),
+            password_change_ttl_seconds: env::var("PASSWORD_CHANGE_TTL_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(900),
+            magic_link_ttl_seconds: env::var("MAGIC_LINK_TTL_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(900), // 15 minutes default
+            magic_link_rate_limit: env::var("MAGIC_LINK_RATE_LIMIT")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(3),
+            magic_link_rate_window_seconds: env::var("MAGIC_LINK_RATE_WINDOW_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(3600),
+            scim_token: env::var("SCIM_TOKEN").ok().filter(|s| !s.is_empty()),
+            scim_organization: env::var("SCIM_ORGANIZATION")
+                .unwrap_or_else(|_| "default".to_string()),
+            public_url: env::var("PUBLIC_URL")
+                .unwrap_or_else(|_| "http://localhost:3001".to_string()),
+            mail_from: env::var("MAIL_FROM").unwrap_or_else(|_| "no-reply@localhost".to_string()),
+            mail_outbox_dir: env::var("MAIL_OUTBOX_DIR").ok().filter(|s| !s.is_empty()),
+            auth_backends: env::var("AUTH_BACKENDS")
+                .map(|s| parse_list(&s))
+                .unwrap_or_else(|_| vec!["local".to_string()]),
+            ldap: LdapConfig::from_env(),
+            session_cookies: env::var("SESSION_COOKIES").is_ok_and(|s| s == "true" || s == "1"),
+            cookie_secure: !env::var("COOKIE_SECURE").is_ok_and(|s| s == "false" || s == "0"),
+            cookie_same_site: env::var("COOKIE_SAME_SITE").unwrap_or_else(|_| "Strict".to_string()),
+            cookie_domain: env::var("COOKIE_DOMAIN").ok().filter(|s| !s.is_empty()),
+            geoip_database: env::var("GEOIP_DATABASE").ok().filter(|s| !s.is_empty()),
+            risk_history_size: env::var("RISK_HISTORY_SIZE")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(20),
+            risk_max_travel_kmh: env::var("RISK_MAX_TRAVEL_KMH")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(1000.0),
+            risk_failed_attempts: env::var("RISK_FAILED_ATTEMPTS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(5),
+            risk_failure_window_seconds: env::var("RISK_FAILURE_WINDOW_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(3600),
+            risk_notifier: env::var("RISK_NOTIFIER").unwrap_or_else(|_| "email".to_string()),
+            risk_step_up: env::var("RISK_STEP_UP")
+                .map(|s| parse_list(&s))
+                .unwrap_or_default(),
+            step_up_code_ttl_seconds: env::var("STEP_UP_CODE_TTL_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(600), // 10 minutes default
+            consent_ttl_seconds: env::var("CONSENT_TTL_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(900),
+            trusted_proxies: env::var("TRUSTED_PROXIES")
+                .map(|s| {
+                    client_ip::parse_trusted_proxies(&s)
+                        .unwrap_or_else(|e| panic!("Invalid TRUSTED_PROXIES: {}", e))
+                })
+                .unwrap_or_default(),
+            cors_allowed_origins: env::var("CORS_ALLOWED_ORIGINS")
+                .map(|s| parse_list(&s))
+                .unwrap_or_default(),
+            weather_service_url: env::var("WEATHER_SERVICE_URL")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            weather_service_audience: env::var("WEATHER_SERVICE_AUDIENCE")
+                .unwrap_or_else(|_| "weather-service".to_string()),
+            webhook_poll_interval_seconds: env::var("WEBHOOK_POLL_INTERVAL_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(5),
+            webhook_timeout_seconds: env::var("WEBHOOK_TIMEOUT_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(10),
+            webhook_max_attempts: env::var("WEBHOOK_MAX_ATTEMPTS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(8),
+            webhook_retry_base_seconds: env::var("WEBHOOK_RETRY_BASE_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(30),
+            webhook_allow_insecure: env::var("WEBHOOK_ALLOW_INSECURE")
+                .is_ok_and(|s| s == "true" || s == "1"),
+            idempotency_ttl_seconds: env::var("IDEMPOTENCY_TTL_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(86400),
+            pii_encryption_keys: env::var("PII_ENCRYPTION_KEYS")
+                .ok()
+                .filter(|s| !s.is_empty()),
+            pii_key_file: env::var("PII_KEY_FILE").ok().filter(|s| !s.is_empty()),
+            pii_active_key_version: env::var("PII_ACTIVE_KEY_VERSION")
+                .ok()
+                .and_then(|s| s.parse().ok()),
+            pii_index_key: env::var("PII_INDEX_KEY").ok().filter(|s| !s.is_empty()),
+            pii_reencrypt_interval_seconds: env::var("PII_REENCRYPT_INTERVAL_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(300),
+            pii_reencrypt_batch_size: env::var("PII_REENCRYPT_BATCH_SIZE")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(100),
+            permission_cache_ttl_seconds: env::var("PERMISSION_CACHE_TTL_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(300),
             port: env::var("PORT")
                 .ok()
                 .and_then(|p| p.parse().ok())
                 .unwrap_or(3001),
         }
     }
+
+    /// Lifetime of an access token issued for `audience`, or for every audience when `None`
+    pub fn token_ttl(&self, audience: Option<&str>) -> Duration {
+        let seconds = audience
+            .and_then(|a| self.audience_token_ttls.get(a).copied())
+            .unwrap_or(self.access_token_ttl_seconds);
+
+        Duration::from_secs(seconds)
+    }
+
+    /// How long a password of an account with `role` stays valid; `None` when it never expires
+    pub fn password_max_age(&self, role: &str) -> Option<chrono::Duration> {
+        self.password_max_age_days
+            .get(role)
+            .filter(|days| **days > 0)
+            .map(|days| chrono::Duration::days((*days).min(36500) as i64))
+    }
+
+    /// Most recent passwords, the current one included, an account with
+    /// `role` may not reuse. Capped since checking each takes a bcrypt
+    /// comparison.
+    pub fn password_history_depth(&self, role: &str) -> i64 {
+        let depth = self.password_history.get(role).copied().unwrap_or(0);
+        depth.min(MAX_PASSWORD_HISTORY) as i64
+    }
+
+    /// Hashes of earlier passwords kept for an account with `role`
+    pub fn previous_passwords_kept(&self, role: &str) -> i64 {
+        (self.password_history_depth(role) - 1).max(0)
+    }
+}
+
+/// Parse a comma separated list, skipping empty entries
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Parse `name=number` pairs, e.g. `weather-service=3600,time-service=7200`
+fn parse_amounts(value: &str) -> HashMap<String, u64> {
+    parse_list(value)
+        .iter()
+        .filter_map(|pair| {
+            let (name, amount) = pair.split_once('=')?;
+            Some((name.trim().to_string(), amount.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+/// Parse `;` separated `<group DN>=<role>` pairs. DNs contain `=` themselves,
+/// so the role is whatever follows the last one.
+fn parse_group_roles(value: &str) -> Vec<(String, String)> {
+    value
+        .split(';')
+        .filter_map(|pair| {
+            let (dn, role) = pair.trim().rsplit_once('=')?;
+            Some((dn.trim().to_string(), role.trim().to_string()))
+        })
+        .filter(|(dn, role)| !dn.is_empty() && !role.is_empty())
+        .collect()
 }
@@ -0,0 +1,138 @@
+//! Browser session mode: tokens travel in cookies instead of response bodies.
+//!
+//! The access and refresh tokens are HttpOnly so scripts can never read them.
+//! Cookies are sent with cross-site requests too, so every state-changing
+//! request authenticated by cookie must echo the readable CSRF cookie in the
+//! `X-CSRF-Token` header. The CSRF token is signed for the session, which keeps
+//! a cookie planted by a sibling subdomain from passing the check.
+
+use axum::http::{HeaderMap, HeaderValue, Method, header};
+use axum::response::{IntoResponse, Json, Response};
+use common::errors::AppError;
+use common::models::LoginResponse;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::tokens;
+
+pub const ACCESS_COOKIE: &str = "access_token";
+pub const REFRESH_COOKIE: &str = "refresh_token";
+pub const CSRF_COOKIE: &str = "csrf_token";
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// The refresh cookie is only needed by the refresh and logout endpoints
+const REFRESH_PATH: &str = "/api/auth";
+const CSRF_PURPOSE: &str = "csrf";
+
+/// Value of cookie `name` in the request's `Cookie` headers
+pub fn get<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// Methods that must not change state and so need no CSRF token
+pub fn is_safe(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+fn set_cookie(config: &Config, name: &str, value: &str, path: &str, max_age: u64) -> HeaderValue {
+    let mut cookie = format!(
+        "{}={}; Path={}; Max-Age={}; SameSite={}",
+        name, value, path, max_age, config.cookie_same_site
+    );
+    if name != CSRF_COOKIE {
+        cookie.push_str("; HttpOnly");
+    }
+    if config.cookie_secure {
+        cookie.push_str("; Secure");
+    }
+    if let Some(domain) = &config.cookie_domain {
+        cookie.push_str("; Domain=");
+        cookie.push_str(domain);
+    }
+
+    HeaderValue::from_str(&cookie).expect("cookie values are URL-safe")
+}
+
+fn csrf_token(config: &Config, session_id: Uuid) -> String {
+    tokens::sign(&config.jwt_secret, CSRF_PURPOSE, &session_id.to_string())
+}
+
+/// Move the tokens of `response` into cookies; the body keeps only the user
+pub fn deliver(
+    config: &Config,
+    mut response: LoginResponse,
+    session_id: Uuid,
+    access_ttl: Duration,
+) -> Response {
+    let refresh_ttl = config.refresh_token_ttl_seconds;
+    let cookies = [
+        set_cookie(
+            config,
+            ACCESS_COOKIE,
+            &response.token,
+            "/",
+            access_ttl.as_secs(),
+        ),
+        set_cookie(
+            config,
+            REFRESH_COOKIE,
+            &response.refresh_token,
+            REFRESH_PATH,
+            refresh_ttl,
+        ),
+        set_cookie(
+            config,
+            CSRF_COOKIE,
+            &csrf_token(config, session_id),
+            "/",
+            refresh_ttl,
+        ),
+    ];
+    response.token.clear();
+    response.refresh_token.clear();
+
+    let mut http = Json(response).into_response();
+    for cookie in cookies {
+        http.headers_mut().append(header::SET_COOKIE, cookie);
+    }
+    http
+}
+
+/// Expire every session cookie
+pub fn clear(config: &Config, response: &mut Response) {
+    for (name, path) in [
+        (ACCESS_COOKIE, "/"),
+        (REFRESH_COOKIE, REFRESH_PATH),
+        (CSRF_COOKIE, "/"),
+    ] {
+        response
+            .headers_mut()
+            .append(header::SET_COOKIE, set_cookie(config, name, "", path, 0));
+    }
+}
+
+/// Double-submit check: the `X-CSRF-Token` header must repeat the CSRF cookie,
+/// and that token must have been issued for `session_id`
+pub fn check_csrf(config: &Config, headers: &HeaderMap, session_id: Uuid) -> Result<(), AppError> {
+    let submitted = headers
+        .get(CSRF_HEADER)
+        .and_then(|h| h.to_str().ok())
+        .ok_or_else(|| AppError::authorization("Missing X-CSRF-Token header"))?;
+
+    let valid = get(headers, CSRF_COOKIE) == Some(submitted)
+        && tokens::verify(&config.jwt_secret, CSRF_PURPOSE, submitted)
+            == Some(session_id.to_string().as_str());
+    if !valid {
+        return Err(AppError::authorization("Invalid CSRF token"));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use sqlx::PgPool;
+use sqlx::types::Json;
+use uuid::Uuid;
+
+#[derive(sqlx::FromRow)]
+pub struct AuditEntry {
+    pub id: Uuid,
+    pub actor_id: Option<Uuid>,
+    pub action: String,
+    pub target_user_id: Option<Uuid>,
+    pub ip_address: Option<String>,
+    pub details: Json<serde_json::Value>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl AuditEntry {
+    pub async fn record(
+        pool: &PgPool,
+        actor_id: Option<Uuid>,
+        action: &str,
+        target_user_id: Option<Uuid>,
+        ip_address: Option<&str>,
+        details: serde_json::Value,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO audit_log (actor_id, action, target_user_id, ip_address, details)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(actor_id)
+        .bind(action)
+        .bind(target_user_id)
+        .bind(ip_address)
+        .bind(Json(details))
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Most recent entries first, optionally limited to those involving `user_id`
+    /// or targeting members of `organization_id`
+    pub async fn list(
+        pool: &PgPool,
+        organization_id: Option<Uuid>,
+        user_id: Option<Uuid>,
+        action: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let entries = sqlx::query_as::<_, AuditEntry>(
+            r#"
+            SELECT id, actor_id, action, target_user_id, ip_address, details, created_at
+            FROM audit_log
+            WHERE ($1::uuid IS NULL OR target_user_id IN (
+                      SELECT user_id FROM organization_members WHERE organization_id = $1
+                  ))
+              AND ($2::uuid IS NULL OR actor_id = $2 OR target_user_id = $2)
+              AND ($3::text IS NULL OR action = $3)
+            ORDER BY created_at DESC
+            LIMIT $4
+            "#,
+        )
+        .bind(organization_id)
+        .bind(user_id)
+        .bind(action)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(entries)
+    }
+}
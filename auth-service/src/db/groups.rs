@@ -0,0 +1,241 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::queries::User;
+
+/// A group of users within an organization, with the permissions granted to it
+#[derive(sqlx::FromRow)]
+pub struct Group {
+    pub id: Uuid,
+    pub organization_id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub permissions: Vec<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Group {
+    pub async fn create(
+        pool: &PgPool,
+        organization_id: Uuid,
+        name: &str,
+        description: Option<&str>,
+    ) -> Result<Self, sqlx::Error> {
+        let group = sqlx::query_as::<_, Group>(
+            r#"
+            INSERT INTO groups (organization_id, name, description)
+            VALUES ($1, $2, $3)
+            RETURNING id, organization_id, name, description, ARRAY[]::text[] AS permissions, created_at
+            "#,
+        )
+        .bind(organization_id)
+        .bind(name)
+        .bind(description)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(group)
+    }
+
+    pub async fn find_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Self>, sqlx::Error> {
+        let group = sqlx::query_as::<_, Group>(
+            r#"
+            SELECT g.id, g.organization_id, g.name, g.description,
+                   COALESCE(array_agg(p.name::text ORDER BY p.name) FILTER (WHERE p.id IS NOT NULL), '{}') AS permissions,
+                   g.created_at
+            FROM groups g
+            LEFT JOIN group_permissions gp ON gp.group_id = g.id
+            LEFT JOIN permissions p ON p.id = gp.permission_id
+            WHERE g.id = $1
+            GROUP BY g.id
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(group)
+    }
+
+    /// Groups of one organization, or of every organization when `organization_id` is `None`
+    pub async fn list(
+        pool: &PgPool,
+        organization_id: Option<Uuid>,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let groups = sqlx::query_as::<_, Group>(
+            r#"
+            SELECT g.id, g.organization_id, g.name, g.description,
+                   COALESCE(array_agg(p.name::text ORDER BY p.name) FILTER (WHERE p.id IS NOT NULL), '{}') AS permissions,
+                   g.created_at
+            FROM groups g
+            LEFT JOIN group_permissions gp ON gp.group_id = g.id
+            LEFT JOIN permissions p ON p.id = gp.permission_id
+            WHERE $1::uuid IS NULL OR g.organization_id = $1
+            GROUP BY g.id
+            ORDER BY g.name
+            "#,
+        )
+        .bind(organization_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(groups)
+    }
+
+    /// Groups a user is a member of, across organizations
+    pub async fn list_for_user(pool: &PgPool, user_id: Uuid) -> Result<Vec<Self>, sqlx::Error> {
+        let groups = sqlx::query_as::<_, Group>(
+            r#"
+            SELECT g.id, g.organization_id, g.name, g.description,
+                   COALESCE(array_agg(p.name::text ORDER BY p.name) FILTER (WHERE p.id IS NOT NULL), '{}') AS permissions,
+                   g.created_at
+            FROM groups g
+            INNER JOIN group_members gm ON gm.group_id = g.id AND gm.user_id = $1
+            LEFT JOIN group_permissions gp ON gp.group_id = g.id
+            LEFT JOIN permissions p ON p.id = gp.permission_id
+            GROUP BY g.id
+            ORDER BY g.name
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(groups)
+    }
+
+    pub async fn rename(pool: &PgPool, id: Uuid, name: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE groups SET name = $1 WHERE id = $2
+            "#,
+        )
+        .bind(name)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn delete(pool: &PgPool, id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM groups WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Replace the permissions granted to a group. Unknown names are ignored, so
+    /// callers should check them with [`Group::known_permissions`] first.
+    pub async fn set_permissions(
+        pool: &PgPool,
+        id: Uuid,
+        permissions: &[String],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM group_permissions WHERE group_id = $1
+            "#,
+        )
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO group_permissions (group_id, permission_id)
+            SELECT $1, id FROM permissions WHERE name = ANY($2)
+            "#,
+        )
+        .bind(id)
+        .bind(permissions)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// The subset of `names` that are defined permissions
+    pub async fn known_permissions(
+        pool: &PgPool,
+        names: &[String],
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let known = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT name FROM permissions WHERE name = ANY($1)
+            "#,
+        )
+        .bind(names)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(known)
+    }
+
+    pub async fn add_member(pool: &PgPool, id: Uuid, user_id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO group_members (group_id, user_id)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn remove_member(
+        pool: &PgPool,
+        id: Uuid,
+        user_id: Uuid,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM group_members WHERE group_id = $1 AND user_id = $2
+            "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Members of a group, with `role` set to their role in the group's organization
+    pub async fn list_members(pool: &PgPool, id: Uuid) -> Result<Vec<User>, sqlx::Error> {
+        let users = sqlx::query_as::<_, User>(
+            r#"
+            SELECT u.id, u.username, u.email, u.password_hash, COALESCE(m.role, u.role) AS role,
+                   u.created_at, u.updated_at, u.last_login_at, u.last_login_ip, u.is_active,
+                   u.auth_source
+            FROM group_members gm
+            INNER JOIN groups g ON g.id = gm.group_id
+            INNER JOIN users u ON u.id = gm.user_id
+            LEFT JOIN organization_members m
+                ON m.organization_id = g.organization_id AND m.user_id = u.id
+            WHERE gm.group_id = $1
+            ORDER BY u.username
+            "#,
+        )
+        .bind(id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(users)
+    }
+}
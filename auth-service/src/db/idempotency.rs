@@ -0,0 +1,109 @@
+use sqlx::PgPool;
+
+/// A request made under an `Idempotency-Key`, and its response once it completed
+#[derive(sqlx::FromRow)]
+pub struct IdempotentRequest {
+    pub fingerprint: String,
+    /// `None` while the first request is still being handled
+    pub status_code: Option<i32>,
+    pub content_type: Option<String>,
+    pub response_body: Option<Vec<u8>>,
+}
+
+impl IdempotentRequest {
+    /// Reserve `key` for a request, unless a live record of it exists. Returns
+    /// `None` when reserved; the caller then runs the request and records its
+    /// response with [`Self::complete`] or gives the key up with [`Self::release`].
+    /// A reservation is held until `lease_until`, after which a retry takes it over.
+    pub async fn claim(
+        pool: &PgPool,
+        scope: &str,
+        key: &str,
+        fingerprint: &str,
+        lease_until: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            DELETE FROM idempotency_keys WHERE expires_at <= NOW()
+            "#,
+        )
+        .execute(pool)
+        .await?;
+
+        let inserted = sqlx::query(
+            r#"
+            INSERT INTO idempotency_keys (scope, key, fingerprint, expires_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (scope, key) DO NOTHING
+            "#,
+        )
+        .bind(scope)
+        .bind(key)
+        .bind(fingerprint)
+        .bind(lease_until)
+        .execute(pool)
+        .await?;
+        if inserted.rows_affected() > 0 {
+            return Ok(None);
+        }
+
+        let existing = sqlx::query_as::<_, IdempotentRequest>(
+            r#"
+            SELECT fingerprint, status_code, content_type, response_body
+            FROM idempotency_keys
+            WHERE scope = $1 AND key = $2
+            "#,
+        )
+        .bind(scope)
+        .bind(key)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(existing)
+    }
+
+    /// Store the response of a reserved request, kept until `expires_at`
+    pub async fn complete(
+        pool: &PgPool,
+        scope: &str,
+        key: &str,
+        status_code: i32,
+        content_type: Option<&str>,
+        response_body: &[u8],
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE idempotency_keys
+            SET status_code = $3, content_type = $4, response_body = $5, expires_at = $6
+            WHERE scope = $1 AND key = $2
+            "#,
+        )
+        .bind(scope)
+        .bind(key)
+        .bind(status_code)
+        .bind(content_type)
+        .bind(response_body)
+        .bind(expires_at)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Give up a reservation so the request can be retried under the same key
+    pub async fn release(pool: &PgPool, scope: &str, key: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            DELETE FROM idempotency_keys
+            WHERE scope = $1 AND key = $2 AND status_code IS NULL
+            "#,
+        )
+        .bind(scope)
+        .bind(key)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+#[derive(sqlx::FromRow)]
+pub struct Invitation {
+    pub id: Uuid,
+    pub organization_id: Uuid,
+    pub email: String,
+    pub role: String,
+    pub invited_by: Option<Uuid>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Invitation {
+    pub async fn create(
+        pool: &PgPool,
+        organization_id: Uuid,
+        email: &str,
+        role: &str,
+        nonce_hash: &str,
+        invited_by: Option<Uuid>,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Self, sqlx::Error> {
+        let invitation = sqlx::query_as::<_, Invitation>(
+            r#"
+            INSERT INTO invitations
+                (organization_id, email, role, nonce_hash, invited_by, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, organization_id, email, role, invited_by, created_at, expires_at
+            "#,
+        )
+        .bind(organization_id)
+        .bind(email)
+        .bind(role)
+        .bind(nonce_hash)
+        .bind(invited_by)
+        .bind(expires_at)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(invitation)
+    }
+
+    /// Invitation that has been neither accepted nor revoked, even if expired
+    pub async fn find_open(pool: &PgPool, id: Uuid) -> Result<Option<Self>, sqlx::Error> {
+        let invitation = sqlx::query_as::<_, Invitation>(
+            r#"
+            SELECT id, organization_id, email, role, invited_by, created_at, expires_at
+            FROM invitations
+            WHERE id = $1 AND accepted_at IS NULL AND revoked_at IS NULL
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(invitation)
+    }
+
+    /// Pending invitation for an email address, compared case-insensitively
+    pub async fn find_pending_for_email(
+        pool: &PgPool,
+        organization_id: Uuid,
+        email: &str,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let invitation = sqlx::query_as::<_, Invitation>(
+            r#"
+            SELECT id, organization_id, email, role, invited_by, created_at, expires_at
+            FROM invitations
+            WHERE organization_id = $1 AND LOWER(email) = LOWER($2)
+              AND accepted_at IS NULL AND revoked_at IS NULL AND expires_at > NOW()
+            "#,
+        )
+        .bind(organization_id)
+        .bind(email)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(invitation)
+    }
+
+    /// Pending invitations, newest first, optionally limited to one organization
+    pub async fn list_pending(
+        pool: &PgPool,
+        organization_id: Option<Uuid>,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let invitations = sqlx::query_as::<_, Invitation>(
+            r#"
+            SELECT id, organization_id, email, role, invited_by, created_at, expires_at
+            FROM invitations
+            WHERE ($1::uuid IS NULL OR organization_id = $1)
+              AND accepted_at IS NULL AND revoked_at IS NULL AND expires_at > NOW()
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(organization_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(invitations)
+    }
+
+    /// Invitations a user sent or accepted, in any state
+    pub async fn list_for_user(pool: &PgPool, user_id: Uuid) -> Result<Vec<Self>, sqlx::Error> {
+        let invitations = sqlx::query_as::<_, Invitation>(
+            r#"
+            SELECT id, organization_id, email, role, invited_by, created_at, expires_at
+            FROM invitations
+            WHERE invited_by = $1 OR accepted_user_id = $1
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(invitations)
+    }
+
+    /// Replace the nonce and expiry of an open invitation, invalidating any
+    /// token issued for it before. Expired invitations can be renewed.
+    pub async fn renew(
+        pool: &PgPool,
+        id: Uuid,
+        nonce_hash: &str,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let invitation = sqlx::query_as::<_, Invitation>(
+            r#"
+            UPDATE invitations SET nonce_hash = $2, expires_at = $3
+            WHERE id = $1 AND accepted_at IS NULL AND revoked_at IS NULL
+            RETURNING id, organization_id, email, role, invited_by, created_at, expires_at
+            "#,
+        )
+        .bind(id)
+        .bind(nonce_hash)
+        .bind(expires_at)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(invitation)
+    }
+
+    pub async fn revoke(pool: &PgPool, id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE invitations SET revoked_at = NOW()
+            WHERE id = $1 AND accepted_at IS NULL AND revoked_at IS NULL
+            "#,
+        )
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Mark a pending invitation accepted if `nonce_hash` is its current nonce.
+    /// Returns `None` when the invitation was already used, revoked, renewed or expired.
+    pub async fn accept<'e>(
+        executor: impl PgExecutor<'e>,
+        id: Uuid,
+        nonce_hash: &str,
+        user_id: Uuid,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let invitation = sqlx::query_as::<_, Invitation>(
+            r#"
+            UPDATE invitations SET accepted_at = NOW(), accepted_user_id = $3
+            WHERE id = $1 AND nonce_hash = $2
+              AND accepted_at IS NULL AND revoked_at IS NULL AND expires_at > NOW()
+            RETURNING id, organization_id, email, role, invited_by, created_at, expires_at
+            "#,
+        )
+        .bind(id)
+        .bind(nonce_hash)
+        .bind(user_id)
+        .fetch_optional(executor)
+        .await?;
+
+        Ok(invitation)
+    }
+}
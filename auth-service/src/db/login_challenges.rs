@@ -0,0 +1,133 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// A login held for the code emailed to the user
+pub const STEP_UP: &str = "step_up";
+/// A login held until the user accepts the current terms of service
+pub const CONSENT: &str = "consent";
+/// A login held until the user replaces their expired password
+pub const PASSWORD_CHANGE: &str = "password_change";
+
+#[derive(sqlx::FromRow)]
+pub struct LoginChallenge {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub organization_id: Option<Uuid>,
+    pub audience: Option<String>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl LoginChallenge {
+    pub async fn create(
+        pool: &PgPool,
+        user_id: Uuid,
+        organization_id: Option<Uuid>,
+        audience: Option<&str>,
+        kind: &str,
+        code_hash: &str,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Self, sqlx::Error> {
+        let challenge = sqlx::query_as::<_, LoginChallenge>(
+            r#"
+            INSERT INTO login_challenges
+                (user_id, organization_id, audience, kind, code_hash, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, user_id, organization_id, audience, expires_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(organization_id)
+        .bind(audience)
+        .bind(kind)
+        .bind(code_hash)
+        .bind(expires_at)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(challenge)
+    }
+
+    pub async fn find(pool: &PgPool, id: Uuid) -> Result<Option<Self>, sqlx::Error> {
+        let challenge = sqlx::query_as::<_, LoginChallenge>(
+            r#"
+            SELECT id, user_id, organization_id, audience, expires_at
+            FROM login_challenges
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(challenge)
+    }
+
+    /// A pending challenge of `kind` that `code_hash` would complete, left pending
+    pub async fn find_pending(
+        pool: &PgPool,
+        id: Uuid,
+        kind: &str,
+        code_hash: &str,
+        max_attempts: i32,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let challenge = sqlx::query_as::<_, LoginChallenge>(
+            r#"
+            SELECT id, user_id, organization_id, audience, expires_at
+            FROM login_challenges
+            WHERE id = $1 AND kind = $4 AND code_hash = $2 AND attempts < $3
+              AND completed_at IS NULL AND expires_at > NOW()
+            "#,
+        )
+        .bind(id)
+        .bind(code_hash)
+        .bind(max_attempts)
+        .bind(kind)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(challenge)
+    }
+
+    /// Complete a pending challenge of `kind` if `code_hash` matches. Returns
+    /// `None` when the code is wrong or the challenge is completed, expired or
+    /// out of attempts.
+    pub async fn complete(
+        pool: &PgPool,
+        id: Uuid,
+        kind: &str,
+        code_hash: &str,
+        max_attempts: i32,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let challenge = sqlx::query_as::<_, LoginChallenge>(
+            r#"
+            UPDATE login_challenges SET completed_at = NOW()
+            WHERE id = $1 AND kind = $4 AND code_hash = $2 AND attempts < $3
+              AND completed_at IS NULL AND expires_at > NOW()
+            RETURNING id, user_id, organization_id, audience, expires_at
+            "#,
+        )
+        .bind(id)
+        .bind(code_hash)
+        .bind(max_attempts)
+        .bind(kind)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(challenge)
+    }
+
+    /// Count a wrong code against a pending challenge
+    pub async fn record_attempt(pool: &PgPool, id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE login_challenges SET attempts = attempts + 1
+            WHERE id = $1 AND completed_at IS NULL
+            "#,
+        )
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+use sqlx::PgPool;
+
+use crate::identity;
+
+pub struct LoginFailure;
+
+impl LoginFailure {
+    /// Record a failed login under the case-folded `username`, so attempts
+    /// spelled in any case count against the same account
+    pub async fn record(
+        pool: &PgPool,
+        username: &str,
+        ip_address: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO login_failures (username, ip_address) VALUES ($1, $2)
+            "#,
+        )
+        .bind(identity::fold(username).to_lowercase())
+        .bind(ip_address)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Failed attempts for `username`, in any case, since `since`
+    pub async fn count_since(
+        pool: &PgPool,
+        username: &str,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<i64, sqlx::Error> {
+        let count = sqlx::query_scalar::<_, i64>(
+            r#"
+            SELECT COUNT(*) FROM login_failures
+            WHERE LOWER(username) = LOWER($1) AND created_at > $2
+            "#,
+        )
+        .bind(identity::fold(username))
+        .bind(since)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(count)
+    }
+}
@@ -0,0 +1,92 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(sqlx::FromRow)]
+pub struct MagicLink {
+    pub id: Uuid,
+    /// Account the link signs in to; `None` for requests that sent nothing
+    pub user_id: Option<Uuid>,
+}
+
+impl MagicLink {
+    /// Record a link request. `user_id` and `token_hash` are `None` when the
+    /// address has no account that may use magic links.
+    pub async fn create(
+        pool: &PgPool,
+        email: &str,
+        user_id: Option<Uuid>,
+        token_hash: Option<&str>,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Self, sqlx::Error> {
+        let link = sqlx::query_as::<_, MagicLink>(
+            r#"
+            INSERT INTO magic_links (email, user_id, token_hash, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, user_id
+            "#,
+        )
+        .bind(email)
+        .bind(user_id)
+        .bind(token_hash)
+        .bind(expires_at)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(link)
+    }
+
+    /// Requests made for an address since `since`, compared case-insensitively
+    pub async fn count_since(
+        pool: &PgPool,
+        email: &str,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<i64, sqlx::Error> {
+        let count = sqlx::query_scalar::<_, i64>(
+            r#"
+            SELECT COUNT(*) FROM magic_links
+            WHERE LOWER(email) = LOWER($1) AND created_at > $2
+            "#,
+        )
+        .bind(email)
+        .bind(since)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(count)
+    }
+
+    /// Delete links that expired and were requested before `window_start`,
+    /// the oldest request the rate limit still counts
+    pub async fn purge_expired(
+        pool: &PgPool,
+        window_start: chrono::DateTime<chrono::Utc>,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM magic_links WHERE expires_at <= NOW() AND created_at <= $1
+            "#,
+        )
+        .bind(window_start)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Mark the link with this token used. Returns `None` when it does not
+    /// exist, was already used or has expired.
+    pub async fn redeem(pool: &PgPool, token_hash: &str) -> Result<Option<Self>, sqlx::Error> {
+        let link = sqlx::query_as::<_, MagicLink>(
+            r#"
+            UPDATE magic_links SET used_at = NOW()
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+            RETURNING id, user_id
+            "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(link)
+    }
+}
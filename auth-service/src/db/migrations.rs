@@ -1,5 +1,23 @@
 use sqlx::PgPool;
-use tracing::info;
+use std::collections::BTreeMap;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::identity;
+use crate::pii;
+
+const USERNAME_INDEX: &str = "idx_users_username_lower";
+const EMAIL_INDEX: &str = "idx_users_email_lower";
+
+/// Accounts whose usernames or emails are the same once normalized and case-folded
+#[derive(serde::Serialize)]
+pub struct Collision {
+    /// `username` or `email`
+    pub field: &'static str,
+    pub key: String,
+    pub user_ids: Vec<Uuid>,
+    pub values: Vec<String>,
+}
 
 pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
     info!("Running database migrations...");
@@ -45,6 +63,529 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    sqlx::query(
+        r#"
+        ALTER TABLE users
+            ADD COLUMN IF NOT EXISTS last_login_at TIMESTAMP WITH TIME ZONE,
+            ADD COLUMN IF NOT EXISTS last_login_ip VARCHAR(64)
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS sessions (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            user_agent TEXT,
+            ip_address VARCHAR(64),
+            refresh_token_hash VARCHAR(64) UNIQUE NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+            last_seen_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+            expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            revoked_at TIMESTAMP WITH TIME ZONE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_sessions_user_id ON sessions (user_id)
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        ALTER TABLE sessions
+            ADD COLUMN IF NOT EXISTS impersonator_id UUID REFERENCES users(id) ON DELETE CASCADE
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS audit_log (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            actor_id UUID REFERENCES users(id) ON DELETE SET NULL,
+            action VARCHAR(100) NOT NULL,
+            target_user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+            ip_address VARCHAR(64),
+            details JSONB NOT NULL DEFAULT '{}',
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_audit_log_target ON audit_log (target_user_id, created_at)
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS organizations (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            name VARCHAR(255) NOT NULL,
+            slug VARCHAR(100) UNIQUE NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS organization_members (
+            organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            role VARCHAR(50) NOT NULL DEFAULT 'user',
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+            PRIMARY KEY (organization_id, user_id)
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_organization_members_user ON organization_members (user_id)
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // The first time the default organization is created, every existing user joins it
+    // keeping their current role
+    sqlx::query(
+        r#"
+        WITH created AS (
+            INSERT INTO organizations (name, slug) VALUES ('Default', 'default')
+            ON CONFLICT (slug) DO NOTHING
+            RETURNING id
+        )
+        INSERT INTO organization_members (organization_id, user_id, role)
+        SELECT created.id, users.id, users.role FROM created CROSS JOIN users
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        ALTER TABLE sessions
+            ADD COLUMN IF NOT EXISTS organization_id UUID
+                REFERENCES organizations(id) ON DELETE CASCADE
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS groups (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+            name VARCHAR(255) NOT NULL,
+            description TEXT,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+            UNIQUE (organization_id, name)
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS group_members (
+            group_id UUID NOT NULL REFERENCES groups(id) ON DELETE CASCADE,
+            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+            PRIMARY KEY (group_id, user_id)
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_group_members_user ON group_members (user_id)
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS group_permissions (
+            group_id UUID NOT NULL REFERENCES groups(id) ON DELETE CASCADE,
+            permission_id UUID NOT NULL REFERENCES permissions(id) ON DELETE CASCADE,
+            PRIMARY KEY (group_id, permission_id)
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Deactivated accounts keep their data but can no longer sign in
+    sqlx::query(
+        r#"
+        ALTER TABLE users ADD COLUMN IF NOT EXISTS is_active BOOLEAN NOT NULL DEFAULT TRUE
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Pending invitations; only the hash of each invitation's nonce is stored
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS invitations (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+            email VARCHAR(255) NOT NULL,
+            role VARCHAR(50) NOT NULL,
+            nonce_hash VARCHAR(64) NOT NULL,
+            invited_by UUID REFERENCES users(id) ON DELETE SET NULL,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+            expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            accepted_at TIMESTAMP WITH TIME ZONE,
+            accepted_user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+            revoked_at TIMESTAMP WITH TIME ZONE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_invitations_organization_email
+            ON invitations (organization_id, LOWER(email))
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Erased accounts are kept as anonymous rows so audit references stay intact
+    sqlx::query(
+        r#"
+        ALTER TABLE users
+            ADD COLUMN IF NOT EXISTS erased_at TIMESTAMP WITH TIME ZONE
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Where an account's credentials are checked: `local` (password_hash) or `ldap`
+    sqlx::query(
+        r#"
+        ALTER TABLE users
+            ADD COLUMN IF NOT EXISTS auth_source VARCHAR(20) NOT NULL DEFAULT 'local'
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // One row per magic link request, also for unknown addresses so the rate
+    // limit reveals nothing; only requests for an account carry a token
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS magic_links (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            email VARCHAR(255) NOT NULL,
+            user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+            token_hash VARCHAR(64) UNIQUE,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+            expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            used_at TIMESTAMP WITH TIME ZONE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_magic_links_email
+            ON magic_links (LOWER(email), created_at)
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_magic_links_expires_at ON magic_links (expires_at)
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Failed password logins, compared with the next successful one
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS login_failures (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            username VARCHAR(255) NOT NULL,
+            ip_address VARCHAR(64),
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        DROP INDEX IF EXISTS idx_login_failures_username
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Failures are counted case-insensitively; rows from before usernames
+    // were folded on insert may be in any case
+    sqlx::query(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_login_failures_username_lower
+            ON login_failures (LOWER(username), created_at)
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Risky logins waiting for the emailed step-up code
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS login_challenges (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            organization_id UUID REFERENCES organizations(id) ON DELETE CASCADE,
+            audience VARCHAR(255),
+            code_hash VARCHAR(64) NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+            expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            completed_at TIMESTAMP WITH TIME ZONE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Usage quotas enforced by weather-service, for a role or a single user;
+    // a user's own quota replaces their role's
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS quotas (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            role VARCHAR(50) UNIQUE,
+            user_id UUID UNIQUE REFERENCES users(id) ON DELETE CASCADE,
+            requests_per_day INTEGER,
+            max_cities INTEGER,
+            forecast_days INTEGER,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+            CHECK ((role IS NULL) <> (user_id IS NULL))
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Webhook subscriptions; organization_id NULL receives events for every user
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS webhooks (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            organization_id UUID REFERENCES organizations(id) ON DELETE CASCADE,
+            url TEXT NOT NULL,
+            secret VARCHAR(255) NOT NULL,
+            events TEXT[] NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Outbox of webhook deliveries, written in the transaction that changes the
+    // user and drained by the dispatcher
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS webhook_deliveries (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            webhook_id UUID NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+            event VARCHAR(100) NOT NULL,
+            payload JSONB NOT NULL,
+            status VARCHAR(20) NOT NULL DEFAULT 'pending',
+            attempts INTEGER NOT NULL DEFAULT 0,
+            last_status_code INTEGER,
+            last_error TEXT,
+            next_attempt_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+            delivered_at TIMESTAMP WITH TIME ZONE
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_due
+            ON webhook_deliveries (next_attempt_at) WHERE status = 'pending'
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Responses of requests made with an Idempotency-Key, replayed to retries.
+    // The status is NULL while the first request is in flight.
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS idempotency_keys (
+            scope VARCHAR(255) NOT NULL,
+            key VARCHAR(255) NOT NULL,
+            fingerprint VARCHAR(64) NOT NULL,
+            status_code INTEGER,
+            content_type VARCHAR(255),
+            response_body BYTEA,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+            expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            PRIMARY KEY (scope, key)
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_idempotency_keys_expires_at ON idempotency_keys (expires_at)
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Versions of the terms of service, the latest being current, and which
+    // of them each user accepted
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS terms_versions (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            version VARCHAR(50) UNIQUE NOT NULL,
+            content TEXT NOT NULL,
+            published_by UUID REFERENCES users(id) ON DELETE SET NULL,
+            published_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS terms_acceptances (
+            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            terms_id UUID NOT NULL REFERENCES terms_versions(id) ON DELETE CASCADE,
+            accepted_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            ip_address VARCHAR(64),
+            PRIMARY KEY (user_id, terms_id)
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_terms_acceptances_terms_id ON terms_acceptances (terms_id)
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Logins are held for a step-up code, for accepting new terms or for
+    // replacing an expired password
+    sqlx::query(
+        r#"
+        ALTER TABLE login_challenges
+            ADD COLUMN IF NOT EXISTS kind VARCHAR(20) NOT NULL DEFAULT 'step_up'
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Existing passwords count as set when this column is added, so enabling
+    // expiry does not lock everyone out at once
+    sqlx::query(
+        r#"
+        ALTER TABLE users
+            ADD COLUMN IF NOT EXISTS password_changed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS password_history (
+            id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            password_hash VARCHAR(255) NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE INDEX IF NOT EXISTS idx_password_history_user_id
+            ON password_history (user_id, created_at DESC)
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Personal data may be sealed (see `crate::pii`), which outgrows the
+    // original column sizes. Emails are then looked up by their blind index.
+    sqlx::query(
+        r#"
+        ALTER TABLE users
+            ALTER COLUMN email TYPE TEXT,
+            ALTER COLUMN last_login_ip TYPE TEXT,
+            ADD COLUMN IF NOT EXISTS email_index VARCHAR(64)
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_users_email_index ON users (email_index)
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    enforce_identity_uniqueness(pool).await?;
+
     // Insert default roles and permissions
     sqlx::query(
         r#"
@@ -70,6 +611,16 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    sqlx::query(
+        r#"
+        INSERT INTO role_permissions (role, permission_id)
+        SELECT 'super_admin', id FROM permissions
+        ON CONFLICT DO NOTHING
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
     sqlx::query(
         r#"
         INSERT INTO role_permissions (role, permission_id)
@@ -80,7 +631,253 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    install_permission_triggers(pool).await?;
+
     info!("Database migrations completed successfully");
     Ok(())
 }
 
+/// Notify `permissions::CHANGED_CHANNEL` whenever role grants change, however
+/// they are changed, with the role or an empty payload when every role is
+/// affected. Replicas starting together would race to replace the function
+/// and triggers, so they take turns.
+async fn install_permission_triggers(pool: &PgPool) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext('notify_role_permissions_changed'))")
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        r#"
+        CREATE OR REPLACE FUNCTION notify_role_permissions_changed() RETURNS trigger AS $$
+        BEGIN
+            IF TG_TABLE_NAME = 'role_permissions' AND TG_OP = 'DELETE' THEN
+                PERFORM pg_notify('role_permissions_changed', OLD.role);
+            ELSIF TG_TABLE_NAME = 'role_permissions' THEN
+                PERFORM pg_notify('role_permissions_changed', NEW.role);
+                IF TG_OP = 'UPDATE' AND OLD.role <> NEW.role THEN
+                    PERFORM pg_notify('role_permissions_changed', OLD.role);
+                END IF;
+            ELSE
+                PERFORM pg_notify('role_permissions_changed', '');
+            END IF;
+            RETURN NULL;
+        END;
+        $$ LANGUAGE plpgsql
+        "#,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("DROP TRIGGER IF EXISTS role_permissions_changed ON role_permissions")
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        r#"
+        CREATE TRIGGER role_permissions_changed
+            AFTER INSERT OR UPDATE OR DELETE ON role_permissions
+            FOR EACH ROW EXECUTE FUNCTION notify_role_permissions_changed()
+        "#,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    // Renaming a permission changes the grants of every role holding it
+    sqlx::query("DROP TRIGGER IF EXISTS permissions_changed ON permissions")
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        r#"
+        CREATE TRIGGER permissions_changed
+            AFTER UPDATE OR DELETE ON permissions
+            FOR EACH STATEMENT EXECUTE FUNCTION notify_role_permissions_changed()
+        "#,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Normalize existing usernames and emails and make both unique regardless of
+/// case. Colliding accounts are left alone and reported; the index for that
+/// field is created once an operator has resolved them.
+///
+/// Sealed emails cannot be compared in SQL, so with PII encryption enabled
+/// emails are only kept unique by their blind index: every account gets its
+/// `email_index` before anything is sealed, and the index on the lowercased
+/// column is dropped. Encryption refuses to start while emails collide.
+async fn enforce_identity_uniqueness(pool: &PgPool) -> Result<(), sqlx::Error> {
+    let sealing = pii::enabled();
+    let (username_indexed, email_indexed, unindexed) = sqlx::query_as::<_, (bool, bool, i64)>(
+        r#"
+        SELECT
+            EXISTS (SELECT 1 FROM pg_indexes WHERE tablename = 'users' AND indexname = $1),
+            EXISTS (SELECT 1 FROM pg_indexes WHERE tablename = 'users' AND indexname = $2),
+            (SELECT COUNT(*) FROM users WHERE email_index IS NULL AND erased_at IS NULL)
+        "#,
+    )
+    .bind(USERNAME_INDEX)
+    .bind(EMAIL_INDEX)
+    .fetch_one(pool)
+    .await?;
+    let settled = if sealing {
+        username_indexed && !email_indexed && unindexed == 0
+    } else {
+        username_indexed && email_indexed
+    };
+    if settled {
+        return Ok(());
+    }
+
+    let collisions = identity_collisions(pool).await?;
+    if sealing && collisions.iter().any(|c| c.field == "email") {
+        return Err(sqlx::Error::Configuration(
+            "Accounts share an email once case and Unicode form are ignored, so emails cannot \
+             be sealed; list them with `auth-admin users collisions` and resolve them first"
+                .into(),
+        ));
+    }
+    for collision in &collisions {
+        warn!(
+            field = collision.field,
+            key = %collision.key,
+            values = ?collision.values,
+            user_ids = ?collision.user_ids,
+            "Accounts collide once case and Unicode form are ignored; rename or merge them"
+        );
+    }
+    let colliding =
+        |field: &str, key: &str| collisions.iter().any(|c| c.field == field && c.key == key);
+
+    for (id, username, email) in identities(pool).await? {
+        let new_username = identity::fold(&username);
+        let new_email = identity::fold(&email).to_lowercase();
+        let rename =
+            new_username != username && !colliding("username", &new_username.to_lowercase());
+        let readdress = new_email != email && !colliding("email", &new_email);
+        if !rename && !readdress {
+            continue;
+        }
+
+        let email = if readdress { &new_email } else { &email };
+        sqlx::query(
+            r#"
+            UPDATE users SET username = $2, email = $3, email_index = $4 WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(if rename { &new_username } else { &username })
+        .bind(pii::seal(pii::EMAIL, email))
+        .bind(pii::email_index(email))
+        .execute(pool)
+        .await?;
+    }
+
+    if sealing {
+        backfill_email_index(pool).await?;
+        sqlx::query(&format!("DROP INDEX IF EXISTS {}", EMAIL_INDEX))
+            .execute(pool)
+            .await?;
+    }
+
+    for (field, index) in [("username", USERNAME_INDEX), ("email", EMAIL_INDEX)] {
+        if field == "email" && sealing {
+            continue;
+        }
+        if collisions.iter().any(|c| c.field == field) {
+            warn!(
+                field,
+                "Not enforcing case-insensitive uniqueness until collisions are resolved"
+            );
+            continue;
+        }
+        sqlx::query(&format!(
+            "CREATE UNIQUE INDEX IF NOT EXISTS {} ON users (LOWER({}))",
+            index, field
+        ))
+        .execute(pool)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Give every account that lacks one the blind index of its email. The
+/// unique index on `email_index` refuses duplicates, so this runs only once
+/// emails are known not to collide.
+async fn backfill_email_index(pool: &PgPool) -> Result<(), sqlx::Error> {
+    let users = sqlx::query_as::<_, (Uuid, String)>(
+        r#"
+        SELECT id, email FROM users WHERE email_index IS NULL AND erased_at IS NULL
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for (id, email) in &users {
+        let email = pii::open(pii::EMAIL, email).map_err(|e| pii::decode_error("email", e))?;
+        sqlx::query("UPDATE users SET email_index = $2 WHERE id = $1")
+            .bind(id)
+            .bind(pii::email_index(&email))
+            .execute(pool)
+            .await?;
+    }
+    if !users.is_empty() {
+        info!(count = users.len(), "Indexed emails ahead of sealing");
+    }
+
+    Ok(())
+}
+
+/// Groups of accounts sharing a username or email once both are normalized
+/// and case-folded, oldest account first
+pub async fn identity_collisions(pool: &PgPool) -> Result<Vec<Collision>, sqlx::Error> {
+    let users = identities(pool).await?;
+
+    let mut collisions = Vec::new();
+    for field in ["username", "email"] {
+        let mut groups: BTreeMap<String, Vec<(Uuid, String)>> = BTreeMap::new();
+        for (id, username, email) in &users {
+            let value = if field == "username" { username } else { email };
+            groups
+                .entry(identity::fold(value).to_lowercase())
+                .or_default()
+                .push((*id, value.clone()));
+        }
+
+        collisions.extend(
+            groups
+                .into_iter()
+                .filter(|(_, accounts)| accounts.len() > 1)
+                .map(|(key, accounts)| Collision {
+                    field,
+                    key,
+                    user_ids: accounts.iter().map(|(id, _)| *id).collect(),
+                    values: accounts.into_iter().map(|(_, value)| value).collect(),
+                }),
+        );
+    }
+
+    Ok(collisions)
+}
+
+/// Every user's id, username and opened email, oldest first
+async fn identities(pool: &PgPool) -> Result<Vec<(Uuid, String, String)>, sqlx::Error> {
+    let users = sqlx::query_as::<_, (Uuid, String, String)>(
+        r#"
+        SELECT id, username, email FROM users ORDER BY created_at
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    users
+        .into_iter()
+        .map(|(id, username, email)| {
+            let email = pii::open(pii::EMAIL, &email).map_err(|e| pii::decode_error("email", e))?;
+            Ok((id, username, email))
+        })
+        .collect()
+}
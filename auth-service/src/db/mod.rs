@@ -1,13 +1,107 @@
+pub mod audit;
+pub mod groups;
+pub mod idempotency;
+pub mod invitations;
+pub mod login_challenges;
+pub mod login_failures;
+pub mod magic_links;
 pub mod migrations;
+pub mod organizations;
+pub mod pii;
 pub mod queries;
+pub mod quotas;
+pub mod sessions;
+pub mod terms;
+pub mod webhooks;
 
+use serde::Serialize;
 use sqlx::PgPool;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tracing::warn;
 
-pub async fn create_pool(database_url: &str) -> Result<PgPool, sqlx::Error> {
-    let pool = PgPool::connect(database_url).await?;
+use crate::config::Config;
+
+/// Longest a health check waits for the database
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Connect to the primary and bring its schema up to date
+pub async fn create_pool(config: &Config) -> Result<PgPool, sqlx::Error> {
+    let pool = connect(config, &config.database_url).await?;
 
     // Run migrations
     migrations::run_migrations(&pool).await?;
 
     Ok(pool)
 }
+
+/// Connect to the read replica, if one is configured
+pub async fn create_replica_pool(config: &Config) -> Result<Option<PgPool>, sqlx::Error> {
+    match &config.database_replica_url {
+        Some(url) => Ok(Some(connect(config, url).await?)),
+        None => Ok(None),
+    }
+}
+
+/// A pool for `url` with the configured size, timeouts and statement timeout
+pub async fn connect(config: &Config, url: &str) -> Result<PgPool, sqlx::Error> {
+    let mut options = PgConnectOptions::from_str(url)?;
+    if config.database_statement_timeout_seconds > 0 {
+        let timeout = format!("{}s", config.database_statement_timeout_seconds);
+        options = options.options([("statement_timeout", timeout.as_str())]);
+    }
+
+    PgPoolOptions::new()
+        .max_connections(config.database_max_connections.max(1))
+        .min_connections(config.database_min_connections)
+        .acquire_timeout(Duration::from_secs(config.database_acquire_timeout_seconds))
+        .idle_timeout(Duration::from_secs(config.database_idle_timeout_seconds))
+        .connect_with(options)
+        .await
+}
+
+/// Whether a pool's database answers, and how the pool is used
+#[derive(Serialize)]
+pub struct PoolHealth {
+    pub reachable: bool,
+    /// Round trip of the check query
+    pub latency_ms: Option<u64>,
+    /// Open connections, idle or in use
+    pub connections: u32,
+    pub idle: usize,
+    pub max_connections: u32,
+}
+
+impl PoolHealth {
+    /// Errors are logged rather than reported, as the health endpoint is public
+    pub async fn check(pool: &PgPool, name: &str) -> Self {
+        let started = Instant::now();
+        let result = tokio::time::timeout(
+            HEALTH_TIMEOUT,
+            sqlx::query_scalar::<_, i32>("SELECT 1").fetch_one(pool),
+        )
+        .await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        let reachable = match result {
+            Ok(Ok(_)) => true,
+            Ok(Err(e)) => {
+                warn!(database = name, error = %e, "Database health check failed");
+                false
+            }
+            Err(_) => {
+                warn!(database = name, "Database health check timed out");
+                false
+            }
+        };
+
+        Self {
+            reachable,
+            latency_ms: reachable.then_some(latency_ms),
+            connections: pool.size(),
+            idle: pool.num_idle(),
+            max_connections: pool.options().get_max_connections(),
+        }
+    }
+}
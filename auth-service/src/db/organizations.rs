@@ -0,0 +1,211 @@
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::queries::User;
+
+#[derive(sqlx::FromRow)]
+pub struct Organization {
+    pub id: Uuid,
+    pub name: String,
+    pub slug: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A user's membership of an organization
+#[derive(sqlx::FromRow)]
+pub struct Membership {
+    pub organization_id: Uuid,
+    pub slug: String,
+    pub name: String,
+    pub role: String,
+}
+
+impl Organization {
+    pub const DEFAULT_SLUG: &'static str = "default";
+
+    pub async fn create(pool: &PgPool, name: &str, slug: &str) -> Result<Self, sqlx::Error> {
+        let org = sqlx::query_as::<_, Organization>(
+            r#"
+            INSERT INTO organizations (name, slug)
+            VALUES ($1, $2)
+            RETURNING id, name, slug, created_at
+            "#,
+        )
+        .bind(name)
+        .bind(slug)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(org)
+    }
+
+    pub async fn find_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Self>, sqlx::Error> {
+        let org = sqlx::query_as::<_, Organization>(
+            r#"
+            SELECT id, name, slug, created_at FROM organizations WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(org)
+    }
+
+    pub async fn find_by_slug(pool: &PgPool, slug: &str) -> Result<Option<Self>, sqlx::Error> {
+        let org = sqlx::query_as::<_, Organization>(
+            r#"
+            SELECT id, name, slug, created_at FROM organizations WHERE slug = $1
+            "#,
+        )
+        .bind(slug)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(org)
+    }
+
+    pub async fn list_all(pool: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        let orgs = sqlx::query_as::<_, Organization>(
+            r#"
+            SELECT id, name, slug, created_at FROM organizations ORDER BY name
+            "#,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(orgs)
+    }
+
+    /// Add a user to an organization, or change their role if already a member
+    pub async fn set_member<'e>(
+        executor: impl PgExecutor<'e>,
+        organization_id: Uuid,
+        user_id: Uuid,
+        role: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO organization_members (organization_id, user_id, role)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (organization_id, user_id) DO UPDATE SET role = EXCLUDED.role
+            "#,
+        )
+        .bind(organization_id)
+        .bind(user_id)
+        .bind(role)
+        .execute(executor)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Remove a user from an organization along with their memberships of its
+    /// groups, within `tx`
+    pub async fn remove_member(
+        tx: &mut Transaction<'_, Postgres>,
+        organization_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<bool, sqlx::Error> {
+        sqlx::query(
+            r#"
+            DELETE FROM group_members gm
+            USING groups g
+            WHERE gm.group_id = g.id AND g.organization_id = $1 AND gm.user_id = $2
+            "#,
+        )
+        .bind(organization_id)
+        .bind(user_id)
+        .execute(&mut **tx)
+        .await?;
+
+        let result = sqlx::query(
+            r#"
+            DELETE FROM organization_members WHERE organization_id = $1 AND user_id = $2
+            "#,
+        )
+        .bind(organization_id)
+        .bind(user_id)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Members of an organization, with `role` set to their role in it
+    pub async fn list_members(
+        pool: &PgPool,
+        organization_id: Uuid,
+    ) -> Result<Vec<User>, sqlx::Error> {
+        let users = sqlx::query_as::<_, User>(
+            r#"
+            SELECT u.id, u.username, u.email, u.password_hash, m.role, u.created_at, u.updated_at,
+                   u.last_login_at, u.last_login_ip, u.is_active,
+                   u.auth_source
+            FROM users u
+            INNER JOIN organization_members m ON m.user_id = u.id
+            WHERE m.organization_id = $1
+            ORDER BY u.created_at DESC
+            "#,
+        )
+        .bind(organization_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(users)
+    }
+}
+
+impl Membership {
+    /// Organizations a user belongs to, oldest membership first
+    pub async fn list_for_user(pool: &PgPool, user_id: Uuid) -> Result<Vec<Self>, sqlx::Error> {
+        let memberships = sqlx::query_as::<_, Membership>(
+            r#"
+            SELECT m.organization_id, o.slug, o.name, m.role
+            FROM organization_members m
+            INNER JOIN organizations o ON o.id = m.organization_id
+            WHERE m.user_id = $1
+            ORDER BY m.created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(memberships)
+    }
+
+    pub async fn find(
+        pool: &PgPool,
+        organization_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let membership = sqlx::query_as::<_, Membership>(
+            r#"
+            SELECT m.organization_id, o.slug, o.name, m.role
+            FROM organization_members m
+            INNER JOIN organizations o ON o.id = m.organization_id
+            WHERE m.organization_id = $1 AND m.user_id = $2
+            "#,
+        )
+        .bind(organization_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(membership)
+    }
+
+    pub async fn count_for_user(pool: &PgPool, user_id: Uuid) -> Result<i64, sqlx::Error> {
+        let count = sqlx::query_scalar::<_, i64>(
+            r#"
+            SELECT COUNT(*) FROM organization_members WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(count)
+    }
+}
@@ -0,0 +1,70 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Personal data of a user as stored, sealed or not
+#[derive(sqlx::FromRow)]
+pub struct StoredPii {
+    pub id: Uuid,
+    pub email: String,
+    pub last_login_ip: Option<String>,
+}
+
+impl StoredPii {
+    /// Up to `limit` users after `after`, by id, with a field that is not
+    /// sealed with `current` (an `enc:<version>:` prefix) or no blind index.
+    /// Erased accounts only hold placeholders and are left out.
+    pub async fn stale_page(
+        pool: &PgPool,
+        current: &str,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let rows = sqlx::query_as::<_, StoredPii>(
+            r#"
+            SELECT id, email, last_login_ip
+            FROM users
+            WHERE erased_at IS NULL
+              AND ($2::uuid IS NULL OR id > $2)
+              AND (email_index IS NULL
+                   OR NOT starts_with(email, $1)
+                   OR NOT starts_with(COALESCE(last_login_ip, $1), $1))
+            ORDER BY id
+            LIMIT $3
+            "#,
+        )
+        .bind(current)
+        .bind(after)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows)
+    }
+
+    /// Store the resealed fields, unless the row changed since it was read.
+    /// Returns whether it was updated.
+    pub async fn replace(
+        &self,
+        pool: &PgPool,
+        email: &str,
+        email_index: &str,
+        last_login_ip: Option<&str>,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE users SET email = $2, email_index = $3, last_login_ip = $4
+            WHERE id = $1 AND email = $5 AND last_login_ip IS NOT DISTINCT FROM $6
+            "#,
+        )
+        .bind(self.id)
+        .bind(email)
+        .bind(email_index)
+        .bind(last_login_ip)
+        .bind(&self.email)
+        .bind(&self.last_login_ip)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
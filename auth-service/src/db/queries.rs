@@ -1,7 +1,12 @@
-use sqlx::PgPool;
+use sqlx::postgres::PgRow;
+use sqlx::{FromRow, PgExecutor, PgPool, Postgres, Row, Transaction};
 use uuid::Uuid;
 
-#[derive(sqlx::FromRow)]
+use crate::identity;
+use crate::pii;
+
+/// A user account. `email` and `last_login_ip` are sealed when written and
+/// opened when read, see [`pii`].
 pub struct User {
     pub id: Uuid,
     pub username: String,
@@ -10,11 +15,40 @@ pub struct User {
     pub role: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
+    pub last_login_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_login_ip: Option<String>,
+    pub is_active: bool,
+    /// Login backend that owns the account's credentials
+    pub auth_source: String,
+}
+
+impl<'r> FromRow<'r, PgRow> for User {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        let email: String = row.try_get("email")?;
+        let last_login_ip: Option<String> = row.try_get("last_login_ip")?;
+
+        Ok(Self {
+            id: row.try_get("id")?,
+            username: row.try_get("username")?,
+            email: pii::open(pii::EMAIL, &email).map_err(|e| pii::decode_error("email", e))?,
+            password_hash: row.try_get("password_hash")?,
+            role: row.try_get("role")?,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+            last_login_at: row.try_get("last_login_at")?,
+            last_login_ip: last_login_ip
+                .map(|ip| pii::open(pii::LAST_LOGIN_IP, &ip))
+                .transpose()
+                .map_err(|e| pii::decode_error("last_login_ip", e))?,
+            is_active: row.try_get("is_active")?,
+            auth_source: row.try_get("auth_source")?,
+        })
+    }
 }
 
 impl User {
-    pub async fn create(
-        pool: &PgPool,
+    pub async fn create<'e>(
+        executor: impl PgExecutor<'e>,
         username: &str,
         email: &str,
         password_hash: &str,
@@ -22,33 +56,62 @@ impl User {
     ) -> Result<Self, sqlx::Error> {
         let user = sqlx::query_as::<_, User>(
             r#"
-            INSERT INTO users (username, email, password_hash, role)
-            VALUES ($1, $2, $3, $4)
-            RETURNING id, username, email, password_hash, role, created_at, updated_at
+            INSERT INTO users (username, email, email_index, password_hash, role)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id, username, email, password_hash, role, created_at, updated_at,
+                      last_login_at, last_login_ip, is_active, auth_source
             "#,
         )
         .bind(username)
-        .bind(email)
+        .bind(pii::seal(pii::EMAIL, email))
+        .bind(pii::email_index(email))
         .bind(password_hash)
         .bind(role)
-        .fetch_one(pool)
+        .fetch_one(executor)
         .await?;
 
         Ok(user)
     }
 
+    /// Case-insensitive lookup of the NFKC form of `username`. Should accounts
+    /// from before usernames were unique regardless of case collide, the one
+    /// matching exactly wins.
     pub async fn find_by_username(
         pool: &PgPool,
         username: &str,
     ) -> Result<Option<Self>, sqlx::Error> {
         let user = sqlx::query_as::<_, User>(
             r#"
-            SELECT id, username, email, password_hash, role, created_at, updated_at
+            SELECT id, username, email, password_hash, role, created_at, updated_at,
+                   last_login_at, last_login_ip, is_active, auth_source
             FROM users
-            WHERE username = $1
+            WHERE LOWER(username) = LOWER($1)
+            ORDER BY username = $1 DESC
+            LIMIT 1
             "#,
         )
-        .bind(username)
+        .bind(identity::fold(username))
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(user)
+    }
+
+    /// Lookup by blind index, or case-insensitively among rows still in
+    /// plaintext without one
+    pub async fn find_by_email(pool: &PgPool, email: &str) -> Result<Option<Self>, sqlx::Error> {
+        let user = sqlx::query_as::<_, User>(
+            r#"
+            SELECT id, username, email, password_hash, role, created_at, updated_at,
+                   last_login_at, last_login_ip, is_active, auth_source
+            FROM users
+            WHERE email_index = $2 OR (email_index IS NULL AND LOWER(email) = LOWER($1))
+            ORDER BY email = $1 DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(identity::fold(email))
+        .bind(pii::email_index(email))
         .fetch_optional(pool)
         .await?;
 
@@ -58,7 +121,8 @@ impl User {
     pub async fn find_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Self>, sqlx::Error> {
         let user = sqlx::query_as::<_, User>(
             r#"
-            SELECT id, username, email, password_hash, role, created_at, updated_at
+            SELECT id, username, email, password_hash, role, created_at, updated_at,
+                   last_login_at, last_login_ip, is_active, auth_source
             FROM users
             WHERE id = $1
             "#,
@@ -73,7 +137,8 @@ impl User {
     pub async fn list_all(pool: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
         let users = sqlx::query_as::<_, User>(
             r#"
-            SELECT id, username, email, password_hash, role, created_at, updated_at
+            SELECT id, username, email, password_hash, role, created_at, updated_at,
+                   last_login_at, last_login_ip, is_active, auth_source
             FROM users
             ORDER BY created_at DESC
             "#,
@@ -84,20 +149,54 @@ impl User {
         Ok(users)
     }
 
-    pub async fn delete(pool: &PgPool, id: Uuid) -> Result<bool, sqlx::Error> {
+    /// One page of users ordered by id, starting after `after`. With an organization,
+    /// only its members are listed and `role` is their role in it.
+    pub async fn list_page(
+        pool: &PgPool,
+        organization_id: Option<Uuid>,
+        after: Option<Uuid>,
+        limit: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let users = sqlx::query_as::<_, User>(
+            r#"
+            SELECT u.id, u.username, u.email, u.password_hash, COALESCE(m.role, u.role) AS role,
+                   u.created_at, u.updated_at, u.last_login_at, u.last_login_ip, u.is_active,
+                   u.auth_source
+            FROM users u
+            LEFT JOIN organization_members m ON m.user_id = u.id AND m.organization_id = $1
+            WHERE ($1::uuid IS NULL OR m.user_id IS NOT NULL)
+              AND ($2::uuid IS NULL OR u.id > $2)
+            ORDER BY u.id
+            LIMIT $3
+            "#,
+        )
+        .bind(organization_id)
+        .bind(after)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(users)
+    }
+
+    pub async fn delete<'e>(executor: impl PgExecutor<'e>, id: Uuid) -> Result<bool, sqlx::Error> {
         let result = sqlx::query(
             r#"
             DELETE FROM users WHERE id = $1
             "#,
         )
         .bind(id)
-        .execute(pool)
+        .execute(executor)
         .await?;
 
         Ok(result.rows_affected() > 0)
     }
 
-    pub async fn update_role(pool: &PgPool, id: Uuid, role: &str) -> Result<bool, sqlx::Error> {
+    pub async fn update_role<'e>(
+        executor: impl PgExecutor<'e>,
+        id: Uuid,
+        role: &str,
+    ) -> Result<bool, sqlx::Error> {
         let result = sqlx::query(
             r#"
             UPDATE users SET role = $1, updated_at = NOW() WHERE id = $2
@@ -105,19 +204,302 @@ impl User {
         )
         .bind(role)
         .bind(id)
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn update_profile(
+        pool: &PgPool,
+        id: Uuid,
+        username: &str,
+        email: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE users SET username = $1, email = $2, email_index = $3, updated_at = NOW()
+            WHERE id = $4
+            "#,
+        )
+        .bind(username)
+        .bind(pii::seal(pii::EMAIL, email))
+        .bind(pii::email_index(email))
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn set_active(pool: &PgPool, id: Uuid, active: bool) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE users SET is_active = $1, updated_at = NOW() WHERE id = $2
+            "#,
+        )
+        .bind(active)
+        .bind(id)
         .execute(pool)
         .await?;
 
         Ok(result.rows_affected() > 0)
     }
 
-    pub async fn get_permissions(pool: &PgPool, role: &str) -> Result<Vec<String>, sqlx::Error> {
+    pub async fn set_auth_source<'e>(
+        executor: impl PgExecutor<'e>,
+        id: Uuid,
+        auth_source: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE users SET auth_source = $1, updated_at = NOW() WHERE id = $2
+            "#,
+        )
+        .bind(auth_source)
+        .bind(id)
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Replace the password, keeping the hashes of up to `keep_previous`
+    /// earlier passwords for the reuse check
+    pub async fn update_password(
+        pool: &PgPool,
+        id: Uuid,
+        password_hash: &str,
+        keep_previous: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        // Erased and directory accounts have no password worth remembering
+        if keep_previous > 0 {
+            sqlx::query(
+                r#"
+                INSERT INTO password_history (user_id, password_hash)
+                SELECT id, password_hash FROM users
+                WHERE id = $1 AND auth_source = 'local' AND password_hash <> '!'
+                "#,
+            )
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let result = sqlx::query(
+            r#"
+            UPDATE users SET password_hash = $1, password_changed_at = NOW(), updated_at = NOW()
+            WHERE id = $2
+            "#,
+        )
+        .bind(password_hash)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM password_history
+            WHERE user_id = $1 AND id NOT IN (
+                SELECT id FROM password_history
+                WHERE user_id = $1
+                ORDER BY created_at DESC
+                LIMIT $2
+            )
+            "#,
+        )
+        .bind(id)
+        .bind(keep_previous)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// When the password was last set
+    pub async fn password_changed_at(
+        pool: &PgPool,
+        id: Uuid,
+    ) -> Result<Option<chrono::DateTime<chrono::Utc>>, sqlx::Error> {
+        let changed_at = sqlx::query_scalar::<_, chrono::DateTime<chrono::Utc>>(
+            r#"
+            SELECT password_changed_at FROM users WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(changed_at)
+    }
+
+    /// Hashes of up to `limit` earlier passwords, newest first
+    pub async fn previous_password_hashes(
+        pool: &PgPool,
+        id: Uuid,
+        limit: i64,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let hashes = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT password_hash FROM password_history
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(hashes)
+    }
+
+    pub async fn record_login(
+        pool: &PgPool,
+        id: Uuid,
+        ip_address: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE users SET last_login_at = NOW(), last_login_ip = $1 WHERE id = $2
+            "#,
+        )
+        .bind(ip_address.map(|ip| pii::seal(pii::LAST_LOGIN_IP, ip)))
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Strip personal data from an account while keeping its row, so audit
+    /// entries and other references to it stay valid. Sessions, memberships and
+    /// login details are deleted, the account is disabled and its name and email
+    /// replaced with placeholders, within `tx`. Returns `false` if the user does
+    /// not exist or was already erased.
+    pub async fn erase(tx: &mut Transaction<'_, Postgres>, id: Uuid) -> Result<bool, sqlx::Error> {
+        let account = sqlx::query_as::<_, (String, String)>(
+            r#"
+            SELECT username, email FROM users WHERE id = $1 AND erased_at IS NULL FOR UPDATE
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&mut **tx)
+        .await?;
+        let Some((username, email)) = account else {
+            return Ok(false);
+        };
+        let email = pii::open(pii::EMAIL, &email).map_err(|e| pii::decode_error("email", e))?;
+
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET username = 'erased-' || id, email = id || '@erased.invalid', email_index = NULL,
+                password_hash = '!', is_active = FALSE, last_login_at = NULL,
+                last_login_ip = NULL, erased_at = NOW(), updated_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+
+        for statement in [
+            "DELETE FROM sessions WHERE user_id = $1",
+            "DELETE FROM group_members WHERE user_id = $1",
+            "DELETE FROM organization_members WHERE user_id = $1",
+            "DELETE FROM login_challenges WHERE user_id = $1",
+            "DELETE FROM password_history WHERE user_id = $1",
+            // Acceptances of the terms stay on record, without the network address
+            "UPDATE terms_acceptances SET ip_address = NULL WHERE user_id = $1",
+            // Audit entries stay, minus the network address and any copied contact details
+            "UPDATE audit_log SET ip_address = NULL, details = details - 'email' - 'username' \
+             WHERE actor_id = $1 OR target_user_id = $1",
+            // Queued and logged webhook events keep the user id only
+            "UPDATE webhook_deliveries SET payload = payload #- '{data,email}' #- '{data,username}' \
+             WHERE payload->'data'->>'user_id' = $1::text",
+            // Stored responses to the user's own requests, and to any that
+            // returned their account, such as registering or creating it
+            "DELETE FROM idempotency_keys WHERE scope = $1::text \
+             OR position(convert_to($1::text, 'UTF8') IN response_body) > 0",
+        ] {
+            sqlx::query(statement).bind(id).execute(&mut **tx).await?;
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE invitations SET email = $1 || '@erased.invalid'
+            WHERE accepted_user_id = $1 OR LOWER(email) = LOWER($2)
+            "#,
+        )
+        .bind(id)
+        .bind(&email)
+        .execute(&mut **tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM magic_links WHERE user_id = $1 OR LOWER(email) = LOWER($2)
+            "#,
+        )
+        .bind(id)
+        .bind(&email)
+        .execute(&mut **tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            DELETE FROM login_failures WHERE LOWER(username) = LOWER($1)
+            "#,
+        )
+        .bind(&username)
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(true)
+    }
+
+    /// Permissions granted to `role` together with those granted to any of the
+    /// user's groups in `organization_id`
+    pub async fn get_permissions(
+        pool: &PgPool,
+        user_id: Uuid,
+        role: &str,
+        organization_id: Option<Uuid>,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let mut permissions = Self::role_permissions(pool, role).await?;
+        permissions.extend(Self::group_permissions(pool, user_id, organization_id).await?);
+        permissions.sort();
+        permissions.dedup();
+
+        Ok(permissions)
+    }
+
+    /// Roles granted at least one permission, which are the roles that can be assigned
+    pub async fn roles(pool: &PgPool) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT DISTINCT role FROM role_permissions ORDER BY role
+            "#,
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Permissions granted to `role` itself
+    pub async fn role_permissions(pool: &PgPool, role: &str) -> Result<Vec<String>, sqlx::Error> {
         let permissions = sqlx::query_scalar::<_, String>(
             r#"
             SELECT p.name
             FROM permissions p
             INNER JOIN role_permissions rp ON p.id = rp.permission_id
             WHERE rp.role = $1
+            ORDER BY p.name
             "#,
         )
         .bind(role)
@@ -126,4 +508,29 @@ impl User {
 
         Ok(permissions)
     }
+
+    /// Permissions granted to any of the user's groups in `organization_id`
+    pub async fn group_permissions(
+        pool: &PgPool,
+        user_id: Uuid,
+        organization_id: Option<Uuid>,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let permissions = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT DISTINCT p.name
+            FROM permissions p
+            INNER JOIN group_permissions gp ON p.id = gp.permission_id
+            INNER JOIN groups g ON g.id = gp.group_id
+            INNER JOIN group_members gm ON gm.group_id = g.id
+            WHERE gm.user_id = $1 AND g.organization_id = $2
+            ORDER BY p.name
+            "#,
+        )
+        .bind(user_id)
+        .bind(organization_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(permissions)
+    }
 }
@@ -0,0 +1,134 @@
+use common::models::Quota;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(sqlx::FromRow)]
+pub struct QuotaRow {
+    pub role: Option<String>,
+    pub user_id: Option<Uuid>,
+    pub requests_per_day: Option<i32>,
+    pub max_cities: Option<i32>,
+    pub forecast_days: Option<i32>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl QuotaRow {
+    pub fn quota(&self) -> Quota {
+        Quota {
+            requests_per_day: self.requests_per_day,
+            max_cities: self.max_cities,
+            forecast_days: self.forecast_days,
+        }
+    }
+
+    /// The quota that applies to a user: their own, otherwise their role's
+    pub async fn effective(
+        pool: &PgPool,
+        user_id: Uuid,
+        role: &str,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let quota = sqlx::query_as::<_, QuotaRow>(
+            r#"
+            SELECT role, user_id, requests_per_day, max_cities, forecast_days, updated_at
+            FROM quotas
+            WHERE user_id = $1 OR role = $2
+            ORDER BY user_id IS NULL
+            LIMIT 1
+            "#,
+        )
+        .bind(user_id)
+        .bind(role)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(quota)
+    }
+
+    pub async fn list_all(pool: &PgPool) -> Result<Vec<Self>, sqlx::Error> {
+        let quotas = sqlx::query_as::<_, QuotaRow>(
+            r#"
+            SELECT role, user_id, requests_per_day, max_cities, forecast_days, updated_at
+            FROM quotas
+            ORDER BY role NULLS LAST, user_id
+            "#,
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(quotas)
+    }
+
+    pub async fn set_for_role(
+        pool: &PgPool,
+        role: &str,
+        quota: &Quota,
+    ) -> Result<Self, sqlx::Error> {
+        let row = sqlx::query_as::<_, QuotaRow>(
+            r#"
+            INSERT INTO quotas (role, requests_per_day, max_cities, forecast_days)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (role) DO UPDATE
+            SET requests_per_day = $2, max_cities = $3, forecast_days = $4, updated_at = NOW()
+            RETURNING role, user_id, requests_per_day, max_cities, forecast_days, updated_at
+            "#,
+        )
+        .bind(role)
+        .bind(quota.requests_per_day)
+        .bind(quota.max_cities)
+        .bind(quota.forecast_days)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    pub async fn set_for_user(
+        pool: &PgPool,
+        user_id: Uuid,
+        quota: &Quota,
+    ) -> Result<Self, sqlx::Error> {
+        let row = sqlx::query_as::<_, QuotaRow>(
+            r#"
+            INSERT INTO quotas (user_id, requests_per_day, max_cities, forecast_days)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id) DO UPDATE
+            SET requests_per_day = $2, max_cities = $3, forecast_days = $4, updated_at = NOW()
+            RETURNING role, user_id, requests_per_day, max_cities, forecast_days, updated_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(quota.requests_per_day)
+        .bind(quota.max_cities)
+        .bind(quota.forecast_days)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(row)
+    }
+
+    pub async fn delete_for_role(pool: &PgPool, role: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM quotas WHERE role = $1
+            "#,
+        )
+        .bind(role)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn delete_for_user(pool: &PgPool, user_id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM quotas WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
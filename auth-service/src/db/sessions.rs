@@ -0,0 +1,231 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(sqlx::FromRow)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub organization_id: Option<Uuid>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub last_seen_at: chrono::DateTime<chrono::Utc>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    /// Admin who opened this session on the user's behalf
+    pub impersonator_id: Option<Uuid>,
+}
+
+/// Fields of a session being opened
+pub struct NewSession<'a> {
+    pub user_id: Uuid,
+    pub organization_id: Option<Uuid>,
+    pub user_agent: Option<&'a str>,
+    pub ip_address: Option<&'a str>,
+    pub refresh_token_hash: &'a str,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub impersonator_id: Option<Uuid>,
+}
+
+impl Session {
+    pub async fn create(pool: &PgPool, new: NewSession<'_>) -> Result<Self, sqlx::Error> {
+        let session = sqlx::query_as::<_, Session>(
+            r#"
+            INSERT INTO sessions
+                (user_id, organization_id, user_agent, ip_address, refresh_token_hash,
+                 expires_at, impersonator_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id, user_id, organization_id, user_agent, ip_address, created_at,
+                      last_seen_at, expires_at, impersonator_id
+            "#,
+        )
+        .bind(new.user_id)
+        .bind(new.organization_id)
+        .bind(new.user_agent)
+        .bind(new.ip_address)
+        .bind(new.refresh_token_hash)
+        .bind(new.expires_at)
+        .bind(new.impersonator_id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(session)
+    }
+
+    /// Find a live session by the hash of its refresh token
+    pub async fn find_active_by_refresh_hash(
+        pool: &PgPool,
+        refresh_token_hash: &str,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let session = sqlx::query_as::<_, Session>(
+            r#"
+            SELECT id, user_id, organization_id, user_agent, ip_address, created_at,
+                   last_seen_at, expires_at, impersonator_id
+            FROM sessions
+            WHERE refresh_token_hash = $1 AND revoked_at IS NULL AND expires_at > NOW()
+            "#,
+        )
+        .bind(refresh_token_hash)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(session)
+    }
+
+    pub async fn list_for_user(pool: &PgPool, user_id: Uuid) -> Result<Vec<Self>, sqlx::Error> {
+        let sessions = sqlx::query_as::<_, Session>(
+            r#"
+            SELECT id, user_id, organization_id, user_agent, ip_address, created_at,
+                   last_seen_at, expires_at, impersonator_id
+            FROM sessions
+            WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+            ORDER BY last_seen_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    /// Every session a user has had, including revoked and expired ones
+    pub async fn history_for_user(pool: &PgPool, user_id: Uuid) -> Result<Vec<Self>, sqlx::Error> {
+        let sessions = sqlx::query_as::<_, Session>(
+            r#"
+            SELECT id, user_id, organization_id, user_agent, ip_address, created_at,
+                   last_seen_at, expires_at, impersonator_id
+            FROM sessions
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    /// The user's own most recent logins, newest first; impersonation sessions are left out
+    pub async fn recent_for_user(
+        pool: &PgPool,
+        user_id: Uuid,
+        limit: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let sessions = sqlx::query_as::<_, Session>(
+            r#"
+            SELECT id, user_id, organization_id, user_agent, ip_address, created_at,
+                   last_seen_at, expires_at, impersonator_id
+            FROM sessions
+            WHERE user_id = $1 AND impersonator_id IS NULL
+            ORDER BY created_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(sessions)
+    }
+
+    pub async fn is_active(pool: &PgPool, id: Uuid) -> Result<bool, sqlx::Error> {
+        let active = sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM sessions
+                WHERE id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+            )
+            "#,
+        )
+        .bind(id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(active)
+    }
+
+    /// Record activity on a session, at most once a minute
+    pub async fn touch(pool: &PgPool, id: Uuid) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE sessions SET last_seen_at = NOW()
+            WHERE id = $1 AND last_seen_at < NOW() - INTERVAL '1 minute'
+            "#,
+        )
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Replace the refresh token of a session, extending its expiry. Fails if the
+    /// token was already rotated by a concurrent exchange.
+    pub async fn rotate_refresh_token(
+        pool: &PgPool,
+        id: Uuid,
+        previous_hash: &str,
+        refresh_token_hash: &str,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE sessions
+            SET refresh_token_hash = $1, expires_at = $2, last_seen_at = NOW()
+            WHERE id = $3 AND refresh_token_hash = $4 AND revoked_at IS NULL
+            "#,
+        )
+        .bind(refresh_token_hash)
+        .bind(expires_at)
+        .bind(id)
+        .bind(previous_hash)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Revoke a session belonging to `user_id`, invalidating its access and refresh tokens
+    pub async fn revoke(
+        pool: &PgPool,
+        id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let session = sqlx::query_as::<_, Session>(
+            r#"
+            UPDATE sessions SET revoked_at = NOW()
+            WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL
+            RETURNING id, user_id, organization_id, user_agent, ip_address, created_at,
+                      last_seen_at, expires_at, impersonator_id
+            "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(session)
+    }
+
+    /// Revoke every session of a user, optionally keeping the one in use
+    pub async fn revoke_all_for_user(
+        pool: &PgPool,
+        user_id: Uuid,
+        except: Option<Uuid>,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE sessions SET revoked_at = NOW()
+            WHERE user_id = $1 AND revoked_at IS NULL AND ($2::uuid IS NULL OR id <> $2)
+            "#,
+        )
+        .bind(user_id)
+        .bind(except)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,214 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// A published version of the terms of service
+#[derive(sqlx::FromRow)]
+pub struct Terms {
+    pub id: Uuid,
+    pub version: String,
+    pub content: String,
+    pub published_by: Option<Uuid>,
+    pub published_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A version with how many of the users in scope accepted it
+#[derive(sqlx::FromRow)]
+pub struct TermsSummary {
+    pub id: Uuid,
+    pub version: String,
+    pub published_at: chrono::DateTime<chrono::Utc>,
+    pub accepted: i64,
+    pub pending: i64,
+}
+
+/// A user in scope and when, if at all, they accepted a version
+#[derive(sqlx::FromRow)]
+pub struct AcceptanceStatus {
+    pub user_id: Uuid,
+    pub username: String,
+    pub version: String,
+    pub accepted_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub ip_address: Option<String>,
+}
+
+impl Terms {
+    pub async fn publish(
+        pool: &PgPool,
+        version: &str,
+        content: &str,
+        published_by: Option<Uuid>,
+    ) -> Result<Self, sqlx::Error> {
+        let terms = sqlx::query_as::<_, Terms>(
+            r#"
+            INSERT INTO terms_versions (version, content, published_by)
+            VALUES ($1, $2, $3)
+            RETURNING id, version, content, published_by, published_at
+            "#,
+        )
+        .bind(version)
+        .bind(content)
+        .bind(published_by)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(terms)
+    }
+
+    /// The latest version, which users must have accepted to sign in
+    pub async fn current(pool: &PgPool) -> Result<Option<Self>, sqlx::Error> {
+        let terms = sqlx::query_as::<_, Terms>(
+            r#"
+            SELECT id, version, content, published_by, published_at
+            FROM terms_versions
+            ORDER BY published_at DESC
+            LIMIT 1
+            "#,
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(terms)
+    }
+
+    pub async fn find_by_version(
+        pool: &PgPool,
+        version: &str,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let terms = sqlx::query_as::<_, Terms>(
+            r#"
+            SELECT id, version, content, published_by, published_at
+            FROM terms_versions
+            WHERE version = $1
+            "#,
+        )
+        .bind(version)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(terms)
+    }
+
+    /// Every version, newest first, with acceptance counts among the members of
+    /// `organization_id`, or among all users when `None`. Erased accounts are
+    /// not counted.
+    pub async fn summaries(
+        pool: &PgPool,
+        organization_id: Option<Uuid>,
+    ) -> Result<Vec<TermsSummary>, sqlx::Error> {
+        let summaries = sqlx::query_as::<_, TermsSummary>(
+            r#"
+            WITH scoped AS (
+                SELECT u.id FROM users u
+                WHERE u.erased_at IS NULL
+                  AND ($1::uuid IS NULL OR EXISTS (
+                      SELECT 1 FROM organization_members m
+                      WHERE m.user_id = u.id AND m.organization_id = $1))
+            )
+            SELECT t.id, t.version, t.published_at,
+                   COUNT(a.user_id) AS accepted,
+                   (SELECT COUNT(*) FROM scoped) - COUNT(a.user_id) AS pending
+            FROM terms_versions t
+            LEFT JOIN terms_acceptances a
+                ON a.terms_id = t.id AND a.user_id IN (SELECT id FROM scoped)
+            GROUP BY t.id
+            ORDER BY t.published_at DESC
+            "#,
+        )
+        .bind(organization_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(summaries)
+    }
+
+    /// Whether each user in scope accepted this version, those who have not first
+    pub async fn acceptance_report(
+        &self,
+        pool: &PgPool,
+        organization_id: Option<Uuid>,
+        pending_only: bool,
+    ) -> Result<Vec<AcceptanceStatus>, sqlx::Error> {
+        let report = sqlx::query_as::<_, AcceptanceStatus>(
+            r#"
+            SELECT u.id AS user_id, u.username, $2 AS version, a.accepted_at, a.ip_address
+            FROM users u
+            LEFT JOIN terms_acceptances a ON a.user_id = u.id AND a.terms_id = $1
+            WHERE u.erased_at IS NULL
+              AND ($3::uuid IS NULL OR EXISTS (
+                  SELECT 1 FROM organization_members m
+                  WHERE m.user_id = u.id AND m.organization_id = $3))
+              AND (NOT $4 OR a.user_id IS NULL)
+            ORDER BY a.accepted_at IS NOT NULL, u.username
+            "#,
+        )
+        .bind(self.id)
+        .bind(&self.version)
+        .bind(organization_id)
+        .bind(pending_only)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(report)
+    }
+
+    /// Record that `user_id` accepted this version. Accepting again keeps the
+    /// first acceptance.
+    pub async fn accept(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        ip_address: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            INSERT INTO terms_acceptances (user_id, terms_id, ip_address)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_id, terms_id) DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(self.id)
+        .bind(ip_address)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn is_accepted_by(&self, pool: &PgPool, user_id: Uuid) -> Result<bool, sqlx::Error> {
+        let accepted = sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM terms_acceptances WHERE user_id = $1 AND terms_id = $2
+            )
+            "#,
+        )
+        .bind(user_id)
+        .bind(self.id)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(accepted)
+    }
+}
+
+impl AcceptanceStatus {
+    /// Every version `user_id` accepted, newest first
+    pub async fn for_user(pool: &PgPool, user_id: Uuid) -> Result<Vec<Self>, sqlx::Error> {
+        let acceptances = sqlx::query_as::<_, AcceptanceStatus>(
+            r#"
+            SELECT u.id AS user_id, u.username, t.version, a.accepted_at, a.ip_address
+            FROM terms_acceptances a
+            INNER JOIN users u ON u.id = a.user_id
+            INNER JOIN terms_versions t ON t.id = a.terms_id
+            WHERE a.user_id = $1
+            ORDER BY a.accepted_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(acceptances)
+    }
+}
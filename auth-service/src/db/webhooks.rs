@@ -0,0 +1,270 @@
+use sqlx::types::Json;
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+#[derive(sqlx::FromRow)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub organization_id: Option<Uuid>,
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Webhook {
+    pub async fn create(
+        pool: &PgPool,
+        organization_id: Option<Uuid>,
+        url: &str,
+        secret: &str,
+        events: &[String],
+    ) -> Result<Self, sqlx::Error> {
+        let webhook = sqlx::query_as::<_, Webhook>(
+            r#"
+            INSERT INTO webhooks (organization_id, url, secret, events)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, organization_id, url, secret, events, created_at
+            "#,
+        )
+        .bind(organization_id)
+        .bind(url)
+        .bind(secret)
+        .bind(events)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(webhook)
+    }
+
+    /// Every subscription, or only those of `organization_id`
+    pub async fn list(
+        pool: &PgPool,
+        organization_id: Option<Uuid>,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let webhooks = sqlx::query_as::<_, Webhook>(
+            r#"
+            SELECT id, organization_id, url, secret, events, created_at
+            FROM webhooks
+            WHERE $1::uuid IS NULL OR organization_id = $1
+            ORDER BY created_at
+            "#,
+        )
+        .bind(organization_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(webhooks)
+    }
+
+    pub async fn find_by_id(pool: &PgPool, id: Uuid) -> Result<Option<Self>, sqlx::Error> {
+        let webhook = sqlx::query_as::<_, Webhook>(
+            r#"
+            SELECT id, organization_id, url, secret, events, created_at
+            FROM webhooks
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(webhook)
+    }
+
+    pub async fn delete(pool: &PgPool, id: Uuid) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM webhooks WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[derive(sqlx::FromRow)]
+pub struct Delivery {
+    pub id: Uuid,
+    pub event: String,
+    pub payload: Json<serde_json::Value>,
+    pub status: String,
+    pub attempts: i32,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub next_attempt_at: chrono::DateTime<chrono::Utc>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub delivered_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// A delivery claimed by the dispatcher, with where to send it
+#[derive(sqlx::FromRow)]
+pub struct DueDelivery {
+    pub id: Uuid,
+    pub event: String,
+    pub payload: Json<serde_json::Value>,
+    pub attempts: i32,
+    pub url: String,
+    pub secret: String,
+}
+
+impl Delivery {
+    /// Queue `payload` for every subscription to `event` that covers `user_id`:
+    /// platform subscriptions and those of the user's organizations. Run it in
+    /// the transaction making the change so events exist exactly when it commits.
+    pub async fn enqueue<'e>(
+        executor: impl PgExecutor<'e>,
+        event: &str,
+        user_id: Uuid,
+        payload: &serde_json::Value,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO webhook_deliveries (webhook_id, event, payload)
+            SELECT w.id, $1, $3
+            FROM webhooks w
+            WHERE $1 = ANY(w.events)
+              AND (w.organization_id IS NULL OR w.organization_id IN (
+                  SELECT organization_id FROM organization_members WHERE user_id = $2
+              ))
+            "#,
+        )
+        .bind(event)
+        .bind(user_id)
+        .bind(Json(payload))
+        .execute(executor)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Claim up to `limit` pending deliveries that are due. Claimed rows are
+    /// leased until `lease_until` so concurrent dispatchers skip them.
+    pub async fn claim_due(
+        pool: &PgPool,
+        limit: i64,
+        lease_until: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<DueDelivery>, sqlx::Error> {
+        let deliveries = sqlx::query_as::<_, DueDelivery>(
+            r#"
+            UPDATE webhook_deliveries d SET next_attempt_at = $2
+            FROM webhooks w
+            WHERE w.id = d.webhook_id AND d.id IN (
+                SELECT id FROM webhook_deliveries
+                WHERE status = 'pending' AND next_attempt_at <= NOW()
+                ORDER BY next_attempt_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING d.id, d.event, d.payload, d.attempts, w.url, w.secret
+            "#,
+        )
+        .bind(limit)
+        .bind(lease_until)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    pub async fn mark_delivered(
+        pool: &PgPool,
+        id: Uuid,
+        status_code: i32,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = 'delivered', attempts = attempts + 1, last_status_code = $2,
+                last_error = NULL, delivered_at = NOW()
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(status_code)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Record a failed attempt. With `retry_at` the delivery is tried again
+    /// then, otherwise it is dead-lettered.
+    pub async fn mark_failed(
+        pool: &PgPool,
+        id: Uuid,
+        status_code: Option<i32>,
+        error: &str,
+        retry_at: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = CASE WHEN $4::timestamptz IS NULL THEN 'dead' ELSE 'pending' END,
+                attempts = attempts + 1, last_status_code = $2, last_error = $3,
+                next_attempt_at = COALESCE($4, next_attempt_at)
+            WHERE id = $1
+            "#,
+        )
+        .bind(id)
+        .bind(status_code)
+        .bind(error)
+        .bind(retry_at)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Most recent deliveries of a subscription first
+    pub async fn list_for_webhook(
+        pool: &PgPool,
+        webhook_id: Uuid,
+        status: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<Self>, sqlx::Error> {
+        let deliveries = sqlx::query_as::<_, Delivery>(
+            r#"
+            SELECT id, event, payload, status, attempts, last_status_code, last_error,
+                   next_attempt_at, created_at, delivered_at
+            FROM webhook_deliveries
+            WHERE webhook_id = $1 AND ($2::text IS NULL OR status = $2)
+            ORDER BY created_at DESC
+            LIMIT $3
+            "#,
+        )
+        .bind(webhook_id)
+        .bind(status)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    /// Queue a dead delivery again with a fresh set of attempts
+    pub async fn retry(
+        pool: &PgPool,
+        webhook_id: Uuid,
+        id: Uuid,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        let delivery = sqlx::query_as::<_, Delivery>(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = 'pending', attempts = 0, next_attempt_at = NOW()
+            WHERE id = $1 AND webhook_id = $2 AND status = 'dead'
+            RETURNING id, event, payload, status, attempts, last_status_code, last_error,
+                      next_attempt_at, created_at, delivered_at
+            "#,
+        )
+        .bind(id)
+        .bind(webhook_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(delivery)
+    }
+}
@@ -0,0 +1,372 @@
+use axum::{
+    Extension,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use common::errors::{AppError, ErrorResponse};
+use common::models::{
+    ChangePasswordRequest, Claims, ConsentRequiredResponse, ExpiredPasswordChangeRequest,
+    LoginResponse, PasswordChangeRequiredResponse,
+};
+use std::time::Duration;
+use tracing::info;
+use uuid::Uuid;
+use validator::Validate;
+
+use super::{AppState, parse_id, session_response, start_session, terms, wants_cookies};
+use crate::client_info::ClientInfo;
+use crate::db::audit::AuditEntry;
+use crate::db::login_challenges::{self, LoginChallenge};
+use crate::db::queries::User;
+use crate::db::sessions::Session;
+use crate::tokens;
+
+/// Wrong change tokens a held login tolerates
+const MAX_ATTEMPTS: i32 = 5;
+
+/// When the password of `user` expired under their role's maximum age, if it has
+async fn password_expired_at(
+    state: &AppState,
+    user: &User,
+) -> Result<Option<chrono::DateTime<chrono::Utc>>, AppError> {
+    // Directory passwords expire under the directory's own policy
+    if user.auth_source != "local" {
+        return Ok(None);
+    }
+    let Some(max_age) = state.config.password_max_age(&user.role) else {
+        return Ok(None);
+    };
+
+    let changed_at = User::password_changed_at(&state.pool, user.id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get password age: {}", e)))?;
+
+    Ok(expiry(changed_at, max_age, chrono::Utc::now()))
+}
+
+/// When a password changed at `changed_at` expired under `max_age`, if it has by `now`
+fn expiry(
+    changed_at: Option<chrono::DateTime<chrono::Utc>>,
+    max_age: chrono::Duration,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Option<chrono::DateTime<chrono::Utc>> {
+    changed_at
+        .map(|changed_at| changed_at + max_age)
+        .filter(|expired_at| *expired_at <= now)
+}
+
+/// Refuse to extend a session whose password has expired since it was
+/// opened; the next login holds it until the password is changed
+pub(super) async fn require_current_password(
+    state: &AppState,
+    user: &User,
+) -> Result<(), AppError> {
+    match password_expired_at(state, user).await? {
+        Some(_) => Err(AppError::auth(
+            "Password expired; sign in again to change it",
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Reject `new_password` if it is one of the recent passwords `user` may not reuse
+pub(crate) async fn ensure_not_reused(
+    state: &AppState,
+    user: &User,
+    new_password: &str,
+) -> Result<(), AppError> {
+    let depth = state.config.password_history_depth(&user.role);
+    if depth == 0 {
+        return Ok(());
+    }
+
+    let mut hashes = vec![user.password_hash.clone()];
+    hashes.extend(
+        User::previous_password_hashes(&state.pool, user.id, depth - 1)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to get password history: {}", e)))?,
+    );
+
+    if hashes
+        .iter()
+        .any(|hash| bcrypt::verify(new_password, hash).unwrap_or(false))
+    {
+        return Err(AppError::validation(format!(
+            "New password must differ from your last {} passwords",
+            depth
+        )));
+    }
+
+    Ok(())
+}
+
+/// Hold a login until the user replaces their expired password. Returns `None`
+/// when the password has not expired.
+pub(super) async fn hold_for_password_change(
+    state: &AppState,
+    user: &User,
+    organization_id: Option<Uuid>,
+    audience: Option<&str>,
+) -> Result<Option<Response>, AppError> {
+    if !user.is_active {
+        return Err(AppError::auth("Account is disabled"));
+    }
+    let Some(expired_at) = password_expired_at(state, user).await? else {
+        return Ok(None);
+    };
+
+    let change_token = tokens::generate_opaque_token();
+    let expires_at =
+        chrono::Utc::now() + Duration::from_secs(state.config.password_change_ttl_seconds);
+    let challenge = LoginChallenge::create(
+        &state.pool,
+        user.id,
+        organization_id,
+        audience,
+        login_challenges::PASSWORD_CHANGE,
+        &tokens::hash_token(&change_token),
+        expires_at,
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to create login challenge: {}", e)))?;
+
+    info!(user_id = %user.id, "Login held for an expired password");
+
+    let response = PasswordChangeRequiredResponse {
+        challenge_id: challenge.id.to_string(),
+        change_token,
+        password_expired_at: expired_at.to_rfc3339(),
+        expires_at: challenge.expires_at.to_rfc3339(),
+    };
+    Ok(Some((StatusCode::ACCEPTED, Json(response)).into_response()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/me/password",
+    request_body = ChangePasswordRequest,
+    responses(
+        (status = 204, description = "Password changed; other sessions are revoked"),
+        (status = 400, description = "Validation error, or the password was used recently", body = ErrorResponse),
+        (status = 401, description = "Unauthorized or wrong current password"),
+        (status = 403, description = "Not permitted with an impersonation token")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "me"
+)]
+pub async fn change_password(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Json(payload): Json<ChangePasswordRequest>,
+) -> Result<StatusCode, AppError> {
+    payload.validate()?;
+    let user_id = parse_id(&claims.sub, "user")?;
+
+    let user = User::find_by_id(&state.pool, user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get user: {}", e)))?
+        .ok_or_else(|| AppError::http(404, "User not found"))?;
+
+    if user.auth_source != "local" {
+        return Err(AppError::validation(
+            "Password is managed by the directory and cannot be changed here",
+        ));
+    }
+
+    let is_valid = bcrypt::verify(&payload.current_password, &user.password_hash)
+        .map_err(|_| AppError::internal("Password verification failed"))?;
+
+    if !is_valid {
+        return Err(AppError::auth("Current password is incorrect"));
+    }
+
+    ensure_not_reused(&state, &user, &payload.new_password).await?;
+
+    let password_hash = bcrypt::hash(&payload.new_password, bcrypt::DEFAULT_COST)
+        .map_err(|e| AppError::internal(format!("Password hashing failed: {}", e)))?;
+
+    let keep_previous = state.config.previous_passwords_kept(&user.role);
+    User::update_password(&state.pool, user_id, &password_hash, keep_previous)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to update password: {}", e)))?;
+
+    // Sign out everywhere else; the session making the change stays valid
+    let current_session = claims.sid.as_deref().and_then(|s| s.parse().ok());
+    Session::revoke_all_for_user(&state.pool, user_id, current_session)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to revoke sessions: {}", e)))?;
+
+    AuditEntry::record(
+        &state.pool,
+        Some(user_id),
+        "password.change",
+        Some(user_id),
+        client.ip_address.as_deref(),
+        serde_json::json!({}),
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to write audit log: {}", e)))?;
+
+    info!(user_id = %user_id, "Password changed");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/login/password",
+    request_body = ExpiredPasswordChangeRequest,
+    responses(
+        (status = 200, description = "Password replaced and login completed; with `cookie` the tokens are set as cookies", body = LoginResponse),
+        (status = 202, description = "Current terms of service must be accepted first", body = ConsentRequiredResponse),
+        (status = 400, description = "Validation error, or the password was used recently", body = ErrorResponse),
+        (status = 401, description = "Invalid or expired change token")
+    ),
+    tag = "auth"
+)]
+pub async fn change_expired_password(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(payload): Json<ExpiredPasswordChangeRequest>,
+) -> Result<Response, AppError> {
+    let use_cookies = wants_cookies(&state, payload.cookie)?;
+    let challenge_id = parse_id(&payload.challenge_id, "challenge")?;
+    let code_hash = tokens::hash_token(&payload.change_token);
+    let invalid = || AppError::auth("Invalid or expired password change token");
+
+    // The token is checked before the new password, which may be rejected
+    // without using up the challenge
+    let pending = LoginChallenge::find_pending(
+        &state.pool,
+        challenge_id,
+        login_challenges::PASSWORD_CHANGE,
+        &code_hash,
+        MAX_ATTEMPTS,
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to get login challenge: {}", e)))?;
+
+    let Some(pending) = pending else {
+        LoginChallenge::record_attempt(&state.pool, challenge_id)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to update login challenge: {}", e)))?;
+        return Err(invalid());
+    };
+
+    payload.validate()?;
+
+    let user = User::find_by_id(&state.pool, pending.user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get user: {}", e)))?
+        .ok_or_else(invalid)?;
+
+    ensure_not_reused(&state, &user, &payload.new_password).await?;
+
+    let password_hash = bcrypt::hash(&payload.new_password, bcrypt::DEFAULT_COST)
+        .map_err(|e| AppError::internal(format!("Password hashing failed: {}", e)))?;
+
+    let challenge = LoginChallenge::complete(
+        &state.pool,
+        challenge_id,
+        login_challenges::PASSWORD_CHANGE,
+        &code_hash,
+        MAX_ATTEMPTS,
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to complete login challenge: {}", e)))?
+    .ok_or_else(invalid)?;
+
+    let keep_previous = state.config.previous_passwords_kept(&user.role);
+    User::update_password(&state.pool, user.id, &password_hash, keep_previous)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to update password: {}", e)))?;
+
+    // Sessions opened with the expired password end with it
+    Session::revoke_all_for_user(&state.pool, user.id, None)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to revoke sessions: {}", e)))?;
+
+    AuditEntry::record(
+        &state.pool,
+        Some(user.id),
+        "password.change",
+        Some(user.id),
+        client.ip_address.as_deref(),
+        serde_json::json!({ "reason": "expired" }),
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to write audit log: {}", e)))?;
+
+    info!(user_id = %user.id, "Expired password replaced");
+
+    if let Some(held) = terms::hold_for_consent(
+        &state,
+        &user,
+        challenge.organization_id,
+        challenge.audience.as_deref(),
+    )
+    .await?
+    {
+        return Ok(held);
+    }
+
+    let (session_id, response) = start_session(
+        &state,
+        user,
+        challenge.organization_id,
+        &client,
+        challenge.audience.as_deref(),
+    )
+    .await?;
+
+    Ok(session_response(
+        &state,
+        session_id,
+        response,
+        challenge.audience.as_deref(),
+        use_cookies,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn password_expires_once_it_reaches_the_maximum_age() {
+        let changed_at = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+        let max_age = chrono::Duration::days(90);
+        let expired_at = changed_at + max_age;
+
+        assert_eq!(
+            expiry(
+                Some(changed_at),
+                max_age,
+                expired_at - chrono::Duration::seconds(1)
+            ),
+            None
+        );
+        assert_eq!(
+            expiry(Some(changed_at), max_age, expired_at),
+            Some(expired_at)
+        );
+        assert_eq!(
+            expiry(
+                Some(changed_at),
+                max_age,
+                expired_at + chrono::Duration::days(30)
+            ),
+            Some(expired_at)
+        );
+    }
+
+    #[test]
+    fn password_without_a_change_date_does_not_expire() {
+        let now = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+
+        assert_eq!(expiry(None, chrono::Duration::days(1), now), None);
+    }
+}
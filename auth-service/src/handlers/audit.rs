@@ -0,0 +1,74 @@
+use axum::{
+    Extension,
+    extract::{Query, State},
+    response::Json,
+};
+use common::errors::AppError;
+use common::models::AuditEntryResponse;
+use serde::Deserialize;
+
+use super::{AppState, parse_id};
+use crate::db::audit::AuditEntry;
+use crate::scope::AdminScope;
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    pub user_id: Option<String>,
+    pub action: Option<String>,
+    pub limit: Option<i64>,
+}
+
+impl From<AuditEntry> for AuditEntryResponse {
+    fn from(entry: AuditEntry) -> Self {
+        Self {
+            id: entry.id.to_string(),
+            actor_id: entry.actor_id.map(|id| id.to_string()),
+            action: entry.action,
+            target_user_id: entry.target_user_id.map(|id| id.to_string()),
+            ip_address: entry.ip_address,
+            details: entry.details.0,
+            created_at: entry.created_at.to_rfc3339(),
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/audit",
+    params(
+        ("user_id" = Option<String>, Query, description = "Only entries where the user is actor or target"),
+        ("action" = Option<String>, Query, description = "Only entries with this action, e.g. impersonation.start"),
+        ("limit" = Option<i64>, Query, description = "Maximum entries to return (default 100, max 1000)")
+    ),
+    responses(
+        (status = 200, description = "Audit log entries, newest first; organization admins only see entries about their members", body = Vec<AuditEntryResponse>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn list_audit_log(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Query(params): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditEntryResponse>>, AppError> {
+    let user_id = params
+        .user_id
+        .as_deref()
+        .map(|id| parse_id(id, "user"))
+        .transpose()?;
+    let limit = params.limit.unwrap_or(100).clamp(1, 1000);
+
+    let entries = AuditEntry::list(
+        state.read_pool(),
+        scope.organization(),
+        user_id,
+        params.action.as_deref(),
+        limit,
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to read audit log: {}", e)))?;
+
+    Ok(Json(entries.into_iter().map(Into::into).collect()))
+}
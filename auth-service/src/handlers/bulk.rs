@@ -0,0 +1,608 @@
+use axum::{
+    Extension,
+    body::Body,
+    extract::{Query, State},
+    http::{HeaderMap, header},
+    response::{IntoResponse, Json, Response},
+};
+use common::errors::AppError;
+use common::models::{Claims, ImportReport, ImportRowResult, UserResponse};
+use common::validation;
+use futures::{StreamExt, TryStreamExt, stream};
+use serde::Deserialize;
+use sqlx::{Postgres, Transaction};
+use std::collections::HashSet;
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::{AppState, membership_role, parse_id};
+use crate::client_info::ClientInfo;
+use crate::csv;
+use crate::db::audit::AuditEntry;
+use crate::db::login_challenges::{self, LoginChallenge};
+use crate::db::organizations::Organization;
+use crate::db::queries::User;
+use crate::identity;
+use crate::mailer::Email;
+use crate::permissions;
+use crate::scope::AdminScope;
+use crate::tokens;
+use crate::webhooks::{self, UserEvent};
+
+const MAX_IMPORT_ROWS: usize = 5000;
+/// Passwords hashed at once on the blocking thread pool during an import
+const HASH_CONCURRENCY: usize = 4;
+/// Stored for imported users who did not get a password; matches none
+const NO_PASSWORD: &str = "!";
+const EXPORT_BATCH: i64 = 500;
+const EXPORT_COLUMNS: [&str; 9] = [
+    "id",
+    "username",
+    "email",
+    "role",
+    "is_active",
+    "created_at",
+    "updated_at",
+    "last_login_at",
+    "last_login_ip",
+];
+
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Csv,
+    JsonLines,
+}
+
+impl Format {
+    fn parse(format: &str) -> Result<Self, AppError> {
+        match format {
+            "csv" => Ok(Self::Csv),
+            "jsonl" | "ndjson" => Ok(Self::JsonLines),
+            other => Err(AppError::validation(format!(
+                "Unsupported format '{}', expected csv or jsonl",
+                other
+            ))),
+        }
+    }
+
+    /// The requested format, falling back to the request's content type
+    fn detect(format: Option<&str>, headers: &HeaderMap) -> Result<Self, AppError> {
+        if let Some(format) = format {
+            return Self::parse(format);
+        }
+
+        let content_type = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or_default();
+        if content_type.contains("csv") {
+            Ok(Self::Csv)
+        } else if content_type.contains("ndjson") || content_type.contains("jsonl") {
+            Ok(Self::JsonLines)
+        } else {
+            Err(AppError::validation(
+                "Specify ?format=csv|jsonl or a text/csv or application/x-ndjson content type",
+            ))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportRow {
+    username: String,
+    email: String,
+    #[serde(default)]
+    password: Option<String>,
+    #[serde(default)]
+    role: Option<String>,
+}
+
+/// A parsed input row, or why it could not be read, with its line number
+type ParsedRow = (usize, Result<ImportRow, String>);
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    pub format: Option<String>,
+    pub dry_run: Option<bool>,
+    pub send_invites: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    pub format: Option<String>,
+}
+
+/// Rows of the input with the line each starts on
+fn parse_rows(format: Format, body: &str) -> Result<Vec<ParsedRow>, AppError> {
+    match format {
+        Format::JsonLines => Ok(body
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| (i + 1, serde_json::from_str(line).map_err(|e| e.to_string())))
+            .collect()),
+        Format::Csv => {
+            let mut records = csv::parse(body).map_err(AppError::validation)?.into_iter();
+            let Some((_, header)) = records.next() else {
+                return Ok(Vec::new());
+            };
+
+            let column = |name: &str| {
+                header
+                    .iter()
+                    .position(|h| h.trim().eq_ignore_ascii_case(name))
+            };
+            let (Some(username), Some(email)) = (column("username"), column("email")) else {
+                return Err(AppError::validation(
+                    "CSV header must include username and email columns",
+                ));
+            };
+            let (password, role) = (column("password"), column("role"));
+
+            let field = |record: &[String], index: Option<usize>| {
+                index
+                    .and_then(|i| record.get(i))
+                    .map(|f| f.trim().to_string())
+                    .filter(|f| !f.is_empty())
+            };
+
+            Ok(records
+                .map(|(line, record)| {
+                    let row = ImportRow {
+                        username: field(&record, Some(username)).unwrap_or_default(),
+                        email: field(&record, Some(email)).unwrap_or_default(),
+                        password: field(&record, password),
+                        role: field(&record, role),
+                    };
+                    (line, Ok(row))
+                })
+                .collect())
+        }
+    }
+}
+
+/// Checks that need no database access, including duplicates within the
+/// input. Normalizes the row's username and email.
+fn validate_row(
+    row: &mut ImportRow,
+    scope: AdminScope,
+    usernames: &mut HashSet<String>,
+    emails: &mut HashSet<String>,
+) -> Result<(), String> {
+    if row.username.is_empty() || row.email.is_empty() {
+        return Err("Username and email are required".to_string());
+    }
+    row.username = identity::normalize_username(&row.username)?;
+    row.email = identity::normalize_email(&row.email)
+        .map_err(|_| format!("Invalid email '{}'", row.email))?;
+    if let Some(password) = &row.password {
+        validation::password(password).map_err(|e| format!("Password: {}", e))?;
+    }
+    if row.role.as_deref() == Some("super_admin") && scope.require_platform().is_err() {
+        return Err("Only super admins can assign the super_admin role".to_string());
+    }
+    if !usernames.insert(row.username.to_lowercase()) {
+        return Err(format!("Duplicate username '{}' in input", row.username));
+    }
+    if !emails.insert(row.email.clone()) {
+        return Err(format!("Duplicate email '{}' in input", row.email));
+    }
+    Ok(())
+}
+
+/// Insert one row inside its own savepoint so a failure only discards that row
+async fn insert_row(
+    tx: &mut Transaction<'_, Postgres>,
+    organization_id: Uuid,
+    row: &ImportRow,
+    role: &str,
+    password_hash: &str,
+) -> Result<Result<User, String>, sqlx::Error> {
+    sqlx::query("SAVEPOINT import_row")
+        .execute(&mut **tx)
+        .await?;
+
+    let inserted =
+        match User::create(&mut **tx, &row.username, &row.email, password_hash, role).await {
+            Ok(user) => match Organization::set_member(
+                &mut **tx,
+                organization_id,
+                user.id,
+                membership_role(role),
+            )
+            .await
+            {
+                Ok(()) => webhooks::enqueue(
+                    &mut **tx,
+                    UserEvent::Created,
+                    &user,
+                    serde_json::json!({ "organization_id": organization_id }),
+                )
+                .await
+                .map(|_| user),
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+
+    match inserted {
+        Ok(user) => {
+            sqlx::query("RELEASE SAVEPOINT import_row")
+                .execute(&mut **tx)
+                .await?;
+            Ok(Ok(user))
+        }
+        Err(e) => {
+            sqlx::query("ROLLBACK TO SAVEPOINT import_row")
+                .execute(&mut **tx)
+                .await?;
+            if e.to_string().contains("unique") {
+                Ok(Err("Username or email already exists".to_string()))
+            } else {
+                warn!(error = %e, username = %row.username, "Failed to import user");
+                Ok(Err("Failed to create user".to_string()))
+            }
+        }
+    }
+}
+
+/// Hash the passwords given in the input on the blocking thread pool, a few
+/// at a time, so the executor keeps serving requests meanwhile
+async fn hash_passwords(passwords: Vec<Option<String>>) -> Result<Vec<String>, AppError> {
+    stream::iter(passwords)
+        .map(|password| async move {
+            let Some(password) = password else {
+                return Ok(NO_PASSWORD.to_string());
+            };
+            tokio::task::spawn_blocking(move || bcrypt::hash(password, bcrypt::DEFAULT_COST))
+                .await
+                .map_err(|e| AppError::internal(format!("Password hashing failed: {}", e)))?
+                .map_err(|e| AppError::internal(format!("Password hashing failed: {}", e)))
+        })
+        .buffered(HASH_CONCURRENCY)
+        .try_collect()
+        .await
+}
+
+/// A link letting a user imported without a password choose one. It is a
+/// password change challenge, completed at `POST /api/auth/login/password`,
+/// valid as long as an invitation.
+async fn set_password_link(
+    state: &AppState,
+    user: &User,
+    organization_id: Uuid,
+) -> Result<String, sqlx::Error> {
+    let token = tokens::generate_opaque_token();
+    let expires_at = chrono::Utc::now() + Duration::from_secs(state.config.invitation_ttl_seconds);
+    let challenge = LoginChallenge::create(
+        &state.pool,
+        user.id,
+        Some(organization_id),
+        None,
+        login_challenges::PASSWORD_CHANGE,
+        &tokens::hash_token(&token),
+        expires_at,
+    )
+    .await?;
+
+    Ok(format!(
+        "{}/set-password?challenge_id={}&token={}",
+        state.config.public_url, challenge.id, token
+    ))
+}
+
+/// The account details; never a password. Users imported without one get a
+/// link to choose it instead.
+fn invite_email(state: &AppState, user: &User, set_password_link: Option<&str>) -> Email {
+    let mut body = format!(
+        "Hello {},\n\nAn account has been created for you.\n\nUsername: {}\n",
+        user.username, user.username
+    );
+    match set_password_link {
+        Some(link) => body.push_str(&format!(
+            "\nChoose your password at:\n{}\n\nThe link expires in {} hours.\n",
+            link,
+            state.config.invitation_ttl_seconds / 3600
+        )),
+        None => body.push_str(&format!("\nSign in at {}\n", state.config.public_url)),
+    }
+
+    Email {
+        to: user.email.clone(),
+        subject: "Your account has been created".to_string(),
+        body,
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/users/import",
+    params(
+        ("format" = Option<String>, Query, description = "csv or jsonl; defaults from the content type"),
+        ("dry_run" = Option<bool>, Query, description = "Validate every row without creating users"),
+        ("send_invites" = Option<bool>, Query, description = "Email each created user their account details")
+    ),
+    request_body(
+        content(
+            (String = "text/csv"),
+            (String = "application/x-ndjson")
+        ),
+        description = "CSV with a header row, or one JSON object per line. Columns/fields: username, email, password (optional), role (optional)"
+    ),
+    responses(
+        (status = 200, description = "Per-row import report", body = ImportReport),
+        (status = 400, description = "Unreadable input"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn import_users(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Query(params): Query<ImportQuery>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Json<ImportReport>, AppError> {
+    let format = Format::detect(params.format.as_deref(), &headers)?;
+    let rows = parse_rows(format, &body)?;
+    if rows.is_empty() {
+        return Err(AppError::validation("No rows to import"));
+    }
+    if rows.len() > MAX_IMPORT_ROWS {
+        return Err(AppError::validation(format!(
+            "At most {} rows can be imported at once",
+            MAX_IMPORT_ROWS
+        )));
+    }
+
+    let dry_run = params.dry_run.unwrap_or(false);
+    let send_invites = params.send_invites.unwrap_or(false) && !dry_run;
+    let organization_id = scope.home_organization(&state.pool, &claims).await?;
+
+    let mut usernames = HashSet::new();
+    let mut emails = HashSet::new();
+    let mut results = Vec::with_capacity(rows.len());
+    let mut valid = Vec::new();
+
+    // Every row is validated before the transaction opens
+    for (line, row) in rows {
+        let mut result = ImportRowResult {
+            line,
+            username: None,
+            status: "error".to_string(),
+            user_id: None,
+            error: None,
+            invited: false,
+        };
+
+        let row = row.and_then(|mut row| {
+            result.username = Some(row.username.clone());
+            validate_row(&mut row, scope, &mut usernames, &mut emails).map(|_| row)
+        });
+        let row = match row {
+            Ok(row) => row,
+            Err(e) => {
+                result.error = Some(e);
+                results.push(result);
+                continue;
+            }
+        };
+        if let Err(e) =
+            permissions::require_role(&state.pool, row.role.as_deref().unwrap_or("user")).await
+        {
+            result.error = Some(e.to_string());
+            results.push(result);
+            continue;
+        }
+
+        valid.push((results.len(), row));
+        results.push(result);
+    }
+
+    // bcrypt is slow, so passwords are hashed before the transaction opens
+    // rather than while it holds its locks. Dry runs are rolled back and
+    // skip it.
+    let password_hashes = if dry_run {
+        vec![NO_PASSWORD.to_string(); valid.len()]
+    } else {
+        hash_passwords(valid.iter().map(|(_, row)| row.password.clone()).collect()).await?
+    };
+
+    let mut created = Vec::new();
+    let mut tx = state
+        .pool
+        .begin()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to start transaction: {}", e)))?;
+
+    for ((index, row), password_hash) in valid.into_iter().zip(password_hashes) {
+        let role = row.role.as_deref().unwrap_or("user");
+        let result = &mut results[index];
+
+        match insert_row(&mut tx, organization_id, &row, role, &password_hash)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to import users: {}", e)))?
+        {
+            Ok(user) => {
+                result.status = if dry_run { "valid" } else { "created" }.to_string();
+                if !dry_run {
+                    result.user_id = Some(user.id.to_string());
+                    created.push((index, user, row.password.is_none()));
+                }
+            }
+            Err(e) => result.error = Some(e),
+        }
+    }
+
+    if dry_run {
+        tx.rollback().await
+    } else {
+        tx.commit().await
+    }
+    .map_err(|e| AppError::database(format!("Failed to finish import: {}", e)))?;
+
+    if send_invites {
+        for (index, user, without_password) in &created {
+            let link = if *without_password {
+                match set_password_link(&state, user, organization_id).await {
+                    Ok(link) => Some(link),
+                    Err(e) => {
+                        warn!(error = %e, user_id = %user.id, "Failed to create set-password link");
+                        continue;
+                    }
+                }
+            } else {
+                None
+            };
+            let email = invite_email(&state, user, link.as_deref());
+            match state.mailer.send(&email).await {
+                Ok(()) => results[*index].invited = true,
+                Err(e) => warn!(error = %e, user_id = %user.id, "Failed to send invite"),
+            }
+        }
+    }
+
+    let succeeded = results.iter().filter(|r| r.status != "error").count();
+    let report = ImportReport {
+        dry_run,
+        total: results.len(),
+        succeeded,
+        failed: results.len() - succeeded,
+        rows: results,
+    };
+
+    if !dry_run {
+        let actor = parse_id(&claims.sub, "user")?;
+        AuditEntry::record(
+            &state.pool,
+            Some(actor),
+            "users.import",
+            None,
+            client.ip_address.as_deref(),
+            serde_json::json!({
+                "organization_id": organization_id,
+                "created": report.succeeded,
+                "failed": report.failed,
+            }),
+        )
+        .await
+        .map_err(|e| AppError::database(format!("Failed to record audit entry: {}", e)))?;
+    }
+
+    info!(
+        organization_id = %organization_id,
+        dry_run,
+        succeeded = report.succeeded,
+        failed = report.failed,
+        "Users imported"
+    );
+
+    Ok(Json(report))
+}
+
+fn export_line(format: Format, user: User) -> String {
+    let user = UserResponse::from(user);
+    match format {
+        Format::JsonLines => {
+            let mut line = serde_json::to_string(&user).unwrap_or_default();
+            line.push('\n');
+            line
+        }
+        Format::Csv => csv::write_record(&[
+            user.id,
+            user.username,
+            user.email,
+            user.role,
+            user.is_active.to_string(),
+            user.created_at,
+            user.updated_at,
+            user.last_login_at.unwrap_or_default(),
+            user.last_login_ip.unwrap_or_default(),
+        ]),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/users/export",
+    params(
+        ("format" = Option<String>, Query, description = "csv (default) or jsonl")
+    ),
+    responses(
+        (status = 200, description = "Users streamed in id order; organization admins get their members", content((String = "text/csv"), (String = "application/x-ndjson"))),
+        (status = 400, description = "Unsupported format"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn export_users(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Query(params): Query<ExportQuery>,
+) -> Result<Response, AppError> {
+    let format = Format::parse(params.format.as_deref().unwrap_or("csv"))?;
+    let organization_id = scope.organization();
+
+    let actor = parse_id(&claims.sub, "user")?;
+    AuditEntry::record(
+        &state.pool,
+        Some(actor),
+        "users.export",
+        None,
+        client.ip_address.as_deref(),
+        serde_json::json!({ "organization_id": organization_id }),
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to record audit entry: {}", e)))?;
+
+    // Page through users by id so memory stays flat however many there are
+    let pages = stream::try_unfold(
+        (state.read_pool().clone(), None, false),
+        move |(pool, after, done)| async move {
+            if done {
+                return Ok(None);
+            }
+            let users = User::list_page(&pool, organization_id, after, EXPORT_BATCH).await?;
+            let Some(last) = users.last().map(|u| u.id) else {
+                return Ok(None);
+            };
+            let done = (users.len() as i64) < EXPORT_BATCH;
+            let chunk: String = users.into_iter().map(|u| export_line(format, u)).collect();
+            Ok::<_, sqlx::Error>(Some((chunk, (pool, Some(last), done))))
+        },
+    );
+
+    let header = match format {
+        Format::Csv => Some(Ok(csv::write_record(&EXPORT_COLUMNS))),
+        Format::JsonLines => None,
+    };
+    let body = Body::from_stream(stream::iter(header).chain(pages));
+
+    let (content_type, filename) = match format {
+        Format::Csv => ("text/csv; charset=utf-8", "users.csv"),
+        Format::JsonLines => ("application/x-ndjson", "users.jsonl"),
+    };
+
+    info!(organization_id = ?organization_id, "User export started");
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
@@ -0,0 +1,353 @@
+use axum::{
+    Extension,
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use common::errors::AppError;
+use common::models::{
+    Claims, CreateGroupRequest, GroupPermissionsRequest, GroupResponse, UserResponse,
+};
+use tracing::info;
+
+use super::{AppState, parse_id};
+use crate::db::groups::Group;
+use crate::db::organizations::{Membership, Organization};
+use crate::scope::AdminScope;
+
+impl From<Group> for GroupResponse {
+    fn from(group: Group) -> Self {
+        Self {
+            id: group.id.to_string(),
+            organization_id: group.organization_id.to_string(),
+            name: group.name,
+            description: group.description,
+            permissions: group.permissions,
+            created_at: group.created_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Load a group the admin may manage; groups of other organizations are not found
+async fn find_group(state: &AppState, scope: AdminScope, id: &str) -> Result<Group, AppError> {
+    let group_id = parse_id(id, "group")?;
+
+    let group = Group::find_by_id(&state.pool, group_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get group: {}", e)))?
+        .ok_or_else(|| AppError::http(404, "Group not found"))?;
+
+    match scope.organization() {
+        Some(org) if org != group.organization_id => Err(AppError::http(404, "Group not found")),
+        _ => Ok(group),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/groups",
+    responses(
+        (status = 200, description = "Groups visible to the admin", body = Vec<GroupResponse>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn list_groups(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+) -> Result<Json<Vec<GroupResponse>>, AppError> {
+    let groups = Group::list(&state.pool, scope.organization())
+        .await
+        .map_err(|e| AppError::database(format!("Failed to list groups: {}", e)))?;
+
+    Ok(Json(groups.into_iter().map(Into::into).collect()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/groups",
+    request_body = CreateGroupRequest,
+    responses(
+        (status = 200, description = "Group created", body = GroupResponse),
+        (status = 400, description = "Validation error"),
+        (status = 404, description = "Organization not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn create_group(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<CreateGroupRequest>,
+) -> Result<Json<GroupResponse>, AppError> {
+    if payload.name.is_empty() {
+        return Err(AppError::validation("Name is required"));
+    }
+
+    let organization_id = match payload.organization_id.as_deref() {
+        Some(id) => {
+            let org = parse_id(id, "organization")?;
+            scope.require_organization(org)?;
+            Organization::find_by_id(&state.pool, org)
+                .await
+                .map_err(|e| AppError::database(format!("Failed to get organization: {}", e)))?
+                .ok_or_else(|| AppError::http(404, "Organization not found"))?
+                .id
+        }
+        None => scope.home_organization(&state.pool, &claims).await?,
+    };
+
+    let group = Group::create(
+        &state.pool,
+        organization_id,
+        &payload.name,
+        payload.description.as_deref(),
+    )
+    .await
+    .map_err(|e| {
+        if e.to_string().contains("unique") {
+            AppError::validation("A group with this name already exists in the organization")
+        } else {
+            AppError::database(format!("Failed to create group: {}", e))
+        }
+    })?;
+
+    info!(group_id = %group.id, organization_id = %organization_id, "Group created");
+
+    Ok(Json(group.into()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/groups/{id}",
+    params(
+        ("id" = String, Path, description = "Group ID")
+    ),
+    responses(
+        (status = 200, description = "Group found", body = GroupResponse),
+        (status = 404, description = "Group not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn get_group(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Path(id): Path<String>,
+) -> Result<Json<GroupResponse>, AppError> {
+    let group = find_group(&state, scope, &id).await?;
+
+    Ok(Json(group.into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/admin/groups/{id}",
+    params(
+        ("id" = String, Path, description = "Group ID")
+    ),
+    responses(
+        (status = 204, description = "Group deleted"),
+        (status = 404, description = "Group not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn delete_group(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    let group = find_group(&state, scope, &id).await?;
+
+    let deleted = Group::delete(&state.pool, group.id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to delete group: {}", e)))?;
+
+    if deleted {
+        info!(group_id = %group.id, "Group deleted");
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::http(404, "Group not found"))
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/admin/groups/{id}/permissions",
+    params(
+        ("id" = String, Path, description = "Group ID")
+    ),
+    request_body = GroupPermissionsRequest,
+    responses(
+        (status = 200, description = "Permissions replaced", body = GroupResponse),
+        (status = 400, description = "Unknown permission"),
+        (status = 404, description = "Group not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Permission not held by the admin")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn set_group_permissions(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(mut payload): Json<GroupPermissionsRequest>,
+) -> Result<Json<GroupResponse>, AppError> {
+    let group = find_group(&state, scope, &id).await?;
+
+    payload.permissions.sort();
+    payload.permissions.dedup();
+
+    let known = Group::known_permissions(&state.pool, &payload.permissions)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get permissions: {}", e)))?;
+    if let Some(unknown) = payload.permissions.iter().find(|p| !known.contains(p)) {
+        return Err(AppError::validation(format!(
+            "Unknown permission '{}'",
+            unknown
+        )));
+    }
+
+    // Organization admins can only hand out permissions they hold themselves
+    if scope.organization().is_some()
+        && let Some(denied) = payload
+            .permissions
+            .iter()
+            .find(|p| !claims.permissions.contains(p))
+    {
+        return Err(AppError::authorization(format!(
+            "Cannot grant permission '{}' you do not hold",
+            denied
+        )));
+    }
+
+    Group::set_permissions(&state.pool, group.id, &payload.permissions)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to set permissions: {}", e)))?;
+
+    info!(group_id = %group.id, permissions = ?payload.permissions, "Group permissions updated");
+
+    Ok(Json(
+        Group {
+            permissions: payload.permissions,
+            ..group
+        }
+        .into(),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/groups/{id}/members",
+    params(
+        ("id" = String, Path, description = "Group ID")
+    ),
+    responses(
+        (status = 200, description = "Group members, with their role in the group's organization", body = Vec<UserResponse>),
+        (status = 404, description = "Group not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn list_group_members(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<UserResponse>>, AppError> {
+    let group = find_group(&state, scope, &id).await?;
+
+    let members = Group::list_members(&state.pool, group.id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to list group members: {}", e)))?;
+
+    Ok(Json(members.into_iter().map(UserResponse::from).collect()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/admin/groups/{id}/members/{user_id}",
+    params(
+        ("id" = String, Path, description = "Group ID"),
+        ("user_id" = String, Path, description = "User ID")
+    ),
+    responses(
+        (status = 204, description = "User added to the group"),
+        (status = 400, description = "User is not a member of the group's organization"),
+        (status = 404, description = "Group not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn add_group_member(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Path((id, user_id)): Path<(String, String)>,
+) -> Result<StatusCode, AppError> {
+    let group = find_group(&state, scope, &id).await?;
+    let user_id = parse_id(&user_id, "user")?;
+
+    Membership::find(&state.pool, group.organization_id, user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get membership: {}", e)))?
+        .ok_or_else(|| AppError::validation("User is not a member of the group's organization"))?;
+
+    Group::add_member(&state.pool, group.id, user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to add group member: {}", e)))?;
+
+    info!(group_id = %group.id, user_id = %user_id, "User added to group");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/admin/groups/{id}/members/{user_id}",
+    params(
+        ("id" = String, Path, description = "Group ID"),
+        ("user_id" = String, Path, description = "User ID")
+    ),
+    responses(
+        (status = 204, description = "User removed from the group"),
+        (status = 404, description = "Group or group membership not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn remove_group_member(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Path((id, user_id)): Path<(String, String)>,
+) -> Result<StatusCode, AppError> {
+    let group = find_group(&state, scope, &id).await?;
+    let user_id = parse_id(&user_id, "user")?;
+
+    let removed = Group::remove_member(&state.pool, group.id, user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to remove group member: {}", e)))?;
+
+    if removed {
+        info!(group_id = %group.id, user_id = %user_id, "User removed from group");
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::http(404, "Group membership not found"))
+    }
+}
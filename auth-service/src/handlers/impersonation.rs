@@ -0,0 +1,170 @@
+use axum::{
+    Extension,
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use common::errors::AppError;
+use common::models::{Claims, ImpersonationResponse};
+use tracing::{info, warn};
+
+use super::{AppState, Principal, issue_access_token, parse_id};
+use crate::client_info::ClientInfo;
+use crate::db::audit::AuditEntry;
+use crate::db::organizations::Membership;
+use crate::db::sessions::{NewSession, Session};
+use crate::scope::AdminScope;
+use crate::tokens;
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/users/{id}/impersonate",
+    params(
+        ("id" = String, Path, description = "User ID to impersonate")
+    ),
+    responses(
+        (status = 200, description = "Impersonation token issued", body = ImpersonationResponse),
+        (status = 400, description = "Validation error"),
+        (status = 404, description = "User not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn impersonate_user(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Path(id): Path<String>,
+) -> Result<Json<ImpersonationResponse>, AppError> {
+    let admin_id = parse_id(&claims.sub, "user")?;
+    let user_id = parse_id(&id, "user")?;
+
+    if claims.act.is_some() {
+        return Err(AppError::authorization(
+            "Cannot start an impersonation from an impersonation session",
+        ));
+    }
+    if user_id == admin_id {
+        return Err(AppError::validation("Cannot impersonate yourself"));
+    }
+
+    let user = scope.find_user(&state.pool, user_id).await?;
+
+    // Organization admins see the user inside their organization; super admins
+    // see them in the organization they would sign in to by default
+    let organization_id = match scope {
+        AdminScope::Organization(org) => Some(org),
+        AdminScope::Platform => Membership::list_for_user(&state.pool, user_id)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to get memberships: {}", e)))?
+            .first()
+            .map(|m| m.organization_id),
+    };
+    let principal = Principal::load(&state, user, organization_id).await?;
+
+    if principal.user.role == "admin" || principal.user.role == "super_admin" {
+        return Err(AppError::authorization(
+            "Admin accounts cannot be impersonated",
+        ));
+    }
+
+    // Impersonation sessions cannot be refreshed, so the refresh token is discarded
+    let session = Session::create(
+        &state.pool,
+        NewSession {
+            user_id,
+            organization_id,
+            user_agent: client.user_agent.as_deref(),
+            ip_address: client.ip_address.as_deref(),
+            refresh_token_hash: &tokens::hash_token(&tokens::generate_opaque_token()),
+            expires_at: chrono::Utc::now()
+                + chrono::Duration::seconds(state.config.impersonation_ttl_seconds as i64),
+            impersonator_id: Some(admin_id),
+        },
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to create session: {}", e)))?;
+
+    let token = issue_access_token(&state, &principal, &session, None).await?;
+    let user = principal.user;
+
+    AuditEntry::record(
+        &state.pool,
+        Some(admin_id),
+        "impersonation.start",
+        Some(user.id),
+        client.ip_address.as_deref(),
+        serde_json::json!({
+            "session_id": session.id,
+            "expires_at": session.expires_at.to_rfc3339(),
+        }),
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to write audit log: {}", e)))?;
+
+    warn!(admin_id = %admin_id, user_id = %user.id, session_id = %session.id, "Impersonation started");
+
+    Ok(Json(ImpersonationResponse {
+        token,
+        session_id: session.id.to_string(),
+        expires_at: session.expires_at.to_rfc3339(),
+        user: user.into(),
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/me/impersonation/end",
+    responses(
+        (status = 204, description = "Impersonation session ended"),
+        (status = 400, description = "Token is not an impersonation token"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "me"
+)]
+pub async fn end_impersonation(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+) -> Result<StatusCode, AppError> {
+    let actor = claims
+        .act
+        .as_ref()
+        .ok_or_else(|| AppError::validation("Not an impersonation session"))?;
+    let admin_id = parse_id(&actor.sub, "user")?;
+    let user_id = parse_id(&claims.sub, "user")?;
+    let session_id = parse_id(
+        claims
+            .sid
+            .as_deref()
+            .ok_or_else(|| AppError::validation("Not an impersonation session"))?,
+        "session",
+    )?;
+
+    Session::revoke(&state.pool, session_id, user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to revoke session: {}", e)))?
+        .ok_or_else(|| AppError::http(404, "Session not found"))?;
+
+    AuditEntry::record(
+        &state.pool,
+        Some(admin_id),
+        "impersonation.end",
+        Some(user_id),
+        client.ip_address.as_deref(),
+        serde_json::json!({
+            "session_id": session_id,
+            "impersonator_id": admin_id,
+        }),
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to write audit log: {}", e)))?;
+
+    info!(admin_id = %admin_id, user_id = %user_id, session_id = %session_id, "Impersonation ended");
+
+    Ok(StatusCode::NO_CONTENT)
+}
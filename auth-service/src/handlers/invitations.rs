@@ -0,0 +1,481 @@
+use axum::{
+    Extension,
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use common::errors::AppError;
+use common::models::{
+    AcceptInvitationRequest, Claims, CreateInvitationRequest, InvitationResponse, UserResponse,
+};
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+use validator::Validate;
+
+use super::{AppState, membership_role, parse_id};
+use crate::client_info::ClientInfo;
+use crate::db::audit::AuditEntry;
+use crate::db::invitations::Invitation;
+use crate::db::organizations::Organization;
+use crate::db::queries::User;
+use crate::identity;
+use crate::mailer::Email;
+use crate::permissions;
+use crate::scope::AdminScope;
+use crate::tokens;
+use crate::webhooks::{self, UserEvent};
+
+/// Binds invitation tokens to this flow so no other signed token is accepted
+const TOKEN_PURPOSE: &str = "invitation";
+
+impl From<Invitation> for InvitationResponse {
+    fn from(invitation: Invitation) -> Self {
+        Self {
+            id: invitation.id.to_string(),
+            organization_id: invitation.organization_id.to_string(),
+            email: invitation.email,
+            role: invitation.role,
+            invited_by: invitation.invited_by.map(|id| id.to_string()),
+            created_at: invitation.created_at.to_rfc3339(),
+            expires_at: invitation.expires_at.to_rfc3339(),
+        }
+    }
+}
+
+/// A fresh nonce and expiry for an invitation; only the nonce's hash is stored
+fn new_nonce(state: &AppState) -> (String, chrono::DateTime<chrono::Utc>) {
+    let expires_at = chrono::Utc::now() + Duration::from_secs(state.config.invitation_ttl_seconds);
+    (tokens::generate_opaque_token(), expires_at)
+}
+
+/// Signed token naming the invitation, its expiry and the current nonce
+fn invitation_token(state: &AppState, invitation: &Invitation, nonce: &str) -> String {
+    let payload = format!(
+        "{}.{}.{}",
+        invitation.id,
+        invitation.expires_at.timestamp(),
+        nonce
+    );
+    tokens::sign(&state.config.jwt_secret, TOKEN_PURPOSE, &payload)
+}
+
+/// Check an invitation token's signature and expiry, returning the invitation
+/// id and the hash of its nonce
+fn parse_token(state: &AppState, token: &str) -> Result<(Uuid, String), AppError> {
+    let invalid = || AppError::auth("Invalid or expired invitation");
+
+    let payload =
+        tokens::verify(&state.config.jwt_secret, TOKEN_PURPOSE, token).ok_or_else(invalid)?;
+    let mut parts = payload.splitn(3, '.');
+    let (Some(id), Some(expires_at), Some(nonce)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid());
+    };
+
+    let id = Uuid::parse_str(id).map_err(|_| invalid())?;
+    let expires_at: i64 = expires_at.parse().map_err(|_| invalid())?;
+    if expires_at <= chrono::Utc::now().timestamp() {
+        return Err(invalid());
+    }
+
+    Ok((id, tokens::hash_token(nonce)))
+}
+
+async fn send_invitation(
+    state: &AppState,
+    invitation: &Invitation,
+    nonce: &str,
+) -> Result<(), std::io::Error> {
+    let organization = Organization::find_by_id(&state.pool, invitation.organization_id)
+        .await
+        .ok()
+        .flatten()
+        .map(|o| o.name)
+        .unwrap_or_default();
+
+    let token = invitation_token(state, invitation, nonce);
+    let body = format!(
+        "Hello,\n\nYou have been invited to join {} as {}.\n\n\
+         Accept the invitation and choose your username and password at:\n\
+         {}/invitations/accept?token={}\n\n\
+         The invitation expires on {}.\n",
+        organization,
+        invitation.role,
+        state.config.public_url,
+        token,
+        invitation.expires_at.to_rfc2822(),
+    );
+
+    state
+        .mailer
+        .send(&Email {
+            to: invitation.email.clone(),
+            subject: "You have been invited".to_string(),
+            body,
+        })
+        .await
+}
+
+/// Load an open invitation the admin may manage; invitations of other organizations are not found
+async fn find_invitation(
+    state: &AppState,
+    scope: AdminScope,
+    id: &str,
+) -> Result<Invitation, AppError> {
+    let invitation_id = parse_id(id, "invitation")?;
+
+    let invitation = Invitation::find_open(&state.pool, invitation_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get invitation: {}", e)))?
+        .ok_or_else(|| AppError::http(404, "Invitation not found"))?;
+
+    match scope.organization() {
+        Some(org) if org != invitation.organization_id => {
+            Err(AppError::http(404, "Invitation not found"))
+        }
+        _ => Ok(invitation),
+    }
+}
+
+async fn audit(
+    state: &AppState,
+    actor_id: Uuid,
+    action: &str,
+    target_user_id: Option<Uuid>,
+    client: &ClientInfo,
+    invitation: &Invitation,
+) -> Result<(), AppError> {
+    AuditEntry::record(
+        &state.pool,
+        Some(actor_id),
+        action,
+        target_user_id,
+        client.ip_address.as_deref(),
+        serde_json::json!({
+            "invitation_id": invitation.id,
+            "organization_id": invitation.organization_id,
+            "email": invitation.email,
+            "role": invitation.role,
+        }),
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to record audit entry: {}", e)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/invitations",
+    responses(
+        (status = 200, description = "Pending invitations, newest first", body = Vec<InvitationResponse>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn list_invitations(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+) -> Result<Json<Vec<InvitationResponse>>, AppError> {
+    let invitations = Invitation::list_pending(&state.pool, scope.organization())
+        .await
+        .map_err(|e| AppError::database(format!("Failed to list invitations: {}", e)))?;
+
+    Ok(Json(invitations.into_iter().map(Into::into).collect()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/invitations",
+    request_body = CreateInvitationRequest,
+    responses(
+        (status = 200, description = "Invitation created and emailed", body = InvitationResponse),
+        (status = 400, description = "Validation error"),
+        (status = 404, description = "Organization not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn create_invitation(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Json(payload): Json<CreateInvitationRequest>,
+) -> Result<Json<InvitationResponse>, AppError> {
+    let email = identity::normalize_email(&payload.email)
+        .map_err(|_| AppError::validation("A valid email is required"))?;
+    let email = email.as_str();
+
+    let role = payload.role.as_deref().unwrap_or("user");
+    permissions::require_role(&state.pool, role).await?;
+    if role == "super_admin" {
+        scope.require_platform()?;
+    }
+
+    let organization_id = match payload.organization_id.as_deref() {
+        Some(id) => {
+            let org = parse_id(id, "organization")?;
+            scope.require_organization(org)?;
+            Organization::find_by_id(&state.pool, org)
+                .await
+                .map_err(|e| AppError::database(format!("Failed to get organization: {}", e)))?
+                .ok_or_else(|| AppError::http(404, "Organization not found"))?
+                .id
+        }
+        None => scope.home_organization(&state.pool, &claims).await?,
+    };
+
+    let existing_user = User::find_by_email(&state.pool, email)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get user: {}", e)))?;
+    if existing_user.is_some() {
+        return Err(AppError::validation(
+            "A user with this email already exists",
+        ));
+    }
+
+    let pending = Invitation::find_pending_for_email(&state.pool, organization_id, email)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get invitation: {}", e)))?;
+    if pending.is_some() {
+        return Err(AppError::validation(
+            "An invitation is already pending for this email",
+        ));
+    }
+
+    let actor = parse_id(&claims.sub, "user")?;
+    let (nonce, expires_at) = new_nonce(&state);
+    let invitation = Invitation::create(
+        &state.pool,
+        organization_id,
+        email,
+        role,
+        &tokens::hash_token(&nonce),
+        Some(actor),
+        expires_at,
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to create invitation: {}", e)))?;
+
+    if let Err(e) = send_invitation(&state, &invitation, &nonce).await {
+        warn!(error = %e, invitation_id = %invitation.id, "Failed to send invitation");
+    }
+
+    audit(
+        &state,
+        actor,
+        "invitation.create",
+        None,
+        &client,
+        &invitation,
+    )
+    .await?;
+
+    info!(invitation_id = %invitation.id, organization_id = %organization_id, "Invitation created");
+
+    Ok(Json(invitation.into()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/invitations/{id}/resend",
+    params(
+        ("id" = String, Path, description = "Invitation ID")
+    ),
+    responses(
+        (status = 200, description = "Invitation renewed and emailed again; earlier links stop working", body = InvitationResponse),
+        (status = 404, description = "Invitation not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn resend_invitation(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Path(id): Path<String>,
+) -> Result<Json<InvitationResponse>, AppError> {
+    let invitation = find_invitation(&state, scope, &id).await?;
+
+    let (nonce, expires_at) = new_nonce(&state);
+    let invitation = Invitation::renew(
+        &state.pool,
+        invitation.id,
+        &tokens::hash_token(&nonce),
+        expires_at,
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to renew invitation: {}", e)))?
+    .ok_or_else(|| AppError::http(404, "Invitation not found"))?;
+
+    send_invitation(&state, &invitation, &nonce)
+        .await
+        .map_err(|e| AppError::internal(format!("Failed to send invitation: {}", e)))?;
+
+    let actor = parse_id(&claims.sub, "user")?;
+    audit(
+        &state,
+        actor,
+        "invitation.resend",
+        None,
+        &client,
+        &invitation,
+    )
+    .await?;
+
+    info!(invitation_id = %invitation.id, "Invitation resent");
+
+    Ok(Json(invitation.into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/admin/invitations/{id}",
+    params(
+        ("id" = String, Path, description = "Invitation ID")
+    ),
+    responses(
+        (status = 204, description = "Invitation revoked"),
+        (status = 404, description = "Invitation not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn revoke_invitation(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    let invitation = find_invitation(&state, scope, &id).await?;
+
+    let revoked = Invitation::revoke(&state.pool, invitation.id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to revoke invitation: {}", e)))?;
+    if !revoked {
+        return Err(AppError::http(404, "Invitation not found"));
+    }
+
+    let actor = parse_id(&claims.sub, "user")?;
+    audit(
+        &state,
+        actor,
+        "invitation.revoke",
+        None,
+        &client,
+        &invitation,
+    )
+    .await?;
+
+    info!(invitation_id = %invitation.id, "Invitation revoked");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/invitations/accept",
+    request_body = AcceptInvitationRequest,
+    responses(
+        (status = 200, description = "Account created in the inviting organization", body = UserResponse),
+        (status = 400, description = "Validation error"),
+        (status = 401, description = "Invalid, used, revoked or expired invitation")
+    ),
+    tag = "auth"
+)]
+pub async fn accept_invitation(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(payload): Json<AcceptInvitationRequest>,
+) -> Result<Json<UserResponse>, AppError> {
+    payload.validate()?;
+    let username = identity::normalize_username(&payload.username).map_err(AppError::validation)?;
+
+    let (invitation_id, nonce_hash) = parse_token(&state, &payload.token)?;
+    let invitation = Invitation::find_open(&state.pool, invitation_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get invitation: {}", e)))?
+        .ok_or_else(|| AppError::auth("Invalid or expired invitation"))?;
+
+    let password_hash = bcrypt::hash(&payload.password, bcrypt::DEFAULT_COST)
+        .map_err(|e| AppError::internal(format!("Password hashing failed: {}", e)))?;
+
+    let mut tx = state
+        .pool
+        .begin()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to start transaction: {}", e)))?;
+
+    // Invitations made before emails were normalized may carry any case
+    let email = identity::normalize_email(&invitation.email).map_err(AppError::validation)?;
+    let user = User::create(
+        &mut *tx,
+        &username,
+        &email,
+        &password_hash,
+        &invitation.role,
+    )
+    .await
+    .map_err(|e| {
+        if e.to_string().contains("unique") {
+            AppError::validation("Username or email already exists")
+        } else {
+            AppError::database(format!("Failed to create user: {}", e))
+        }
+    })?;
+
+    Organization::set_member(
+        &mut *tx,
+        invitation.organization_id,
+        user.id,
+        membership_role(&invitation.role),
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to add organization member: {}", e)))?;
+
+    webhooks::enqueue(
+        &mut *tx,
+        UserEvent::Created,
+        &user,
+        serde_json::json!({
+            "organization_id": invitation.organization_id,
+            "invitation_id": invitation.id,
+        }),
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to queue webhook: {}", e)))?;
+
+    // Consuming the invitation in the same transaction makes the token single-use
+    Invitation::accept(&mut *tx, invitation.id, &nonce_hash, user.id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to accept invitation: {}", e)))?
+        .ok_or_else(|| AppError::auth("Invalid or expired invitation"))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to commit invitation: {}", e)))?;
+
+    audit(
+        &state,
+        user.id,
+        "invitation.accept",
+        Some(user.id),
+        &client,
+        &invitation,
+    )
+    .await?;
+
+    info!(user_id = %user.id, invitation_id = %invitation.id, "Invitation accepted");
+
+    Ok(Json(user.into()))
+}
@@ -0,0 +1,280 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use common::errors::AppError;
+use common::models::{
+    ConsentRequiredResponse, LoginChallengeResponse, LoginResponse, PasswordChangeRequiredResponse,
+    VerifyLoginRequest,
+};
+use rand::Rng;
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::{AppState, account, parse_id, session_response, start_session, terms, wants_cookies};
+use crate::client_info::ClientInfo;
+use crate::db::audit::AuditEntry;
+use crate::db::login_challenges::{self, LoginChallenge};
+use crate::db::queries::User;
+use crate::mailer::Email;
+use crate::notifier::Notice;
+use crate::risk::Assessment;
+use crate::tokens;
+
+/// Wrong codes a challenge tolerates before it is dead
+const MAX_ATTEMPTS: i32 = 5;
+
+/// Step-up codes are short, so they are hashed with the server secret and the
+/// user's id to keep a leaked table from being brute-forced offline
+fn code_hash(state: &AppState, user_id: Uuid, code: &str) -> String {
+    let payload = format!("{}.{}", user_id, code);
+    tokens::hash_token(&tokens::sign(&state.config.jwt_secret, "step-up", &payload))
+}
+
+/// Audit a suspicious login and tell the account's owner about it
+async fn report(
+    state: &AppState,
+    user: &User,
+    client: &ClientInfo,
+    assessment: &Assessment,
+    step_up: bool,
+) -> Result<(), AppError> {
+    let location = assessment.location.as_ref().map(|l| l.to_string());
+
+    AuditEntry::record(
+        &state.pool,
+        Some(user.id),
+        "login.suspicious",
+        Some(user.id),
+        client.ip_address.as_deref(),
+        serde_json::json!({
+            "signals": assessment.signal_names(),
+            "user_agent": client.user_agent,
+            "location": location,
+            "step_up": step_up,
+        }),
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to record audit entry: {}", e)))?;
+
+    warn!(user_id = %user.id, signals = ?assessment.signal_names(), "Suspicious login");
+
+    let reasons: String = assessment
+        .signals
+        .iter()
+        .map(|s| format!("- {}\n", s.describe()))
+        .collect();
+    let body = format!(
+        "Hello {},\n\nWe noticed a sign-in to your account that looks unusual:\n\n{}\n\
+         Time: {}\nIP address: {}\nDevice: {}\nLocation: {}\n\n\
+         If this was you, no action is needed. If not, change your password and \
+         end the session from your list of active sessions.\n",
+        user.username,
+        reasons,
+        chrono::Utc::now().to_rfc2822(),
+        client.ip_address.as_deref().unwrap_or("unknown"),
+        client.user_agent.as_deref().unwrap_or("unknown"),
+        location.as_deref().unwrap_or("unknown"),
+    );
+
+    // A lost notice must not block the login it is about
+    if let Err(e) = state
+        .notifier
+        .notify(
+            user,
+            Notice {
+                subject: "Unusual sign-in to your account".to_string(),
+                body,
+            },
+        )
+        .await
+    {
+        warn!(error = %e, user_id = %user.id, "Failed to send security notice");
+    }
+
+    Ok(())
+}
+
+/// Assess a login whose credentials were accepted, report it when it is
+/// suspicious, and hold it for a step-up code when `RISK_STEP_UP` asks to.
+/// Returns `None` when the login may go on.
+pub(super) async fn screen(
+    state: &AppState,
+    user: &User,
+    organization_id: Option<Uuid>,
+    client: &ClientInfo,
+    audience: Option<&str>,
+) -> Result<Option<Response>, AppError> {
+    let assessment = state.risk.assess(&state.pool, user, client).await?;
+    if !assessment.is_suspicious() {
+        return Ok(None);
+    }
+
+    let step_up = state.risk.requires_step_up(&assessment);
+    report(state, user, client, &assessment, step_up).await?;
+    if !step_up {
+        return Ok(None);
+    }
+
+    challenge(state, user, organization_id, audience)
+        .await
+        .map(Some)
+}
+
+/// Hold a risky login until the user enters the code emailed to them
+async fn challenge(
+    state: &AppState,
+    user: &User,
+    organization_id: Option<Uuid>,
+    audience: Option<&str>,
+) -> Result<Response, AppError> {
+    if !user.is_active {
+        return Err(AppError::auth("Account is disabled"));
+    }
+
+    let code = format!("{:06}", rand::thread_rng().gen_range(0..1_000_000));
+    let expires_at =
+        chrono::Utc::now() + Duration::from_secs(state.config.step_up_code_ttl_seconds);
+    let challenge = LoginChallenge::create(
+        &state.pool,
+        user.id,
+        organization_id,
+        audience,
+        login_challenges::STEP_UP,
+        &code_hash(state, user.id, &code),
+        expires_at,
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to create login challenge: {}", e)))?;
+
+    let body = format!(
+        "Hello {},\n\nYour verification code is {}\n\n\
+         Enter it to finish signing in. It expires in {} minutes. If you did not \
+         try to sign in, change your password.\n",
+        user.username,
+        code,
+        state.config.step_up_code_ttl_seconds / 60,
+    );
+    state
+        .mailer
+        .send(&Email {
+            to: user.email.clone(),
+            subject: "Your verification code".to_string(),
+            body,
+        })
+        .await
+        .map_err(|e| AppError::internal(format!("Failed to send verification code: {}", e)))?;
+
+    info!(user_id = %user.id, challenge_id = %challenge.id, "Login held for step-up verification");
+
+    let response = LoginChallengeResponse {
+        challenge_id: challenge.id.to_string(),
+        method: "email".to_string(),
+        expires_at: challenge.expires_at.to_rfc3339(),
+    };
+    Ok((StatusCode::ACCEPTED, Json(response)).into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/login/verify",
+    request_body = VerifyLoginRequest,
+    responses(
+        (status = 200, description = "Login completed; with `cookie` the tokens are set as cookies", body = LoginResponse),
+        (status = 202, description = "Current terms of service must be accepted first", body = ConsentRequiredResponse),
+        (status = 202, description = "The password expired and must be replaced first", body = PasswordChangeRequiredResponse),
+        (status = 401, description = "Wrong code, or the challenge expired or ran out of attempts")
+    ),
+    tag = "auth"
+)]
+pub async fn verify_login(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(payload): Json<VerifyLoginRequest>,
+) -> Result<Response, AppError> {
+    let use_cookies = wants_cookies(&state, payload.cookie)?;
+    let challenge_id = parse_id(&payload.challenge_id, "challenge")?;
+    let invalid = || AppError::auth("Invalid or expired verification code");
+
+    // The code is bound to the user, who is only known once the challenge is
+    let pending = LoginChallenge::find(&state.pool, challenge_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get login challenge: {}", e)))?
+        .ok_or_else(invalid)?;
+
+    let challenge = LoginChallenge::complete(
+        &state.pool,
+        challenge_id,
+        login_challenges::STEP_UP,
+        &code_hash(&state, pending.user_id, payload.code.trim()),
+        MAX_ATTEMPTS,
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to complete login challenge: {}", e)))?;
+
+    let Some(challenge) = challenge else {
+        LoginChallenge::record_attempt(&state.pool, challenge_id)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to update login challenge: {}", e)))?;
+        return Err(invalid());
+    };
+
+    let user = User::find_by_id(&state.pool, challenge.user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get user: {}", e)))?
+        .ok_or_else(invalid)?;
+
+    AuditEntry::record(
+        &state.pool,
+        Some(challenge.user_id),
+        "login.step_up",
+        Some(challenge.user_id),
+        client.ip_address.as_deref(),
+        serde_json::json!({ "challenge_id": challenge.id }),
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to record audit entry: {}", e)))?;
+
+    if let Some(held) = account::hold_for_password_change(
+        &state,
+        &user,
+        challenge.organization_id,
+        challenge.audience.as_deref(),
+    )
+    .await?
+    {
+        return Ok(held);
+    }
+
+    if let Some(held) = terms::hold_for_consent(
+        &state,
+        &user,
+        challenge.organization_id,
+        challenge.audience.as_deref(),
+    )
+    .await?
+    {
+        return Ok(held);
+    }
+
+    let (session_id, response) = start_session(
+        &state,
+        user,
+        challenge.organization_id,
+        &client,
+        challenge.audience.as_deref(),
+    )
+    .await?;
+
+    info!(user_id = %challenge.user_id, session_id = %session_id, "Step-up verification passed");
+
+    Ok(session_response(
+        &state,
+        session_id,
+        response,
+        challenge.audience.as_deref(),
+        use_cookies,
+    ))
+}
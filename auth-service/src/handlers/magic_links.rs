@@ -0,0 +1,230 @@
+use axum::{extract::State, http::StatusCode, response::Json, response::Response};
+use common::errors::AppError;
+use common::models::{
+    ConsentRequiredResponse, LoginChallengeResponse, LoginResponse, MagicLinkRequest,
+    RedeemMagicLinkRequest,
+};
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::{
+    AppState, account, login_risk, resolve_organization, session_response, start_session, terms,
+    wants_cookies,
+};
+use crate::client_info::ClientInfo;
+use crate::db::magic_links::MagicLink;
+use crate::db::organizations::Membership;
+use crate::db::queries::User;
+use crate::identity;
+use crate::mailer::Email;
+use crate::tokens;
+
+fn role_allowed(state: &AppState, role: &str) -> bool {
+    state.config.magic_link_roles.iter().any(|r| r == role)
+}
+
+/// Whether `user` may sign in with a link to `organization_id`. Both their
+/// platform role and their role in the organization must be enabled, and
+/// directory accounts always sign in through the directory.
+async fn may_use_link(
+    state: &AppState,
+    user: &User,
+    organization_id: Option<Uuid>,
+) -> Result<bool, AppError> {
+    if !user.is_active || user.auth_source != "local" || !role_allowed(state, &user.role) {
+        return Ok(false);
+    }
+
+    let Some(org) = organization_id else {
+        return Ok(true);
+    };
+    let membership = Membership::find(&state.pool, org, user.id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get membership: {}", e)))?;
+
+    Ok(membership.is_none_or(|m| role_allowed(state, &m.role)))
+}
+
+async fn send_link(state: &AppState, email: &str, token: &str) -> Result<(), std::io::Error> {
+    let body = format!(
+        "Hello,\n\nUse this link to sign in:\n\
+         {}/magic-link?token={}\n\n\
+         The link works once and expires in {} minutes. If you did not ask to \
+         sign in, you can ignore this email.\n",
+        state.config.public_url,
+        token,
+        state.config.magic_link_ttl_seconds / 60,
+    );
+
+    state
+        .mailer
+        .send(&Email {
+            to: email.to_string(),
+            subject: "Your sign-in link".to_string(),
+            body,
+        })
+        .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/magic-link",
+    request_body = MagicLinkRequest,
+    responses(
+        (status = 202, description = "A sign-in link is emailed if the address belongs to an account that may use one"),
+        (status = 400, description = "Invalid email address"),
+        (status = 404, description = "Magic links are disabled"),
+        (status = 429, description = "Too many links requested for this address")
+    ),
+    tag = "auth"
+)]
+pub async fn request_magic_link(
+    State(state): State<AppState>,
+    Json(payload): Json<MagicLinkRequest>,
+) -> Result<StatusCode, AppError> {
+    if state.config.magic_link_roles.is_empty() {
+        return Err(AppError::http(404, "Magic links are disabled"));
+    }
+
+    let email = identity::normalize_email(&payload.email).map_err(AppError::validation)?;
+    let email = email.as_str();
+
+    // Counted per address whether or not it has an account, so a 429 reveals nothing
+    let window_start =
+        chrono::Utc::now() - Duration::from_secs(state.config.magic_link_rate_window_seconds);
+    MagicLink::purge_expired(&state.pool, window_start)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to purge magic links: {}", e)))?;
+    let recent = MagicLink::count_since(&state.pool, email, window_start)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to count magic links: {}", e)))?;
+    if recent >= state.config.magic_link_rate_limit {
+        return Err(AppError::http(
+            429,
+            "Too many sign-in links requested, try again later",
+        ));
+    }
+
+    let user = User::find_by_email(&state.pool, email)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get user: {}", e)))?;
+    let user = match user {
+        Some(user) if may_use_link(&state, &user, None).await? => Some(user),
+        _ => None,
+    };
+
+    let token = user.as_ref().map(|_| tokens::generate_opaque_token());
+    let expires_at = chrono::Utc::now() + Duration::from_secs(state.config.magic_link_ttl_seconds);
+    let link = MagicLink::create(
+        &state.pool,
+        email,
+        user.as_ref().map(|u| u.id),
+        token.as_deref().map(tokens::hash_token).as_deref(),
+        expires_at,
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to create magic link: {}", e)))?;
+
+    // Sent after responding, so neither the time taken nor a failed delivery
+    // tells the caller whether the address has an account
+    if let (Some(user), Some(token)) = (user, token) {
+        tokio::spawn(async move {
+            match send_link(&state, &user.email, &token).await {
+                Ok(()) => info!(user_id = %user.id, link_id = %link.id, "Magic link sent"),
+                Err(e) => warn!(error = %e, link_id = %link.id, "Failed to send magic link"),
+            }
+        });
+    }
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/magic-link/redeem",
+    request_body = RedeemMagicLinkRequest,
+    responses(
+        (status = 200, description = "Login successful; with `cookie` the tokens are set as cookies", body = LoginResponse),
+        (status = 202, description = "Risky login held until the emailed code is verified", body = LoginChallengeResponse),
+        (status = 202, description = "Current terms of service must be accepted first", body = ConsentRequiredResponse),
+        (status = 401, description = "Invalid, used or expired link"),
+        (status = 403, description = "Not a member of the requested organization")
+    ),
+    tag = "auth"
+)]
+pub async fn redeem_magic_link(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(payload): Json<RedeemMagicLinkRequest>,
+) -> Result<Response, AppError> {
+    let use_cookies = wants_cookies(&state, payload.cookie)?;
+    let invalid = || AppError::auth("Invalid or expired sign-in link");
+
+    let link = MagicLink::redeem(&state.pool, &tokens::hash_token(&payload.token))
+        .await
+        .map_err(|e| AppError::database(format!("Failed to redeem magic link: {}", e)))?
+        .ok_or_else(invalid)?;
+
+    let user = User::find_by_id(&state.pool, link.user_id.ok_or_else(invalid)?)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get user: {}", e)))?
+        .ok_or_else(invalid)?;
+
+    let organization_id =
+        resolve_organization(&state, &user, payload.organization.as_deref()).await?;
+
+    // Roles may have changed since the link was sent
+    if !may_use_link(&state, &user, organization_id).await? {
+        return Err(invalid());
+    }
+
+    // Holding the mailbox is no more trusted than knowing the password
+    if let Some(held) = login_risk::screen(
+        &state,
+        &user,
+        organization_id,
+        &client,
+        payload.audience.as_deref(),
+    )
+    .await?
+    {
+        return Ok(held);
+    }
+
+    if let Some(held) = account::hold_for_password_change(
+        &state,
+        &user,
+        organization_id,
+        payload.audience.as_deref(),
+    )
+    .await?
+    {
+        return Ok(held);
+    }
+
+    if let Some(held) =
+        terms::hold_for_consent(&state, &user, organization_id, payload.audience.as_deref()).await?
+    {
+        return Ok(held);
+    }
+
+    let (session_id, response) = start_session(
+        &state,
+        user,
+        organization_id,
+        &client,
+        payload.audience.as_deref(),
+    )
+    .await?;
+
+    info!(user_id = %response.user.id, link_id = %link.id, "User logged in with magic link");
+
+    Ok(session_response(
+        &state,
+        session_id,
+        response,
+        payload.audience.as_deref(),
+        use_cookies,
+    ))
+}
@@ -0,0 +1,986 @@
+use axum::{
+    Extension,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use common::errors::{AppError, ErrorResponse};
+use common::models::{
+    Claims, ConsentRequiredResponse, CreateUserRequest, LoginChallengeResponse, LoginRequest,
+    LoginResponse, PasswordChangeRequiredResponse, RefreshRequest, RoleRequest, UserResponse,
+};
+use common::policy::PolicyEngine;
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::authenticator::AuthenticatorChain;
+use crate::client_info::ClientInfo;
+use crate::config::Config;
+use crate::cookies;
+use crate::db::PoolHealth;
+use crate::db::login_failures::LoginFailure;
+use crate::db::organizations::{Membership, Organization};
+use crate::db::queries::User;
+use crate::db::quotas::QuotaRow;
+use crate::db::sessions::{NewSession, Session};
+use crate::identity;
+use crate::jwt::{JwtService, TokenGrant};
+use crate::mailer::Mailer;
+use crate::notifier::Notifier;
+use crate::permissions::{self, PermissionCache};
+use crate::risk::RiskPolicy;
+use crate::scope::AdminScope;
+use crate::tokens;
+use crate::webhooks::UserEvent;
+
+pub mod account;
+pub mod audit;
+pub mod bulk;
+pub mod groups;
+pub mod impersonation;
+pub mod invitations;
+pub mod login_risk;
+pub mod magic_links;
+pub mod organizations;
+pub mod policy;
+pub mod privacy;
+pub mod quotas;
+pub mod sessions;
+pub mod terms;
+pub mod webhooks;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub pool: PgPool,
+    /// Read replica, when configured; see [`AppState::read_pool`]
+    pub replica: Option<PgPool>,
+    pub config: Arc<Config>,
+    pub jwt: Arc<JwtService>,
+    pub mailer: Arc<Mailer>,
+    pub authenticators: Arc<AuthenticatorChain>,
+    pub risk: Arc<RiskPolicy>,
+    pub notifier: Arc<Notifier>,
+    pub policy: Arc<PolicyEngine>,
+    pub permissions: Arc<PermissionCache>,
+}
+
+impl AppState {
+    /// Pool for read-only queries that tolerate replication lag: the replica
+    /// when one is configured, otherwise the primary. Anything that writes, or
+    /// authenticates someone, must use `pool`.
+    pub fn read_pool(&self) -> &PgPool {
+        self.replica.as_ref().unwrap_or(&self.pool)
+    }
+}
+
+impl From<User> for UserResponse {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id.to_string(),
+            username: user.username,
+            email: user.email,
+            role: user.role,
+            created_at: user.created_at.to_rfc3339(),
+            updated_at: user.updated_at.to_rfc3339(),
+            last_login_at: user.last_login_at.map(|t| t.to_rfc3339()),
+            last_login_ip: user.last_login_ip,
+            is_active: user.is_active,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/health",
+    responses(
+        (status = 200, description = "The database is reachable; `degraded` when the replica is not"),
+        (status = 503, description = "The primary database is unreachable")
+    )
+)]
+pub async fn health(State(state): State<AppState>) -> Response {
+    let primary = PoolHealth::check(&state.pool, "primary").await;
+    let replica = match &state.replica {
+        Some(pool) => Some(PoolHealth::check(pool, "replica").await),
+        None => None,
+    };
+
+    let (code, status) = match (&primary, &replica) {
+        (p, _) if !p.reachable => (StatusCode::SERVICE_UNAVAILABLE, "unavailable"),
+        (_, Some(r)) if !r.reachable => (StatusCode::OK, "degraded"),
+        _ => (StatusCode::OK, "ok"),
+    };
+
+    let body = serde_json::json!({
+        "status": status,
+        "service": "auth-service",
+        "database": { "primary": primary, "replica": replica },
+    });
+    (code, Json(body)).into_response()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/login",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Login successful; with `cookie` the tokens are set as cookies", body = LoginResponse),
+        (status = 202, description = "Risky login held until the emailed code is verified", body = LoginChallengeResponse),
+        (status = 202, description = "Current terms of service must be accepted first", body = ConsentRequiredResponse),
+        (status = 202, description = "The password expired and must be replaced first", body = PasswordChangeRequiredResponse),
+        (status = 400, description = "Invalid request, or cookie sessions are disabled", body = ErrorResponse),
+        (status = 401, description = "Invalid credentials")
+    ),
+    tag = "auth"
+)]
+pub async fn login(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(payload): Json<LoginRequest>,
+) -> Result<Response, AppError> {
+    payload.validate()?;
+    let use_cookies = wants_cookies(&state, payload.cookie)?;
+
+    let user = state
+        .authenticators
+        .authenticate(&state.pool, &payload.username, &payload.password)
+        .await?;
+    let Some(user) = user else {
+        LoginFailure::record(&state.pool, &payload.username, client.ip_address.as_deref())
+            .await
+            .map_err(|e| AppError::database(format!("Failed to record login failure: {}", e)))?;
+        return Err(AppError::auth("Invalid username or password"));
+    };
+
+    let organization_id =
+        resolve_organization(&state, &user, payload.organization.as_deref()).await?;
+
+    if let Some(held) = login_risk::screen(
+        &state,
+        &user,
+        organization_id,
+        &client,
+        payload.audience.as_deref(),
+    )
+    .await?
+    {
+        return Ok(held);
+    }
+
+    if let Some(held) = account::hold_for_password_change(
+        &state,
+        &user,
+        organization_id,
+        payload.audience.as_deref(),
+    )
+    .await?
+    {
+        return Ok(held);
+    }
+
+    if let Some(held) =
+        terms::hold_for_consent(&state, &user, organization_id, payload.audience.as_deref()).await?
+    {
+        return Ok(held);
+    }
+
+    let (session_id, response) = start_session(
+        &state,
+        user,
+        organization_id,
+        &client,
+        payload.audience.as_deref(),
+    )
+    .await?;
+
+    info!(user_id = %response.user.id, "User logged in successfully");
+
+    Ok(session_response(
+        &state,
+        session_id,
+        response,
+        payload.audience.as_deref(),
+        use_cookies,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/refresh",
+    request_body = RefreshRequest,
+    responses(
+        (status = 200, description = "Tokens refreshed; a cookie session gets new cookies", body = LoginResponse),
+        (status = 401, description = "Invalid, expired or revoked refresh token, or the password expired and must be changed at the next login"),
+        (status = 403, description = "Missing or invalid CSRF token, or the current terms of service are not accepted")
+    ),
+    tag = "auth"
+)]
+pub async fn refresh(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<RefreshRequest>,
+) -> Result<Response, AppError> {
+    let from_cookie = payload.refresh_token.is_empty();
+    let presented = if from_cookie && state.config.session_cookies {
+        cookies::get(&headers, cookies::REFRESH_COOKIE)
+            .ok_or_else(|| AppError::auth("Missing refresh token"))?
+    } else {
+        payload.refresh_token.as_str()
+    };
+
+    let previous_hash = tokens::hash_token(presented);
+    let session = Session::find_active_by_refresh_hash(&state.pool, &previous_hash)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to look up session: {}", e)))?
+        .ok_or_else(|| AppError::auth("Invalid refresh token"))?;
+
+    if from_cookie {
+        cookies::check_csrf(&state.config, &headers, session.id)?;
+    }
+
+    resolve_audience(&state, payload.audience.as_deref())?;
+
+    let user = User::find_by_id(&state.pool, session.user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get user: {}", e)))?
+        .ok_or_else(|| AppError::auth("Invalid refresh token"))?;
+    terms::require_accepted(&state, user.id).await?;
+    account::require_current_password(&state, &user).await?;
+    let principal = Principal::load(&state, user, session.organization_id).await?;
+
+    // Refresh tokens are single use: every exchange rotates it
+    let refresh_token = tokens::generate_opaque_token();
+    let rotated = Session::rotate_refresh_token(
+        &state.pool,
+        session.id,
+        &previous_hash,
+        &tokens::hash_token(&refresh_token),
+        refresh_expiry(&state),
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to rotate refresh token: {}", e)))?;
+
+    if !rotated {
+        return Err(AppError::auth("Invalid refresh token"));
+    }
+
+    let token =
+        issue_access_token(&state, &principal, &session, payload.audience.as_deref()).await?;
+
+    info!(user_id = %principal.user.id, session_id = %session.id, "Session refreshed");
+
+    let response = LoginResponse {
+        token,
+        refresh_token,
+        user: principal.user.into(),
+    };
+    Ok(session_response(
+        &state,
+        session.id,
+        response,
+        payload.audience.as_deref(),
+        from_cookie,
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/logout",
+    responses(
+        (status = 204, description = "Cookie session revoked and its cookies cleared"),
+        (status = 403, description = "Missing or invalid CSRF token")
+    ),
+    tag = "auth"
+)]
+pub async fn logout(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let refresh_token = cookies::get(&headers, cookies::REFRESH_COOKIE).unwrap_or_default();
+    let session =
+        Session::find_active_by_refresh_hash(&state.pool, &tokens::hash_token(refresh_token))
+            .await
+            .map_err(|e| AppError::database(format!("Failed to look up session: {}", e)))?;
+
+    if let Some(session) = session {
+        cookies::check_csrf(&state.config, &headers, session.id)?;
+        Session::revoke(&state.pool, session.id, session.user_id)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to revoke session: {}", e)))?;
+
+        info!(user_id = %session.user_id, session_id = %session.id, "User logged out");
+    }
+
+    let mut response = StatusCode::NO_CONTENT.into_response();
+    cookies::clear(&state.config, &mut response);
+    Ok(response)
+}
+
+/// A user as seen from one organization: `user.role` is their effective role there
+pub(crate) struct Principal {
+    pub user: User,
+    pub organization_id: Option<Uuid>,
+    pub organization_role: Option<String>,
+}
+
+impl Principal {
+    pub(crate) async fn load(
+        state: &AppState,
+        user: User,
+        organization_id: Option<Uuid>,
+    ) -> Result<Self, AppError> {
+        if !user.is_active {
+            return Err(AppError::auth("Account is disabled"));
+        }
+
+        let Some(org) = organization_id else {
+            return Ok(Self {
+                user,
+                organization_id: None,
+                organization_role: None,
+            });
+        };
+
+        let membership = Membership::find(&state.pool, org, user.id)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to get membership: {}", e)))?;
+
+        match membership {
+            // Super admins keep their platform role in every organization
+            _ if user.role == "super_admin" => Ok(Self {
+                organization_id: Some(org),
+                organization_role: membership.map(|m| m.role),
+                user,
+            }),
+            Some(m) => Ok(Self {
+                user: User {
+                    role: m.role.clone(),
+                    ..user
+                },
+                organization_id: Some(org),
+                organization_role: Some(m.role),
+            }),
+            None => Err(AppError::auth("No longer a member of this organization")),
+        }
+    }
+}
+
+/// Organization a login signs in to: the requested one, or the user's oldest membership
+async fn resolve_organization(
+    state: &AppState,
+    user: &User,
+    slug: Option<&str>,
+) -> Result<Option<Uuid>, AppError> {
+    let memberships = Membership::list_for_user(&state.pool, user.id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get memberships: {}", e)))?;
+
+    let Some(slug) = slug else {
+        return Ok(memberships.first().map(|m| m.organization_id));
+    };
+
+    if let Some(membership) = memberships.iter().find(|m| m.slug == slug) {
+        return Ok(Some(membership.organization_id));
+    }
+
+    if user.role == "super_admin"
+        && let Some(org) = Organization::find_by_slug(&state.pool, slug)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to get organization: {}", e)))?
+    {
+        return Ok(Some(org.id));
+    }
+
+    Err(AppError::authorization(format!(
+        "Not a member of organization '{}'",
+        slug
+    )))
+}
+
+/// Whether a login asked for a cookie session, refused when those are disabled
+fn wants_cookies(state: &AppState, requested: Option<bool>) -> Result<bool, AppError> {
+    let use_cookies = requested.unwrap_or(false);
+    if use_cookies && !state.config.session_cookies {
+        return Err(AppError::validation("Cookie sessions are disabled"));
+    }
+
+    Ok(use_cookies)
+}
+
+/// Hand a session's tokens to the client, in the body or as cookies
+fn session_response(
+    state: &AppState,
+    session_id: Uuid,
+    response: LoginResponse,
+    audience: Option<&str>,
+    use_cookies: bool,
+) -> Response {
+    if use_cookies {
+        let ttl = state.config.token_ttl(audience);
+        return cookies::deliver(&state.config, response, session_id, ttl);
+    }
+
+    Json(response).into_response()
+}
+
+/// Open a new session for an authenticated user and issue its tokens.
+/// Returns the session's id alongside the tokens.
+pub(crate) async fn start_session(
+    state: &AppState,
+    user: User,
+    organization_id: Option<Uuid>,
+    client: &ClientInfo,
+    audience: Option<&str>,
+) -> Result<(Uuid, LoginResponse), AppError> {
+    resolve_audience(state, audience)?;
+    let principal = Principal::load(state, user, organization_id).await?;
+
+    let refresh_token = tokens::generate_opaque_token();
+    let session = Session::create(
+        &state.pool,
+        NewSession {
+            user_id: principal.user.id,
+            organization_id,
+            user_agent: client.user_agent.as_deref(),
+            ip_address: client.ip_address.as_deref(),
+            refresh_token_hash: &tokens::hash_token(&refresh_token),
+            expires_at: refresh_expiry(state),
+            impersonator_id: None,
+        },
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to create session: {}", e)))?;
+
+    User::record_login(&state.pool, principal.user.id, client.ip_address.as_deref())
+        .await
+        .map_err(|e| AppError::database(format!("Failed to record login: {}", e)))?;
+
+    let token = issue_access_token(state, &principal, &session, audience).await?;
+
+    let mut user: UserResponse = principal.user.into();
+    user.last_login_at = Some(session.created_at.to_rfc3339());
+    user.last_login_ip = session.ip_address;
+
+    Ok((
+        session.id,
+        LoginResponse {
+            token,
+            refresh_token,
+            user,
+        },
+    ))
+}
+
+/// Issue an access token bound to `session`. Impersonation sessions get the
+/// `act` claim and the (shorter) impersonation lifetime.
+pub(crate) async fn issue_access_token(
+    state: &AppState,
+    principal: &Principal,
+    session: &Session,
+    audience: Option<&str>,
+) -> Result<String, AppError> {
+    let audiences = resolve_audience(state, audience)?;
+
+    let permissions = state
+        .permissions
+        .resolve(
+            &state.pool,
+            principal.user.id,
+            &principal.user.role,
+            principal.organization_id,
+        )
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get permissions: {}", e)))?;
+
+    let quota = QuotaRow::effective(&state.pool, principal.user.id, &principal.user.role)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get quota: {}", e)))?;
+
+    let mut ttl = state.config.token_ttl(audience);
+    if session.impersonator_id.is_some() {
+        ttl = ttl.min(Duration::from_secs(state.config.impersonation_ttl_seconds));
+    }
+
+    let grant = TokenGrant {
+        user_id: principal.user.id,
+        role: principal.user.role.clone(),
+        permissions,
+        audience: audiences,
+        session_id: Some(session.id),
+        actor: session.impersonator_id,
+        organization_id: principal.organization_id,
+        organization_role: principal.organization_role.clone(),
+        quota: quota.map(|q| q.quota()),
+    };
+
+    state
+        .jwt
+        .generate_token(grant, ttl)
+        .map_err(|e| AppError::internal(format!("JWT generation failed: {}", e)))
+}
+
+/// The `aud` claim for a token requested for `audience`, or for every audience when `None`
+fn resolve_audience(state: &AppState, audience: Option<&str>) -> Result<Vec<String>, AppError> {
+    match audience {
+        Some(aud) if !state.config.token_audiences.iter().any(|a| a == aud) => {
+            Err(AppError::validation(format!("Unknown audience: {}", aud)))
+        }
+        Some(aud) => Ok(vec![aud.to_string()]),
+        None => Ok(state.config.token_audiences.clone()),
+    }
+}
+
+pub(crate) fn parse_id(id: &str, what: &str) -> Result<Uuid, AppError> {
+    Uuid::parse_str(id).map_err(|_| AppError::validation(format!("Invalid {} ID format", what)))
+}
+
+fn refresh_expiry(state: &AppState) -> chrono::DateTime<chrono::Utc> {
+    chrono::Utc::now() + chrono::Duration::seconds(state.config.refresh_token_ttl_seconds as i64)
+}
+
+/// Validate a new account against its canonical username and email, returning both
+fn validate_new_user(payload: &CreateUserRequest) -> Result<(String, String), AppError> {
+    let canonical = CreateUserRequest {
+        username: identity::fold(&payload.username),
+        email: identity::canonical_email(&payload.email),
+        password: payload.password.clone(),
+        role: payload.role.clone(),
+    };
+    canonical.validate()?;
+
+    Ok((canonical.username, canonical.email))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/register",
+    request_body = CreateUserRequest,
+    responses(
+        (status = 200, description = "User registered successfully", body = UserResponse),
+        (status = 400, description = "Validation error", body = ErrorResponse)
+    ),
+    tag = "auth"
+)]
+pub async fn register(
+    State(state): State<AppState>,
+    Json(payload): Json<CreateUserRequest>,
+) -> Result<Json<UserResponse>, AppError> {
+    // Anyone may register, so the role is never theirs to choose; admins are
+    // appointed by other admins
+    let payload = CreateUserRequest {
+        role: None,
+        ..payload
+    };
+    let (username, email) = validate_new_user(&payload)?;
+
+    let password_hash = bcrypt::hash(&payload.password, bcrypt::DEFAULT_COST)
+        .map_err(|e| AppError::internal(format!("Password hashing failed: {}", e)))?;
+
+    let organization = Organization::find_by_slug(&state.pool, Organization::DEFAULT_SLUG)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get organization: {}", e)))?
+        .ok_or_else(|| AppError::internal("Default organization is missing"))?;
+
+    let user = create_member(
+        &state,
+        organization.id,
+        &username,
+        &email,
+        &password_hash,
+        "user",
+    )
+    .await?;
+
+    info!(user_id = %user.id, "User registered successfully");
+
+    Ok(Json(user.into()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/users",
+    responses(
+        (status = 200, description = "List of all users", body = Vec<UserResponse>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn list_users(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+) -> Result<Json<Vec<UserResponse>>, AppError> {
+    let users = match scope {
+        AdminScope::Platform => User::list_all(state.read_pool()).await,
+        AdminScope::Organization(org) => Organization::list_members(state.read_pool(), org).await,
+    }
+    .map_err(|e| AppError::database(format!("Failed to list users: {}", e)))?;
+
+    Ok(Json(users.into_iter().map(UserResponse::from).collect()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/users",
+    request_body = CreateUserRequest,
+    responses(
+        (status = 200, description = "User created successfully", body = UserResponse),
+        (status = 400, description = "Validation error", body = ErrorResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn create_user(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<CreateUserRequest>,
+) -> Result<Json<UserResponse>, AppError> {
+    let (username, email) = validate_new_user(&payload)?;
+
+    let password_hash = bcrypt::hash(&payload.password, bcrypt::DEFAULT_COST)
+        .map_err(|e| AppError::internal(format!("Password hashing failed: {}", e)))?;
+
+    let role = payload.role.unwrap_or_else(|| "user".to_string());
+    permissions::require_role(&state.pool, &role).await?;
+    if role == "super_admin" {
+        scope.require_platform()?;
+    }
+
+    let organization_id = scope.home_organization(&state.pool, &claims).await?;
+    let user = create_member(
+        &state,
+        organization_id,
+        &username,
+        &email,
+        &password_hash,
+        &role,
+    )
+    .await?;
+
+    info!(user_id = %user.id, organization_id = %organization_id, "Admin created user");
+
+    Ok(Json(user.into()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/users/{id}",
+    params(
+        ("id" = String, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "User details", body = UserResponse),
+        (status = 404, description = "User not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn get_user(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Path(id): Path<String>,
+) -> Result<Json<UserResponse>, AppError> {
+    let user_id =
+        Uuid::parse_str(&id).map_err(|_| AppError::validation("Invalid user ID format"))?;
+
+    let user = scope.find_user(state.read_pool(), user_id).await?;
+
+    Ok(Json(user.into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/admin/users/{id}",
+    params(
+        ("id" = String, Path, description = "User ID")
+    ),
+    responses(
+        (status = 204, description = "User deleted successfully"),
+        (status = 404, description = "User not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn delete_user(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    let user_id =
+        Uuid::parse_str(&id).map_err(|_| AppError::validation("Invalid user ID format"))?;
+
+    let user = scope.find_user(&state.pool, user_id).await?;
+
+    if let AdminScope::Organization(org) = scope {
+        return remove_from_organization(&state, org, user_id)
+            .await
+            .map(|_| StatusCode::NO_CONTENT);
+    }
+
+    delete_account(&state, &user).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Delete an account, queueing `user.deleted` in the same transaction
+async fn delete_account(state: &AppState, user: &User) -> Result<(), AppError> {
+    let mut tx = state
+        .pool
+        .begin()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to start transaction: {}", e)))?;
+
+    // Queued first, while the user's memberships still select their subscriptions
+    crate::webhooks::enqueue(&mut *tx, UserEvent::Deleted, user, serde_json::json!({}))
+        .await
+        .map_err(|e| AppError::database(format!("Failed to queue webhook: {}", e)))?;
+
+    let deleted = User::delete(&mut *tx, user.id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to delete user: {}", e)))?;
+    if !deleted {
+        return Err(AppError::http(404, "User not found"));
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to delete user: {}", e)))?;
+
+    info!(user_id = %user.id, "User deleted");
+    Ok(())
+}
+
+/// Remove a user from an organization on its behalf. The account is only deleted
+/// when it belongs to no other organization.
+pub(crate) async fn remove_from_organization(
+    state: &AppState,
+    organization_id: Uuid,
+    user_id: Uuid,
+) -> Result<(), AppError> {
+    let memberships = Membership::count_for_user(&state.pool, user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to count memberships: {}", e)))?;
+
+    let user = User::find_by_id(&state.pool, user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get user: {}", e)))?
+        .ok_or_else(|| AppError::http(404, "User not found"))?;
+
+    if memberships > 1 {
+        remove_member(state, organization_id, &user).await?;
+        return Ok(());
+    }
+
+    delete_account(state, &user).await
+}
+
+/// Remove a user from an organization, queueing `user.removed` in the same
+/// transaction. Returns `false` if they were not a member.
+pub(crate) async fn remove_member(
+    state: &AppState,
+    organization_id: Uuid,
+    user: &User,
+) -> Result<bool, AppError> {
+    let mut tx = state
+        .pool
+        .begin()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to start transaction: {}", e)))?;
+
+    // Queued first, while the membership still selects the organization's subscriptions
+    crate::webhooks::enqueue(
+        &mut *tx,
+        UserEvent::Removed,
+        user,
+        serde_json::json!({ "organization_id": organization_id }),
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to queue webhook: {}", e)))?;
+
+    let removed = Organization::remove_member(&mut tx, organization_id, user.id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to remove member: {}", e)))?;
+    if !removed {
+        return Ok(false);
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to remove member: {}", e)))?;
+
+    info!(user_id = %user.id, organization_id = %organization_id, "User removed from organization");
+    Ok(true)
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/admin/users/{id}/role",
+    params(
+        ("id" = String, Path, description = "User ID")
+    ),
+    request_body = RoleRequest,
+    responses(
+        (status = 200, description = "User role updated", body = UserResponse),
+        (status = 400, description = "Validation error", body = ErrorResponse),
+        (status = 404, description = "User not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn update_user_role(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(payload): Json<RoleRequest>,
+) -> Result<Json<UserResponse>, AppError> {
+    let user_id =
+        Uuid::parse_str(&id).map_err(|_| AppError::validation("Invalid user ID format"))?;
+
+    let role = payload.role.as_str();
+    permissions::require_role(&state.pool, role).await?;
+
+    let user = scope.find_user(&state.pool, user_id).await?;
+
+    // super_admin is a platform role; every other role is held within an organization
+    let organization_id = match scope {
+        AdminScope::Organization(org) => Some(org),
+        AdminScope::Platform => claims.org.as_deref().and_then(|o| o.parse().ok()),
+    };
+    let platform_role_changes = role == "super_admin" || user.role == "super_admin";
+    if platform_role_changes {
+        scope.require_platform()?;
+    }
+
+    let mut tx = state
+        .pool
+        .begin()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to start transaction: {}", e)))?;
+
+    if platform_role_changes {
+        User::update_role(&mut *tx, user_id, role)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to update user role: {}", e)))?;
+    }
+
+    let mut changed_in = None;
+    let mut previous_role = user.role.clone();
+    if role != "super_admin" {
+        match organization_id {
+            Some(org) => {
+                let membership = Membership::find(&state.pool, org, user_id)
+                    .await
+                    .map_err(|e| AppError::database(format!("Failed to get membership: {}", e)))?
+                    .ok_or_else(|| AppError::http(404, "User not found"))?;
+
+                Organization::set_member(&mut *tx, org, user_id, role)
+                    .await
+                    .map_err(|e| {
+                        AppError::database(format!("Failed to update user role: {}", e))
+                    })?;
+                changed_in = Some(org);
+                if !platform_role_changes {
+                    previous_role = membership.role;
+                }
+            }
+            None if !platform_role_changes => {
+                User::update_role(&mut *tx, user_id, role)
+                    .await
+                    .map_err(|e| {
+                        AppError::database(format!("Failed to update user role: {}", e))
+                    })?;
+            }
+            None => {}
+        }
+    }
+
+    if role != previous_role {
+        crate::webhooks::enqueue(
+            &mut *tx,
+            UserEvent::RoleChanged,
+            &user,
+            serde_json::json!({
+                "role": role,
+                "previous_role": previous_role,
+                "organization_id": changed_in,
+            }),
+        )
+        .await
+        .map_err(|e| AppError::database(format!("Failed to queue webhook: {}", e)))?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to update user role: {}", e)))?;
+
+    let user = scope.find_user(&state.pool, user_id).await?;
+
+    info!(user_id = %user_id, new_role = %role, "User role updated");
+
+    Ok(Json(user.into()))
+}
+
+/// Create a user and add them to an organization with the same role. The
+/// username and email must already be normalized.
+pub(crate) async fn create_member(
+    state: &AppState,
+    organization_id: Uuid,
+    username: &str,
+    email: &str,
+    password_hash: &str,
+    role: &str,
+) -> Result<User, AppError> {
+    let mut tx = state
+        .pool
+        .begin()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to start transaction: {}", e)))?;
+
+    let user = User::create(&mut *tx, username, email, password_hash, role)
+        .await
+        .map_err(|e| {
+            if e.to_string().contains("unique") {
+                AppError::validation("Username or email already exists")
+            } else {
+                AppError::database(format!("Failed to create user: {}", e))
+            }
+        })?;
+
+    Organization::set_member(&mut *tx, organization_id, user.id, membership_role(role))
+        .await
+        .map_err(|e| AppError::database(format!("Failed to add organization member: {}", e)))?;
+
+    crate::webhooks::enqueue(
+        &mut *tx,
+        UserEvent::Created,
+        &user,
+        serde_json::json!({ "organization_id": organization_id }),
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to queue webhook: {}", e)))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to create user: {}", e)))?;
+
+    Ok(user)
+}
+
+/// Role a new user holds in the organization they are created in. Super admins
+/// hold their role on the platform and administer their home organization.
+pub fn membership_role(role: &str) -> &str {
+    if role == "super_admin" { "admin" } else { role }
+}
@@ -0,0 +1,284 @@
+use axum::{
+    Extension,
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use common::errors::{AppError, ErrorResponse};
+use common::models::{
+    Claims, CreateOrganizationRequest, MembershipResponse, OrganizationResponse, RoleRequest,
+    UserResponse,
+};
+use tracing::info;
+
+use super::{AppState, parse_id};
+use crate::db::organizations::{Membership, Organization};
+use crate::db::queries::User;
+use crate::permissions;
+use crate::scope::AdminScope;
+use crate::webhooks::{self, UserEvent};
+
+impl From<Organization> for OrganizationResponse {
+    fn from(org: Organization) -> Self {
+        Self {
+            id: org.id.to_string(),
+            name: org.name,
+            slug: org.slug,
+            created_at: org.created_at.to_rfc3339(),
+        }
+    }
+}
+
+impl From<Membership> for MembershipResponse {
+    fn from(membership: Membership) -> Self {
+        Self {
+            organization_id: membership.organization_id.to_string(),
+            slug: membership.slug,
+            name: membership.name,
+            role: membership.role,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/me/organizations",
+    responses(
+        (status = 200, description = "Organizations the current user belongs to", body = Vec<MembershipResponse>),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "me"
+)]
+pub async fn list_my_organizations(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<Vec<MembershipResponse>>, AppError> {
+    let user_id = parse_id(&claims.sub, "user")?;
+
+    let memberships = Membership::list_for_user(&state.pool, user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get memberships: {}", e)))?;
+
+    Ok(Json(memberships.into_iter().map(Into::into).collect()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/organizations",
+    responses(
+        (status = 200, description = "Organizations visible to the admin", body = Vec<OrganizationResponse>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn list_organizations(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+) -> Result<Json<Vec<OrganizationResponse>>, AppError> {
+    let orgs = match scope {
+        AdminScope::Platform => Organization::list_all(&state.pool).await,
+        AdminScope::Organization(org) => Organization::find_by_id(&state.pool, org)
+            .await
+            .map(|org| org.into_iter().collect()),
+    }
+    .map_err(|e| AppError::database(format!("Failed to list organizations: {}", e)))?;
+
+    Ok(Json(orgs.into_iter().map(Into::into).collect()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/organizations",
+    request_body = CreateOrganizationRequest,
+    responses(
+        (status = 200, description = "Organization created", body = OrganizationResponse),
+        (status = 400, description = "Validation error"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Super admin role required")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn create_organization(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Json(payload): Json<CreateOrganizationRequest>,
+) -> Result<Json<OrganizationResponse>, AppError> {
+    scope.require_platform()?;
+
+    if payload.name.is_empty() || payload.slug.is_empty() {
+        return Err(AppError::validation("Name and slug are required"));
+    }
+
+    let org = Organization::create(&state.pool, &payload.name, &payload.slug)
+        .await
+        .map_err(|e| {
+            if e.to_string().contains("unique") {
+                AppError::validation("Organization slug already exists")
+            } else {
+                AppError::database(format!("Failed to create organization: {}", e))
+            }
+        })?;
+
+    info!(organization_id = %org.id, slug = %org.slug, "Organization created");
+
+    Ok(Json(org.into()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/organizations/{id}/members",
+    params(
+        ("id" = String, Path, description = "Organization ID")
+    ),
+    responses(
+        (status = 200, description = "Members, with their role in the organization", body = Vec<UserResponse>),
+        (status = 404, description = "Organization not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn list_members(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<UserResponse>>, AppError> {
+    let org_id = parse_id(&id, "organization")?;
+    scope.require_organization(org_id)?;
+
+    let members = Organization::list_members(&state.pool, org_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to list members: {}", e)))?;
+
+    Ok(Json(members.into_iter().map(UserResponse::from).collect()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/admin/organizations/{id}/members/{user_id}",
+    params(
+        ("id" = String, Path, description = "Organization ID"),
+        ("user_id" = String, Path, description = "User ID")
+    ),
+    request_body = RoleRequest,
+    responses(
+        (status = 204, description = "Membership created or role updated"),
+        (status = 400, description = "Validation error", body = ErrorResponse),
+        (status = 404, description = "Organization or user not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn set_member(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Path((id, user_id)): Path<(String, String)>,
+    Json(payload): Json<RoleRequest>,
+) -> Result<StatusCode, AppError> {
+    let org_id = parse_id(&id, "organization")?;
+    let user_id = parse_id(&user_id, "user")?;
+    scope.require_organization(org_id)?;
+
+    permissions::require_role(&state.pool, &payload.role).await?;
+    if payload.role == "super_admin" {
+        return Err(AppError::validation(
+            "super_admin is a platform role and cannot be held within an organization",
+        ));
+    }
+
+    let user = match scope {
+        // Only super admins can bring existing accounts into an organization
+        AdminScope::Platform => {
+            Organization::find_by_id(&state.pool, org_id)
+                .await
+                .map_err(|e| AppError::database(format!("Failed to get organization: {}", e)))?
+                .ok_or_else(|| AppError::http(404, "Organization not found"))?;
+            User::find_by_id(&state.pool, user_id)
+                .await
+                .map_err(|e| AppError::database(format!("Failed to get user: {}", e)))?
+                .ok_or_else(|| AppError::http(404, "User not found"))?
+        }
+        AdminScope::Organization(_) => scope.find_user(&state.pool, user_id).await?,
+    };
+    let previous_role = Membership::find(&state.pool, org_id, user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get membership: {}", e)))?
+        .map(|m| m.role);
+
+    let mut tx = state
+        .pool
+        .begin()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to start transaction: {}", e)))?;
+
+    Organization::set_member(&mut *tx, org_id, user_id, &payload.role)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to set member: {}", e)))?;
+
+    if previous_role.as_deref() != Some(payload.role.as_str()) {
+        webhooks::enqueue(
+            &mut *tx,
+            UserEvent::RoleChanged,
+            &user,
+            serde_json::json!({
+                "role": payload.role,
+                "previous_role": previous_role,
+                "organization_id": org_id,
+            }),
+        )
+        .await
+        .map_err(|e| AppError::database(format!("Failed to queue webhook: {}", e)))?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to set member: {}", e)))?;
+
+    info!(organization_id = %org_id, user_id = %user_id, role = %payload.role, "Organization member set");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/admin/organizations/{id}/members/{user_id}",
+    params(
+        ("id" = String, Path, description = "Organization ID"),
+        ("user_id" = String, Path, description = "User ID")
+    ),
+    responses(
+        (status = 204, description = "Member removed"),
+        (status = 404, description = "Membership not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn remove_member(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Path((id, user_id)): Path<(String, String)>,
+) -> Result<StatusCode, AppError> {
+    let org_id = parse_id(&id, "organization")?;
+    let user_id = parse_id(&user_id, "user")?;
+    scope.require_organization(org_id)?;
+
+    let user = User::find_by_id(&state.pool, user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get user: {}", e)))?
+        .ok_or_else(|| AppError::http(404, "Membership not found"))?;
+
+    if super::remove_member(&state, org_id, &user).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::http(404, "Membership not found"))
+    }
+}
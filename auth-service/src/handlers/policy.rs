@@ -0,0 +1,44 @@
+use axum::{Extension, extract::State, response::Json};
+use common::errors::AppError;
+use common::models::{Claims, PolicyExplainRequest};
+use common::policy::{AccessRequest, Explanation};
+
+use super::AppState;
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/policy/explain",
+    request_body = PolicyExplainRequest,
+    responses(
+        (status = 200, description = "How every access policy rule treats the request", body = Explanation),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn explain_policy(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<PolicyExplainRequest>,
+) -> Result<Json<Explanation>, AppError> {
+    if !payload.path.starts_with('/') {
+        return Err(AppError::validation("Path must start with '/'"));
+    }
+
+    // Without an explicit principal the request is explained as the caller's own
+    let principal = match payload.principal {
+        Some(principal) => principal,
+        None => serde_json::to_value(&claims)
+            .map_err(|e| AppError::internal(format!("Failed to serialize claims: {}", e)))?,
+    };
+
+    let (path, query) = match payload.path.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (payload.path.as_str(), None),
+    };
+    let request = AccessRequest::from_parts(&payload.method, path, query, principal);
+
+    Ok(Json(state.policy.explain(&request)))
+}
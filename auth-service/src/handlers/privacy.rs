@@ -0,0 +1,305 @@
+use axum::{
+    Extension,
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Json, Response},
+};
+use common::errors::AppError;
+use common::models::{Claims, EraseAccountRequest, UserDataExport};
+use tracing::info;
+use uuid::Uuid;
+
+use super::{AppState, parse_id, sessions};
+use crate::client_info::ClientInfo;
+use crate::db::audit::AuditEntry;
+use crate::db::groups::Group;
+use crate::db::invitations::Invitation;
+use crate::db::organizations::Membership;
+use crate::db::queries::User;
+use crate::db::sessions::Session;
+use crate::db::terms::AcceptanceStatus;
+use crate::scope::AdminScope;
+use crate::webhooks::{self, UserEvent};
+
+/// Gather everything stored about `user` into one archive
+async fn collect(state: &AppState, user: User) -> Result<UserDataExport, AppError> {
+    let memberships = Membership::list_for_user(&state.pool, user.id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to list memberships: {}", e)))?;
+
+    let groups = Group::list_for_user(&state.pool, user.id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to list groups: {}", e)))?;
+
+    let sessions = Session::history_for_user(&state.pool, user.id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to list sessions: {}", e)))?;
+
+    let audit_log = AuditEntry::list(&state.pool, None, Some(user.id), None, i64::MAX)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to list audit entries: {}", e)))?;
+
+    let invitations = Invitation::list_for_user(&state.pool, user.id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to list invitations: {}", e)))?;
+
+    let terms_acceptances = AcceptanceStatus::for_user(&state.pool, user.id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to list terms acceptances: {}", e)))?;
+
+    Ok(UserDataExport {
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        user: user.into(),
+        memberships: memberships.into_iter().map(Into::into).collect(),
+        groups: groups.into_iter().map(Into::into).collect(),
+        sessions: sessions
+            .into_iter()
+            .map(|s| sessions::to_response(s, None))
+            .collect(),
+        audit_log: audit_log.into_iter().map(Into::into).collect(),
+        invitations: invitations.into_iter().map(Into::into).collect(),
+        terms_acceptances: terms_acceptances.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// Exports and erasure cover every organization the user belongs to, so
+/// organization admins may only act on users who belong to theirs alone
+async fn find_sole_member(
+    state: &AppState,
+    scope: &AdminScope,
+    user_id: Uuid,
+    action: &str,
+) -> Result<User, AppError> {
+    scope.find_user(&state.pool, user_id).await?;
+    // The account itself, with its platform role rather than the membership's
+    let user = User::find_by_id(&state.pool, user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get user: {}", e)))?
+        .ok_or_else(|| AppError::http(404, "User not found"))?;
+
+    if let AdminScope::Organization(_) = scope {
+        let memberships = Membership::count_for_user(&state.pool, user_id)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to count memberships: {}", e)))?;
+
+        if user.role == "super_admin" || memberships > 1 {
+            return Err(AppError::authorization(format!(
+                "Only a super admin can {} users who belong to other organizations",
+                action
+            )));
+        }
+    }
+
+    Ok(user)
+}
+
+/// Serve an export as a JSON file download
+fn attachment(export: UserDataExport) -> Response {
+    let filename = format!("attachment; filename=\"user-{}.json\"", export.user.id);
+    ([(header::CONTENT_DISPOSITION, filename)], Json(export)).into_response()
+}
+
+async fn erase(
+    state: &AppState,
+    actor_id: Uuid,
+    user_id: Uuid,
+    ip_address: Option<&str>,
+) -> Result<(), AppError> {
+    let user = User::find_by_id(&state.pool, user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get user: {}", e)))?
+        .ok_or_else(|| AppError::http(404, "User not found"))?;
+
+    let mut tx = state
+        .pool
+        .begin()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to start transaction: {}", e)))?;
+
+    // Queued while the user's memberships still select their subscriptions.
+    // Erasing strips the name and email from it again before it is sent.
+    webhooks::enqueue(
+        &mut *tx,
+        UserEvent::Deleted,
+        &user,
+        serde_json::json!({ "erased": true }),
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to queue webhook: {}", e)))?;
+
+    let erased = User::erase(&mut tx, user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to erase user: {}", e)))?;
+    if !erased {
+        return Err(AppError::http(404, "User not found"));
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| AppError::database(format!("Failed to erase user: {}", e)))?;
+
+    AuditEntry::record(
+        &state.pool,
+        Some(actor_id),
+        "user.erase",
+        Some(user_id),
+        ip_address,
+        serde_json::json!({}),
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to record audit entry: {}", e)))?;
+
+    info!(user_id = %user_id, actor_id = %actor_id, "User erased");
+
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/me/export",
+    responses(
+        (status = 200, description = "Everything stored about the current user", body = UserDataExport),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Not permitted with an impersonation token")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "me"
+)]
+pub async fn export_my_data(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+) -> Result<Response, AppError> {
+    let user_id = parse_id(&claims.sub, "user")?;
+
+    let user = User::find_by_id(&state.pool, user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get user: {}", e)))?
+        .ok_or_else(|| AppError::http(404, "User not found"))?;
+
+    AuditEntry::record(
+        &state.pool,
+        Some(user_id),
+        "user.export",
+        Some(user_id),
+        client.ip_address.as_deref(),
+        serde_json::json!({}),
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to record audit entry: {}", e)))?;
+
+    Ok(attachment(collect(&state, user).await?))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/me",
+    request_body = EraseAccountRequest,
+    responses(
+        (status = 204, description = "Account anonymized and disabled"),
+        (status = 401, description = "Unauthorized or wrong password"),
+        (status = 403, description = "Not permitted with an impersonation token")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "me"
+)]
+pub async fn erase_my_account(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<EraseAccountRequest>,
+) -> Result<StatusCode, AppError> {
+    let user_id = parse_id(&claims.sub, "user")?;
+
+    let user = User::find_by_id(&state.pool, user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get user: {}", e)))?
+        .ok_or_else(|| AppError::http(404, "User not found"))?;
+
+    // Confirm through the account's own login backend, which may be the directory
+    let confirmed = state
+        .authenticators
+        .authenticate(&state.pool, &user.username, &payload.password)
+        .await?
+        .is_some_and(|u| u.id == user_id);
+    if !confirmed {
+        return Err(AppError::auth("Password is incorrect"));
+    }
+
+    // The user's address is personal data too, so it is not kept in the audit entry
+    erase(&state, user_id, user_id, None).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/users/{id}/export",
+    params(
+        ("id" = String, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Everything stored about the user", body = UserDataExport),
+        (status = 404, description = "User not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn export_user_data(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Path(id): Path<String>,
+) -> Result<Response, AppError> {
+    let user_id = parse_id(&id, "user")?;
+    let user = find_sole_member(&state, &scope, user_id, "export").await?;
+
+    let actor = parse_id(&claims.sub, "user")?;
+    AuditEntry::record(
+        &state.pool,
+        Some(actor),
+        "user.export",
+        Some(user_id),
+        client.ip_address.as_deref(),
+        serde_json::json!({}),
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to record audit entry: {}", e)))?;
+
+    info!(user_id = %user_id, actor_id = %actor, "User data exported");
+
+    Ok(attachment(collect(&state, user).await?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/users/{id}/erase",
+    params(
+        ("id" = String, Path, description = "User ID")
+    ),
+    responses(
+        (status = 204, description = "Account anonymized and disabled"),
+        (status = 404, description = "User not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn erase_user(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    let user_id = parse_id(&id, "user")?;
+    find_sole_member(&state, &scope, user_id, "erase").await?;
+
+    let actor = parse_id(&claims.sub, "user")?;
+    erase(&state, actor, user_id, client.ip_address.as_deref()).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,362 @@
+use axum::{
+    Extension,
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use common::errors::AppError;
+use common::models::{Claims, Quota, QuotaResponse, QuotaUsage, UsageResponse};
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::{AppState, parse_id};
+use crate::client_info::ClientInfo;
+use crate::db::audit::AuditEntry;
+use crate::db::queries::User;
+use crate::db::quotas::QuotaRow;
+use crate::jwt::TokenGrant;
+use crate::scope::AdminScope;
+
+/// Lifetime of the token usage is looked up with
+const USAGE_TOKEN_TTL: Duration = Duration::from_secs(60);
+
+impl From<QuotaRow> for QuotaResponse {
+    fn from(row: QuotaRow) -> Self {
+        Self {
+            quota: row.quota(),
+            role: row.role,
+            user_id: row.user_id.map(|id| id.to_string()),
+            updated_at: row.updated_at.to_rfc3339(),
+        }
+    }
+}
+
+fn validate_quota(quota: &Quota) -> Result<(), AppError> {
+    let limits = [
+        ("requests_per_day", quota.requests_per_day),
+        ("max_cities", quota.max_cities),
+        ("forecast_days", quota.forecast_days),
+    ];
+    match limits
+        .iter()
+        .find(|(_, limit)| limit.is_some_and(|l| l < 0))
+    {
+        Some((name, _)) => Err(AppError::validation(format!(
+            "{} must not be negative",
+            name
+        ))),
+        None => Ok(()),
+    }
+}
+
+async fn audit(
+    state: &AppState,
+    claims: &Claims,
+    client: &ClientInfo,
+    action: &str,
+    target: Option<Uuid>,
+    details: serde_json::Value,
+) -> Result<(), AppError> {
+    let actor = parse_id(&claims.sub, "user")?;
+    AuditEntry::record(
+        &state.pool,
+        Some(actor),
+        action,
+        target,
+        client.ip_address.as_deref(),
+        details,
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to write audit log: {}", e)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/quotas",
+    responses(
+        (status = 200, description = "Every role and user quota", body = Vec<QuotaResponse>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Super admin role required")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn list_quotas(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+) -> Result<Json<Vec<QuotaResponse>>, AppError> {
+    scope.require_platform()?;
+
+    let quotas = QuotaRow::list_all(&state.pool)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to list quotas: {}", e)))?;
+
+    Ok(Json(quotas.into_iter().map(Into::into).collect()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/admin/quotas/roles/{role}",
+    params(
+        ("role" = String, Path, description = "Role the quota applies to")
+    ),
+    request_body = Quota,
+    responses(
+        (status = 200, description = "Quota set; tokens issued from now on carry it", body = QuotaResponse),
+        (status = 400, description = "Validation error"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Super admin role required")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn set_role_quota(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Path(role): Path<String>,
+    Json(payload): Json<Quota>,
+) -> Result<Json<QuotaResponse>, AppError> {
+    scope.require_platform()?;
+    validate_quota(&payload)?;
+    if role.is_empty() || role.len() > 50 {
+        return Err(AppError::validation("Role must be 1-50 characters"));
+    }
+
+    let row = QuotaRow::set_for_role(&state.pool, &role, &payload)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to set quota: {}", e)))?;
+
+    audit(
+        &state,
+        &claims,
+        &client,
+        "quota.set",
+        None,
+        serde_json::json!({ "role": role, "quota": payload }),
+    )
+    .await?;
+
+    info!(role = %role, quota = ?payload, "Role quota set");
+
+    Ok(Json(row.into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/admin/quotas/roles/{role}",
+    params(
+        ("role" = String, Path, description = "Role the quota applies to")
+    ),
+    responses(
+        (status = 204, description = "Quota removed; the role is unlimited"),
+        (status = 404, description = "The role has no quota"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Super admin role required")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn delete_role_quota(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Path(role): Path<String>,
+) -> Result<StatusCode, AppError> {
+    scope.require_platform()?;
+
+    let deleted = QuotaRow::delete_for_role(&state.pool, &role)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to delete quota: {}", e)))?;
+    if !deleted {
+        return Err(AppError::http(404, "Quota not found"));
+    }
+
+    audit(
+        &state,
+        &claims,
+        &client,
+        "quota.delete",
+        None,
+        serde_json::json!({ "role": role }),
+    )
+    .await?;
+
+    info!(role = %role, "Role quota removed");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/admin/quotas/users/{id}",
+    params(
+        ("id" = String, Path, description = "User ID")
+    ),
+    request_body = Quota,
+    responses(
+        (status = 200, description = "Quota set, replacing the user's role quota", body = QuotaResponse),
+        (status = 400, description = "Validation error"),
+        (status = 404, description = "User not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Super admin role required")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn set_user_quota(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Path(id): Path<String>,
+    Json(payload): Json<Quota>,
+) -> Result<Json<QuotaResponse>, AppError> {
+    scope.require_platform()?;
+    validate_quota(&payload)?;
+    let user_id = parse_id(&id, "user")?;
+
+    User::find_by_id(&state.pool, user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get user: {}", e)))?
+        .ok_or_else(|| AppError::http(404, "User not found"))?;
+
+    let row = QuotaRow::set_for_user(&state.pool, user_id, &payload)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to set quota: {}", e)))?;
+
+    audit(
+        &state,
+        &claims,
+        &client,
+        "quota.set",
+        Some(user_id),
+        serde_json::json!({ "quota": payload }),
+    )
+    .await?;
+
+    info!(user_id = %user_id, quota = ?payload, "User quota set");
+
+    Ok(Json(row.into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/admin/quotas/users/{id}",
+    params(
+        ("id" = String, Path, description = "User ID")
+    ),
+    responses(
+        (status = 204, description = "Quota removed; the user's role quota applies again"),
+        (status = 404, description = "The user has no quota of their own"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Super admin role required")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn delete_user_quota(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    scope.require_platform()?;
+    let user_id = parse_id(&id, "user")?;
+
+    let deleted = QuotaRow::delete_for_user(&state.pool, user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to delete quota: {}", e)))?;
+    if !deleted {
+        return Err(AppError::http(404, "Quota not found"));
+    }
+
+    audit(
+        &state,
+        &claims,
+        &client,
+        "quota.delete",
+        Some(user_id),
+        serde_json::json!({}),
+    )
+    .await?;
+
+    info!(user_id = %user_id, "User quota removed");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Ask the weather service for the caller's counters, with a short-lived
+/// token for them that carries `quota`
+async fn fetch_usage(
+    state: &AppState,
+    url: &str,
+    claims: &Claims,
+    quota: Option<Quota>,
+) -> Result<QuotaUsage, String> {
+    let grant = TokenGrant {
+        user_id: parse_id(&claims.sub, "user").map_err(|e| e.to_string())?,
+        role: claims.role.clone(),
+        permissions: claims.permissions.clone(),
+        audience: vec![state.config.weather_service_audience.clone()],
+        session_id: None,
+        actor: None,
+        organization_id: claims.org.as_deref().and_then(|o| o.parse().ok()),
+        organization_role: claims.org_role.clone(),
+        quota,
+    };
+    let token = state
+        .jwt
+        .generate_token(grant, USAGE_TOKEN_TTL)
+        .map_err(|e| e.to_string())?;
+
+    let response = reqwest::Client::new()
+        .get(format!("{}/api/usage", url.trim_end_matches('/')))
+        .bearer_auth(token)
+        .timeout(Duration::from_secs(5))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?;
+
+    response.json().await.map_err(|e| e.to_string())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/me/usage",
+    responses(
+        (status = 200, description = "The caller's quota and today's usage", body = UsageResponse),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "me"
+)]
+pub async fn my_usage(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<UsageResponse>, AppError> {
+    let user_id = parse_id(&claims.sub, "user")?;
+
+    let quota = QuotaRow::effective(&state.pool, user_id, &claims.role)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get quota: {}", e)))?
+        .map(|row| row.quota());
+
+    let usage = match &state.config.weather_service_url {
+        Some(url) => match fetch_usage(&state, url, &claims, quota.clone()).await {
+            Ok(usage) => Some(usage),
+            Err(e) => {
+                warn!(error = %e, user_id = %user_id, "Failed to get usage from weather service");
+                None
+            }
+        },
+        None => None,
+    };
+
+    Ok(Json(UsageResponse { quota, usage }))
+}
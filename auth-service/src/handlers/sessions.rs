@@ -0,0 +1,177 @@
+use axum::{
+    Extension,
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+};
+use common::errors::AppError;
+use common::models::{Claims, SessionResponse};
+use tracing::info;
+use uuid::Uuid;
+
+use super::{AppState, parse_id};
+use crate::client_info::ClientInfo;
+use crate::db::audit::AuditEntry;
+use crate::db::sessions::Session;
+use crate::scope::AdminScope;
+
+pub(crate) fn to_response(session: Session, current_sid: Option<&str>) -> SessionResponse {
+    SessionResponse {
+        current: current_sid == Some(session.id.to_string().as_str()),
+        id: session.id.to_string(),
+        user_agent: session.user_agent,
+        ip_address: session.ip_address,
+        created_at: session.created_at.to_rfc3339(),
+        last_seen_at: session.last_seen_at.to_rfc3339(),
+        expires_at: session.expires_at.to_rfc3339(),
+        impersonated_by: session.impersonator_id.map(|id| id.to_string()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/me/sessions",
+    responses(
+        (status = 200, description = "Active sessions of the current user", body = Vec<SessionResponse>),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "me"
+)]
+pub async fn list_my_sessions(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<Vec<SessionResponse>>, AppError> {
+    let user_id = parse_id(&claims.sub, "user")?;
+
+    let sessions = Session::list_for_user(&state.pool, user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to list sessions: {}", e)))?;
+
+    Ok(Json(
+        sessions
+            .into_iter()
+            .map(|s| to_response(s, claims.sid.as_deref()))
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/me/sessions/{id}",
+    params(
+        ("id" = String, Path, description = "Session ID")
+    ),
+    responses(
+        (status = 204, description = "Session revoked"),
+        (status = 404, description = "Session not found"),
+        (status = 401, description = "Unauthorized")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "me"
+)]
+pub async fn revoke_my_session(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    let user_id = parse_id(&claims.sub, "user")?;
+    let session_id = parse_id(&id, "session")?;
+
+    revoke(&state, user_id, user_id, session_id, &client).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/users/{id}/sessions",
+    params(
+        ("id" = String, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Active sessions of the user", body = Vec<SessionResponse>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn list_user_sessions(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<SessionResponse>>, AppError> {
+    let user_id = parse_id(&id, "user")?;
+    scope.find_user(&state.pool, user_id).await?;
+
+    let sessions = Session::list_for_user(&state.pool, user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to list sessions: {}", e)))?;
+
+    Ok(Json(
+        sessions.into_iter().map(|s| to_response(s, None)).collect(),
+    ))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/admin/users/{id}/sessions/{session_id}",
+    params(
+        ("id" = String, Path, description = "User ID"),
+        ("session_id" = String, Path, description = "Session ID")
+    ),
+    responses(
+        (status = 204, description = "Session revoked"),
+        (status = 404, description = "Session not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn revoke_user_session(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Path((id, session_id)): Path<(String, String)>,
+) -> Result<StatusCode, AppError> {
+    let admin_id = parse_id(&claims.sub, "user")?;
+    let user_id = parse_id(&id, "user")?;
+    let session_id = parse_id(&session_id, "session")?;
+    scope.find_user(&state.pool, user_id).await?;
+
+    revoke(&state, admin_id, user_id, session_id, &client).await
+}
+
+async fn revoke(
+    state: &AppState,
+    actor_id: Uuid,
+    user_id: Uuid,
+    session_id: Uuid,
+    client: &ClientInfo,
+) -> Result<StatusCode, AppError> {
+    let session = Session::revoke(&state.pool, session_id, user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to revoke session: {}", e)))?
+        .ok_or_else(|| AppError::http(404, "Session not found"))?;
+
+    if let Some(impersonator_id) = session.impersonator_id {
+        AuditEntry::record(
+            &state.pool,
+            Some(actor_id),
+            "impersonation.end",
+            Some(user_id),
+            client.ip_address.as_deref(),
+            serde_json::json!({
+                "session_id": session_id,
+                "impersonator_id": impersonator_id,
+            }),
+        )
+        .await
+        .map_err(|e| AppError::database(format!("Failed to write audit log: {}", e)))?;
+    }
+
+    info!(user_id = %user_id, session_id = %session_id, "Session revoked");
+    Ok(StatusCode::NO_CONTENT)
+}
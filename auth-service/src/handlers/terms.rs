@@ -0,0 +1,374 @@
+use axum::{
+    Extension,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use common::errors::{AppError, ErrorResponse};
+use common::models::{
+    AcceptTermsRequest, Claims, ConsentRequiredResponse, LoginResponse, PublishTermsRequest,
+    TermsAcceptanceResponse, TermsResponse, TermsSummaryResponse,
+};
+use serde::Deserialize;
+use std::time::Duration;
+use tracing::info;
+use uuid::Uuid;
+use validator::Validate;
+
+use super::{AppState, parse_id, session_response, start_session, wants_cookies};
+use crate::client_info::ClientInfo;
+use crate::db::audit::AuditEntry;
+use crate::db::login_challenges::{self, LoginChallenge};
+use crate::db::queries::User;
+use crate::db::terms::{AcceptanceStatus, Terms};
+use crate::scope::AdminScope;
+use crate::tokens;
+
+/// Path segment standing for whichever version is current
+const CURRENT: &str = "current";
+
+/// Wrong consent tokens a held login tolerates
+const MAX_ATTEMPTS: i32 = 5;
+
+#[derive(Deserialize)]
+pub struct AcceptanceQuery {
+    /// Only users who have not accepted the version
+    pub pending: Option<bool>,
+}
+
+impl From<Terms> for TermsResponse {
+    fn from(terms: Terms) -> Self {
+        Self {
+            id: terms.id.to_string(),
+            version: terms.version,
+            content: terms.content,
+            published_at: terms.published_at.to_rfc3339(),
+        }
+    }
+}
+
+impl From<AcceptanceStatus> for TermsAcceptanceResponse {
+    fn from(status: AcceptanceStatus) -> Self {
+        Self {
+            user_id: status.user_id.to_string(),
+            username: status.username,
+            version: status.version,
+            accepted_at: status.accepted_at.map(|t| t.to_rfc3339()),
+            ip_address: status.ip_address,
+        }
+    }
+}
+
+async fn current(state: &AppState) -> Result<Option<Terms>, AppError> {
+    Terms::current(&state.pool)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get terms of service: {}", e)))
+}
+
+/// The current terms if `user_id` has not accepted them yet
+async fn unaccepted(state: &AppState, user_id: Uuid) -> Result<Option<Terms>, AppError> {
+    let Some(terms) = current(state).await? else {
+        return Ok(None);
+    };
+
+    let accepted = terms
+        .is_accepted_by(&state.pool, user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to check terms acceptance: {}", e)))?;
+
+    Ok((!accepted).then_some(terms))
+}
+
+/// Hold a login until the user accepts the current terms of service. Returns
+/// `None` when they already have, or when no terms are published.
+pub(super) async fn hold_for_consent(
+    state: &AppState,
+    user: &User,
+    organization_id: Option<Uuid>,
+    audience: Option<&str>,
+) -> Result<Option<Response>, AppError> {
+    if !user.is_active {
+        return Err(AppError::auth("Account is disabled"));
+    }
+    let Some(terms) = unaccepted(state, user.id).await? else {
+        return Ok(None);
+    };
+
+    let consent_token = tokens::generate_opaque_token();
+    let expires_at = chrono::Utc::now() + Duration::from_secs(state.config.consent_ttl_seconds);
+    let challenge = LoginChallenge::create(
+        &state.pool,
+        user.id,
+        organization_id,
+        audience,
+        login_challenges::CONSENT,
+        &tokens::hash_token(&consent_token),
+        expires_at,
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to create login challenge: {}", e)))?;
+
+    info!(user_id = %user.id, version = %terms.version, "Login held for terms of service acceptance");
+
+    let response = ConsentRequiredResponse {
+        challenge_id: challenge.id.to_string(),
+        consent_token,
+        terms: terms.into(),
+        expires_at: challenge.expires_at.to_rfc3339(),
+    };
+    Ok(Some((StatusCode::ACCEPTED, Json(response)).into_response()))
+}
+
+/// Refuse to extend a session whose user has not accepted the current terms;
+/// they are asked to at their next login
+pub(super) async fn require_accepted(state: &AppState, user_id: Uuid) -> Result<(), AppError> {
+    match unaccepted(state, user_id).await? {
+        Some(terms) => Err(AppError::authorization(format!(
+            "Terms of service version {} must be accepted; sign in again",
+            terms.version
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/terms/current",
+    responses(
+        (status = 200, description = "The terms of service users must accept", body = TermsResponse),
+        (status = 404, description = "No terms of service are published")
+    ),
+    tag = "auth"
+)]
+pub async fn current_terms(State(state): State<AppState>) -> Result<Json<TermsResponse>, AppError> {
+    let terms = current(&state)
+        .await?
+        .ok_or_else(|| AppError::http(404, "No terms of service are published"))?;
+
+    Ok(Json(terms.into()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/login/consent",
+    request_body = AcceptTermsRequest,
+    responses(
+        (status = 200, description = "Terms accepted and login completed; with `cookie` the tokens are set as cookies", body = LoginResponse),
+        (status = 401, description = "Invalid or expired consent token"),
+        (status = 409, description = "A newer version was published; accept that one")
+    ),
+    tag = "auth"
+)]
+pub async fn accept_terms(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(payload): Json<AcceptTermsRequest>,
+) -> Result<Response, AppError> {
+    let use_cookies = wants_cookies(&state, payload.cookie)?;
+    let challenge_id = parse_id(&payload.challenge_id, "challenge")?;
+    let invalid = || AppError::auth("Invalid or expired consent token");
+
+    let terms = current(&state).await?.ok_or_else(invalid)?;
+    if terms.version != payload.version {
+        return Err(AppError::http(
+            409,
+            format!(
+                "Version {} is not current; accept version {}",
+                payload.version, terms.version
+            ),
+        ));
+    }
+
+    let challenge = LoginChallenge::complete(
+        &state.pool,
+        challenge_id,
+        login_challenges::CONSENT,
+        &tokens::hash_token(&payload.consent_token),
+        MAX_ATTEMPTS,
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to complete login challenge: {}", e)))?;
+
+    let Some(challenge) = challenge else {
+        LoginChallenge::record_attempt(&state.pool, challenge_id)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to update login challenge: {}", e)))?;
+        return Err(invalid());
+    };
+
+    let user = User::find_by_id(&state.pool, challenge.user_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get user: {}", e)))?
+        .ok_or_else(invalid)?;
+
+    terms
+        .accept(&state.pool, user.id, client.ip_address.as_deref())
+        .await
+        .map_err(|e| AppError::database(format!("Failed to record terms acceptance: {}", e)))?;
+
+    AuditEntry::record(
+        &state.pool,
+        Some(user.id),
+        "terms.accept",
+        Some(user.id),
+        client.ip_address.as_deref(),
+        serde_json::json!({ "version": terms.version }),
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to record audit entry: {}", e)))?;
+
+    let (session_id, response) = start_session(
+        &state,
+        user,
+        challenge.organization_id,
+        &client,
+        challenge.audience.as_deref(),
+    )
+    .await?;
+
+    info!(user_id = %challenge.user_id, version = %terms.version, "Terms of service accepted");
+
+    Ok(session_response(
+        &state,
+        session_id,
+        response,
+        challenge.audience.as_deref(),
+        use_cookies,
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/terms",
+    responses(
+        (status = 200, description = "Every version, newest first, with acceptance among the users the admin manages", body = Vec<TermsSummaryResponse>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn list_terms(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+) -> Result<Json<Vec<TermsSummaryResponse>>, AppError> {
+    let summaries = Terms::summaries(&state.pool, scope.organization())
+        .await
+        .map_err(|e| AppError::database(format!("Failed to list terms of service: {}", e)))?;
+
+    Ok(Json(
+        summaries
+            .into_iter()
+            .enumerate()
+            .map(|(i, summary)| TermsSummaryResponse {
+                id: summary.id.to_string(),
+                version: summary.version,
+                published_at: summary.published_at.to_rfc3339(),
+                current: i == 0,
+                accepted: summary.accepted,
+                pending: summary.pending,
+            })
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/terms",
+    request_body = PublishTermsRequest,
+    responses(
+        (status = 200, description = "Version published; users must accept it at their next login", body = TermsResponse),
+        (status = 400, description = "Validation error, or the version already exists", body = ErrorResponse),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Super admin role required")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn publish_terms(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Json(mut payload): Json<PublishTermsRequest>,
+) -> Result<Json<TermsResponse>, AppError> {
+    scope.require_platform()?;
+
+    payload.version = payload.version.trim().to_string();
+    payload.validate()?;
+    if payload.version == CURRENT {
+        return Err(AppError::validation(format!(
+            "'{}' is reserved and cannot be a version",
+            CURRENT
+        )));
+    }
+
+    let actor_id = claims.sub.parse().ok();
+    let terms = Terms::publish(&state.pool, &payload.version, &payload.content, actor_id)
+        .await
+        .map_err(|e| {
+            if e.to_string().contains("unique") {
+                AppError::validation("Terms of service version already exists")
+            } else {
+                AppError::database(format!("Failed to publish terms of service: {}", e))
+            }
+        })?;
+
+    AuditEntry::record(
+        &state.pool,
+        actor_id,
+        "terms.publish",
+        None,
+        client.ip_address.as_deref(),
+        serde_json::json!({ "version": terms.version }),
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to record audit entry: {}", e)))?;
+
+    info!(version = %terms.version, "Terms of service published");
+
+    Ok(Json(terms.into()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/terms/{version}/acceptances",
+    params(
+        ("version" = String, Path, description = "Terms version, or `current`"),
+        ("pending" = Option<bool>, Query, description = "Only users who have not accepted it")
+    ),
+    responses(
+        (status = 200, description = "Users the admin manages and whether they accepted the version, pending ones first", body = Vec<TermsAcceptanceResponse>),
+        (status = 404, description = "Version not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn list_acceptances(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Path(version): Path<String>,
+    Query(query): Query<AcceptanceQuery>,
+) -> Result<Json<Vec<TermsAcceptanceResponse>>, AppError> {
+    let terms = if version == CURRENT {
+        current(&state).await?
+    } else {
+        Terms::find_by_version(&state.pool, &version)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to get terms of service: {}", e)))?
+    }
+    .ok_or_else(|| AppError::http(404, "Terms of service version not found"))?;
+
+    let report = terms
+        .acceptance_report(
+            &state.pool,
+            scope.organization(),
+            query.pending.unwrap_or(false),
+        )
+        .await
+        .map_err(|e| AppError::database(format!("Failed to list terms acceptances: {}", e)))?;
+
+    Ok(Json(report.into_iter().map(Into::into).collect()))
+}
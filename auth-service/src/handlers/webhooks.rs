@@ -0,0 +1,314 @@
+use axum::{
+    Extension,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use common::errors::AppError;
+use common::models::{Claims, CreateWebhookRequest, WebhookDeliveryResponse, WebhookResponse};
+use serde::Deserialize;
+use tracing::info;
+
+use super::{AppState, parse_id};
+use crate::client_info::ClientInfo;
+use crate::db::audit::AuditEntry;
+use crate::db::organizations::Organization;
+use crate::db::webhooks::{Delivery, Webhook};
+use crate::scope::AdminScope;
+use crate::tokens;
+use crate::webhooks::{self, UserEvent};
+
+#[derive(Deserialize)]
+pub struct DeliveryQuery {
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+}
+
+impl From<Webhook> for WebhookResponse {
+    fn from(webhook: Webhook) -> Self {
+        Self {
+            id: webhook.id.to_string(),
+            organization_id: webhook.organization_id.map(|id| id.to_string()),
+            url: webhook.url,
+            events: webhook.events,
+            secret: None,
+            created_at: webhook.created_at.to_rfc3339(),
+        }
+    }
+}
+
+impl From<Delivery> for WebhookDeliveryResponse {
+    fn from(delivery: Delivery) -> Self {
+        Self {
+            id: delivery.id.to_string(),
+            event: delivery.event,
+            next_attempt_at: (delivery.status == "pending")
+                .then(|| delivery.next_attempt_at.to_rfc3339()),
+            status: delivery.status,
+            attempts: delivery.attempts,
+            last_status_code: delivery.last_status_code,
+            last_error: delivery.last_error,
+            created_at: delivery.created_at.to_rfc3339(),
+            delivered_at: delivery.delivered_at.map(|t| t.to_rfc3339()),
+            payload: delivery.payload.0,
+        }
+    }
+}
+
+/// Load a subscription the admin may manage. Organization admins only see
+/// their organization's; platform-wide subscriptions belong to super admins.
+async fn find_webhook(state: &AppState, scope: AdminScope, id: &str) -> Result<Webhook, AppError> {
+    let webhook_id = parse_id(id, "webhook")?;
+
+    let webhook = Webhook::find_by_id(&state.pool, webhook_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get webhook: {}", e)))?
+        .ok_or_else(|| AppError::http(404, "Webhook not found"))?;
+
+    match scope.organization() {
+        Some(org) if webhook.organization_id != Some(org) => {
+            Err(AppError::http(404, "Webhook not found"))
+        }
+        _ => Ok(webhook),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/webhooks",
+    responses(
+        (status = 200, description = "Webhook subscriptions visible to the admin", body = Vec<WebhookResponse>),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn list_webhooks(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+) -> Result<Json<Vec<WebhookResponse>>, AppError> {
+    let webhooks = Webhook::list(&state.pool, scope.organization())
+        .await
+        .map_err(|e| AppError::database(format!("Failed to list webhooks: {}", e)))?;
+
+    Ok(Json(webhooks.into_iter().map(Into::into).collect()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/webhooks",
+    request_body = CreateWebhookRequest,
+    responses(
+        (status = 200, description = "Webhook created; the signing secret is only returned here", body = WebhookResponse),
+        (status = 400, description = "Validation error, or the URL is not https or reaches an internal address"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden"),
+        (status = 404, description = "Organization not found")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn create_webhook(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Json(mut payload): Json<CreateWebhookRequest>,
+) -> Result<Json<WebhookResponse>, AppError> {
+    let url = payload.url.trim();
+    webhooks::check_url(url, state.config.webhook_allow_insecure)
+        .await
+        .map_err(AppError::validation)?;
+
+    payload.events.sort();
+    payload.events.dedup();
+    if payload.events.is_empty() {
+        return Err(AppError::validation("At least one event is required"));
+    }
+    if let Some(unknown) = payload
+        .events
+        .iter()
+        .find(|e| UserEvent::parse(e).is_none())
+    {
+        return Err(AppError::validation(format!("Unknown event '{}'", unknown)));
+    }
+
+    let organization_id = match (payload.organization_id.as_deref(), scope) {
+        (Some(id), _) => {
+            let org = parse_id(id, "organization")?;
+            scope.require_organization(org)?;
+            let organization = Organization::find_by_id(&state.pool, org)
+                .await
+                .map_err(|e| AppError::database(format!("Failed to get organization: {}", e)))?
+                .ok_or_else(|| AppError::http(404, "Organization not found"))?;
+            Some(organization.id)
+        }
+        (None, AdminScope::Organization(org)) => Some(org),
+        (None, AdminScope::Platform) => None,
+    };
+
+    let secret = tokens::generate_opaque_token();
+    let webhook = Webhook::create(&state.pool, organization_id, url, &secret, &payload.events)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to create webhook: {}", e)))?;
+
+    AuditEntry::record(
+        &state.pool,
+        Some(parse_id(&claims.sub, "user")?),
+        "webhook.create",
+        None,
+        client.ip_address.as_deref(),
+        serde_json::json!({
+            "webhook_id": webhook.id,
+            "url": webhook.url,
+            "events": webhook.events,
+            "organization_id": webhook.organization_id,
+        }),
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to write audit log: {}", e)))?;
+
+    info!(webhook_id = %webhook.id, url = %webhook.url, "Webhook created");
+
+    Ok(Json(WebhookResponse {
+        secret: Some(secret),
+        ..webhook.into()
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/webhooks/{id}",
+    params(
+        ("id" = String, Path, description = "Webhook ID")
+    ),
+    responses(
+        (status = 200, description = "Webhook subscription", body = WebhookResponse),
+        (status = 404, description = "Webhook not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn get_webhook(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Path(id): Path<String>,
+) -> Result<Json<WebhookResponse>, AppError> {
+    let webhook = find_webhook(&state, scope, &id).await?;
+    Ok(Json(webhook.into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/admin/webhooks/{id}",
+    params(
+        ("id" = String, Path, description = "Webhook ID")
+    ),
+    responses(
+        (status = 204, description = "Webhook and its undelivered events deleted"),
+        (status = 404, description = "Webhook not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    let webhook = find_webhook(&state, scope, &id).await?;
+
+    Webhook::delete(&state.pool, webhook.id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to delete webhook: {}", e)))?;
+
+    AuditEntry::record(
+        &state.pool,
+        Some(parse_id(&claims.sub, "user")?),
+        "webhook.delete",
+        None,
+        client.ip_address.as_deref(),
+        serde_json::json!({ "webhook_id": webhook.id, "url": webhook.url }),
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to write audit log: {}", e)))?;
+
+    info!(webhook_id = %webhook.id, "Webhook deleted");
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/webhooks/{id}/deliveries",
+    params(
+        ("id" = String, Path, description = "Webhook ID"),
+        ("status" = Option<String>, Query, description = "Only deliveries in this state: pending, delivered or dead"),
+        ("limit" = Option<i64>, Query, description = "Maximum deliveries to return (default 100, max 1000)")
+    ),
+    responses(
+        (status = 200, description = "Delivery log, newest first", body = Vec<WebhookDeliveryResponse>),
+        (status = 404, description = "Webhook not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn list_deliveries(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Path(id): Path<String>,
+    Query(params): Query<DeliveryQuery>,
+) -> Result<Json<Vec<WebhookDeliveryResponse>>, AppError> {
+    let webhook = find_webhook(&state, scope, &id).await?;
+    let limit = params.limit.unwrap_or(100).clamp(1, 1000);
+
+    let deliveries =
+        Delivery::list_for_webhook(&state.pool, webhook.id, params.status.as_deref(), limit)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to list deliveries: {}", e)))?;
+
+    Ok(Json(deliveries.into_iter().map(Into::into).collect()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/webhooks/{id}/deliveries/{delivery_id}/retry",
+    params(
+        ("id" = String, Path, description = "Webhook ID"),
+        ("delivery_id" = String, Path, description = "Delivery ID")
+    ),
+    responses(
+        (status = 200, description = "Dead delivery queued again with a fresh set of attempts", body = WebhookDeliveryResponse),
+        (status = 404, description = "Webhook or dead delivery not found"),
+        (status = 401, description = "Unauthorized"),
+        (status = 403, description = "Forbidden")
+    ),
+    security(("bearer_auth" = [])),
+    tag = "admin"
+)]
+pub async fn retry_delivery(
+    State(state): State<AppState>,
+    Extension(scope): Extension<AdminScope>,
+    Path((id, delivery_id)): Path<(String, String)>,
+) -> Result<Json<WebhookDeliveryResponse>, AppError> {
+    let webhook = find_webhook(&state, scope, &id).await?;
+    let delivery_id = parse_id(&delivery_id, "delivery")?;
+
+    let delivery = Delivery::retry(&state.pool, webhook.id, delivery_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to retry delivery: {}", e)))?
+        .ok_or_else(|| AppError::http(404, "Dead delivery not found"))?;
+
+    info!(webhook_id = %webhook.id, delivery_id = %delivery.id, "Webhook delivery requeued");
+
+    Ok(Json(delivery.into()))
+}
@@ -0,0 +1,55 @@
+//! Canonical forms of usernames and email addresses.
+//!
+//! Both are NFKC-normalized on write, so visually identical input written with
+//! different code points (full-width letters, ligatures, composed accents) is
+//! stored once. Emails are lowercased; usernames keep the case they were
+//! registered with but are unique regardless of case.
+
+use common::validation::{self, USERNAME_MAX_LENGTH};
+use unicode_normalization::UnicodeNormalization;
+
+/// NFKC form of `value` without surrounding whitespace, for lookups
+pub fn fold(value: &str) -> String {
+    value.nfkc().collect::<String>().trim().to_string()
+}
+
+/// Canonical form of a new or changed username, or why it is not allowed
+pub fn normalize_username(username: &str) -> Result<String, String> {
+    let username = fold(username);
+
+    if username.is_empty() || username.len() > USERNAME_MAX_LENGTH {
+        return Err(format!(
+            "Username must be 1-{} characters",
+            USERNAME_MAX_LENGTH
+        ));
+    }
+    if let Err(e) = validation::username(&username) {
+        return Err(format!("username: {}", e));
+    }
+
+    Ok(username)
+}
+
+/// Lowercased NFKC form of an email address
+pub fn canonical_email(email: &str) -> String {
+    fold(email).to_lowercase()
+}
+
+/// Canonical form of a new or changed email address
+pub fn normalize_email(email: &str) -> Result<String, String> {
+    let email = canonical_email(email);
+
+    if !is_valid_email(&email) {
+        return Err("Invalid email address".to_string());
+    }
+
+    Ok(email)
+}
+
+/// Loose syntactic check that a string looks like a deliverable address
+pub fn is_valid_email(email: &str) -> bool {
+    email
+        .split_once('@')
+        .is_some_and(|(local, domain)| !local.is_empty() && domain.contains('.'))
+        && !email.contains(char::is_whitespace)
+}
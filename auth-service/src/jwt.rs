@@ -1,38 +1,85 @@
-use common::models::Claims;
-use jsonwebtoken::{EncodingKey, Header, encode};
-use std::time::{SystemTime, UNIX_EPOCH};
+use common::models::{Actor, Claims, Quota};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+use crate::config::Config;
+
+/// The principal and scope an access token is issued for
+pub struct TokenGrant {
+    pub user_id: Uuid,
+    pub role: String,
+    pub permissions: Vec<String>,
+    pub audience: Vec<String>,
+    pub session_id: Option<Uuid>,
+    /// Admin impersonating the user, recorded in the `act` claim
+    pub actor: Option<Uuid>,
+    pub organization_id: Option<Uuid>,
+    pub organization_role: Option<String>,
+    pub quota: Option<Quota>,
+}
 
 pub struct JwtService {
     encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    issuer: String,
+    leeway_seconds: u64,
 }
 
 impl JwtService {
-    pub fn new(secret: &str) -> Self {
+    pub fn new(config: &Config) -> Self {
         Self {
-            encoding_key: EncodingKey::from_secret(secret.as_ref()),
+            encoding_key: EncodingKey::from_secret(config.jwt_secret.as_ref()),
+            decoding_key: DecodingKey::from_secret(config.jwt_secret.as_ref()),
+            issuer: config.jwt_issuer.clone(),
+            leeway_seconds: config.jwt_leeway_seconds,
         }
     }
 
     pub fn generate_token(
         &self,
-        user_id: &str,
-        role: &str,
-        permissions: Vec<String>,
-        exp_hours: u64,
+        grant: TokenGrant,
+        ttl: Duration,
     ) -> Result<String, jsonwebtoken::errors::Error> {
-        let exp = SystemTime::now()
+        let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
-            .as_secs()
-            + (exp_hours * 3600);
+            .as_secs();
 
         let claims = Claims {
-            sub: user_id.to_string(),
-            exp: exp as usize,
-            role: role.to_string(),
-            permissions,
+            sub: grant.user_id.to_string(),
+            iss: self.issuer.clone(),
+            aud: grant.audience,
+            exp: (now + ttl.as_secs()) as usize,
+            iat: now as usize,
+            nbf: now as usize,
+            sid: grant.session_id.map(|id| id.to_string()),
+            act: grant.actor.map(|id| Actor {
+                sub: id.to_string(),
+            }),
+            org: grant.organization_id.map(|id| id.to_string()),
+            org_role: grant.organization_role,
+            role: grant.role,
+            permissions: grant.permissions,
+            quota: grant.quota,
         };
 
         encode(&Header::default(), &claims, &self.encoding_key)
     }
+
+    /// Decode a token, checking signature, issuer, `audience`, `exp` and `nbf`
+    pub fn validate_token(
+        &self,
+        token: &str,
+        audience: &str,
+    ) -> Result<Claims, jsonwebtoken::errors::Error> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_audience(&[audience]);
+        validation.set_required_spec_claims(&["exp", "nbf", "iat", "iss", "aud", "sub"]);
+        validation.validate_nbf = true;
+        validation.leeway = self.leeway_seconds;
+
+        decode::<Claims>(token, &self.decoding_key, &validation).map(|data| data.claims)
+    }
 }
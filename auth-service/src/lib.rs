@@ -0,0 +1,23 @@
+//! Authentication service: the HTTP API served by the `auth-service` binary
+//! and the operator tooling in `auth-admin` share these modules.
+
+pub mod authenticator;
+pub mod client_info;
+pub mod config;
+pub mod cookies;
+pub mod csv;
+pub mod db;
+pub mod handlers;
+pub mod identity;
+pub mod jwt;
+pub mod mailer;
+pub mod middleware;
+pub mod notifier;
+pub mod openapi;
+pub mod permissions;
+pub mod pii;
+pub mod risk;
+pub mod scim;
+pub mod scope;
+pub mod tokens;
+pub mod webhooks;
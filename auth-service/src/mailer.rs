@@ -0,0 +1,57 @@
+use std::path::PathBuf;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::config::Config;
+
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Outgoing mail transport. Messages are either logged or written as `.eml`
+/// files to an outbox directory that a relay picks up.
+pub enum Mailer {
+    Log { from: String },
+    Outbox { from: String, dir: PathBuf },
+}
+
+impl Mailer {
+    pub fn from_config(config: &Config) -> Self {
+        let from = config.mail_from.clone();
+        match &config.mail_outbox_dir {
+            Some(dir) => Self::Outbox {
+                from,
+                dir: PathBuf::from(dir),
+            },
+            None => Self::Log { from },
+        }
+    }
+
+    pub async fn send(&self, email: &Email) -> std::io::Result<()> {
+        match self {
+            Self::Log { from } => {
+                info!(from = %from, to = %email.to, subject = %email.subject, "Email (not delivered, no outbox configured)");
+                Ok(())
+            }
+            Self::Outbox { from, dir } => {
+                let message = format!(
+                    "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n",
+                    from,
+                    email.to,
+                    email.subject,
+                    chrono::Utc::now().to_rfc2822(),
+                    email.body.replace('\n', "\r\n"),
+                );
+
+                tokio::fs::create_dir_all(dir).await?;
+                let path = dir.join(format!("{}.eml", Uuid::new_v4()));
+                tokio::fs::write(&path, message).await?;
+
+                info!(to = %email.to, path = %path.display(), "Email written to outbox");
+                Ok(())
+            }
+        }
+    }
+}
@@ -1,16 +1,17 @@
-mod config;
-mod db;
-mod handlers;
-mod jwt;
-mod middleware;
-mod openapi;
-
+use auth_service::{
+    authenticator, config, cookies, db, handlers, jwt, mailer, middleware, notifier, openapi,
+    permissions, pii, risk, scim, webhooks,
+};
 use axum::{
-    Router, middleware as axum_middleware,
+    Router,
+    http::{HeaderName, HeaderValue, Method, header},
+    middleware as axum_middleware,
     routing::{delete, get, post, put},
 };
+use common::policy::{self, PolicyEngine};
 use common::tracing::init_tracing_pretty;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use tokio::signal;
 use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
@@ -21,58 +22,350 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     init_tracing_pretty();
 
     let config = config::Config::from_env();
-    let pool = db::create_pool(&config.database_url).await?;
+    // Installed before migrations, which read and rewrite emails
+    if let Some(keyring) = pii::Keyring::from_config(&config)? {
+        pii::install(keyring);
+    }
+    let pool = db::create_pool(&config).await?;
+    let replica = db::create_replica_pool(&config).await?;
+    if replica.is_some() {
+        info!("Read-only admin queries use the database replica");
+    }
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
+
+    webhooks::Dispatcher::new(pool.clone(), &config).spawn();
+    pii::Reencryptor::new(pool.clone(), &config).spawn();
+
+    let permissions = Arc::new(permissions::PermissionCache::from_config(&config));
+    permissions.clone().listen(pool.clone());
 
+    let mailer = Arc::new(mailer::Mailer::from_config(&config));
     let state = handlers::AppState {
         pool: pool.clone(),
-        jwt_secret: config.jwt_secret.clone(),
+        replica,
+        jwt: Arc::new(jwt::JwtService::new(&config)),
+        notifier: Arc::new(notifier::Notifier::from_config(&config, mailer.clone())),
+        mailer,
+        authenticators: Arc::new(authenticator::AuthenticatorChain::from_config(&config)),
+        risk: Arc::new(risk::RiskPolicy::from_config(&config)),
+        policy: Arc::new(PolicyEngine::from_env()?),
+        permissions,
+        config: Arc::new(config),
     };
 
     let app = create_router(state);
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
     info!("Auth service starting on {}", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await?;
 
     info!("Auth service stopped");
     Ok(())
 }
 
 fn create_router(state: handlers::AppState) -> Router {
+    // Replays retried creations instead of running them twice
+    let idempotent = axum_middleware::from_fn_with_state(state.clone(), middleware::idempotency);
+
     // Public routes (no auth required)
     let public_routes = Router::new()
         .route("/health", get(handlers::health))
         .route("/api/auth/login", post(handlers::login))
-        .route("/api/auth/register", post(handlers::register));
+        .route(
+            "/api/auth/register",
+            post(handlers::register).layer(idempotent.clone()),
+        )
+        .route("/api/auth/refresh", post(handlers::refresh))
+        .route("/api/auth/logout", post(handlers::logout))
+        .route(
+            "/api/auth/login/verify",
+            post(handlers::login_risk::verify_login),
+        )
+        .route(
+            "/api/auth/magic-link",
+            post(handlers::magic_links::request_magic_link),
+        )
+        .route(
+            "/api/auth/magic-link/redeem",
+            post(handlers::magic_links::redeem_magic_link),
+        )
+        .route(
+            "/api/auth/invitations/accept",
+            post(handlers::invitations::accept_invitation),
+        )
+        .route(
+            "/api/auth/login/password",
+            post(handlers::account::change_expired_password),
+        )
+        .route(
+            "/api/auth/login/consent",
+            post(handlers::terms::accept_terms),
+        )
+        .route("/api/terms/current", get(handlers::terms::current_terms));
 
-    // Admin routes (require JWT + admin role)
+    // Self-service routes (require JWT)
+    let me_routes = Router::new()
+        .route(
+            "/api/me/sessions",
+            get(handlers::sessions::list_my_sessions),
+        )
+        .route(
+            "/api/me/sessions/{id}",
+            delete(handlers::sessions::revoke_my_session)
+                .layer(axum_middleware::from_fn(middleware::deny_impersonation)),
+        )
+        .route(
+            "/api/me/password",
+            put(handlers::account::change_password)
+                .layer(axum_middleware::from_fn(middleware::deny_impersonation)),
+        )
+        .route(
+            "/api/me/impersonation/end",
+            post(handlers::impersonation::end_impersonation),
+        )
+        .route("/api/me/usage", get(handlers::quotas::my_usage))
+        .route(
+            "/api/me/organizations",
+            get(handlers::organizations::list_my_organizations),
+        )
+        .route(
+            "/api/me/export",
+            get(handlers::privacy::export_my_data)
+                .layer(axum_middleware::from_fn(middleware::deny_impersonation)),
+        )
+        .route(
+            "/api/me",
+            delete(handlers::privacy::erase_my_account)
+                .layer(axum_middleware::from_fn(middleware::deny_impersonation)),
+        )
+        .layer(axum_middleware::from_fn_with_state(
+            state.policy.clone(),
+            policy::enforce,
+        ))
+        .layer(axum_middleware::from_fn_with_state(
+            state.clone(),
+            middleware::auth_middleware,
+        ));
+
+    // Admin routes (require JWT + organization admin or super admin role)
     let admin_routes = Router::new()
         .route("/api/admin/users", get(handlers::list_users))
-        .route("/api/admin/users", post(handlers::create_user))
+        .route(
+            "/api/admin/users",
+            post(handlers::create_user).layer(idempotent.clone()),
+        )
         .route("/api/admin/users/{id}", get(handlers::get_user))
         .route("/api/admin/users/{id}", delete(handlers::delete_user))
         .route(
             "/api/admin/users/{id}/role",
             put(handlers::update_user_role),
         )
+        .route(
+            "/api/admin/users/{id}/sessions",
+            get(handlers::sessions::list_user_sessions),
+        )
+        .route(
+            "/api/admin/users/{id}/sessions/{session_id}",
+            delete(handlers::sessions::revoke_user_session),
+        )
+        .route(
+            "/api/admin/users/{id}/impersonate",
+            post(handlers::impersonation::impersonate_user),
+        )
+        .route(
+            "/api/admin/users/import",
+            post(handlers::bulk::import_users),
+        )
+        .route("/api/admin/users/export", get(handlers::bulk::export_users))
+        .route(
+            "/api/admin/users/{id}/export",
+            get(handlers::privacy::export_user_data),
+        )
+        .route(
+            "/api/admin/users/{id}/erase",
+            post(handlers::privacy::erase_user),
+        )
+        .route(
+            "/api/admin/invitations",
+            get(handlers::invitations::list_invitations)
+                .merge(post(handlers::invitations::create_invitation).layer(idempotent.clone())),
+        )
+        .route(
+            "/api/admin/invitations/{id}",
+            delete(handlers::invitations::revoke_invitation),
+        )
+        .route(
+            "/api/admin/invitations/{id}/resend",
+            post(handlers::invitations::resend_invitation),
+        )
+        .route("/api/admin/audit", get(handlers::audit::list_audit_log))
+        .route(
+            "/api/admin/organizations",
+            get(handlers::organizations::list_organizations).merge(
+                post(handlers::organizations::create_organization).layer(idempotent.clone()),
+            ),
+        )
+        .route(
+            "/api/admin/organizations/{id}/members",
+            get(handlers::organizations::list_members),
+        )
+        .route(
+            "/api/admin/organizations/{id}/members/{user_id}",
+            put(handlers::organizations::set_member).delete(handlers::organizations::remove_member),
+        )
+        .route(
+            "/api/admin/groups",
+            get(handlers::groups::list_groups)
+                .merge(post(handlers::groups::create_group).layer(idempotent.clone())),
+        )
+        .route(
+            "/api/admin/groups/{id}",
+            get(handlers::groups::get_group).delete(handlers::groups::delete_group),
+        )
+        .route(
+            "/api/admin/groups/{id}/permissions",
+            put(handlers::groups::set_group_permissions),
+        )
+        .route(
+            "/api/admin/groups/{id}/members",
+            get(handlers::groups::list_group_members),
+        )
+        .route(
+            "/api/admin/groups/{id}/members/{user_id}",
+            put(handlers::groups::add_group_member).delete(handlers::groups::remove_group_member),
+        )
+        .route("/api/admin/quotas", get(handlers::quotas::list_quotas))
+        .route(
+            "/api/admin/quotas/roles/{role}",
+            put(handlers::quotas::set_role_quota).delete(handlers::quotas::delete_role_quota),
+        )
+        .route(
+            "/api/admin/quotas/users/{id}",
+            put(handlers::quotas::set_user_quota).delete(handlers::quotas::delete_user_quota),
+        )
+        .route(
+            "/api/admin/webhooks",
+            get(handlers::webhooks::list_webhooks).post(handlers::webhooks::create_webhook),
+        )
+        .route(
+            "/api/admin/webhooks/{id}",
+            get(handlers::webhooks::get_webhook).delete(handlers::webhooks::delete_webhook),
+        )
+        .route(
+            "/api/admin/webhooks/{id}/deliveries",
+            get(handlers::webhooks::list_deliveries),
+        )
+        .route(
+            "/api/admin/webhooks/{id}/deliveries/{delivery_id}/retry",
+            post(handlers::webhooks::retry_delivery),
+        )
+        .route(
+            "/api/admin/terms",
+            get(handlers::terms::list_terms).post(handlers::terms::publish_terms),
+        )
+        .route(
+            "/api/admin/terms/{version}/acceptances",
+            get(handlers::terms::list_acceptances),
+        )
+        .route(
+            "/api/admin/policy/explain",
+            post(handlers::policy::explain_policy),
+        )
+        .layer(axum_middleware::from_fn_with_state(
+            state.policy.clone(),
+            policy::enforce,
+        ))
         .layer(axum_middleware::from_fn(middleware::require_admin))
         .layer(axum_middleware::from_fn_with_state(
             state.clone(),
             middleware::auth_middleware,
         ));
 
+    // SCIM provisioning routes (require the provisioning token)
+    let scim_routes = Router::new()
+        .route(
+            "/scim/v2/ServiceProviderConfig",
+            get(scim::service_provider_config),
+        )
+        .route(
+            "/scim/v2/Users",
+            get(scim::users::list_users).post(scim::users::create_user),
+        )
+        .route(
+            "/scim/v2/Users/{id}",
+            get(scim::users::get_user)
+                .put(scim::users::replace_user)
+                .patch(scim::users::patch_user)
+                .delete(scim::users::delete_user),
+        )
+        .route(
+            "/scim/v2/Groups",
+            get(scim::groups::list_groups).post(scim::groups::create_group),
+        )
+        .route(
+            "/scim/v2/Groups/{id}",
+            get(scim::groups::get_group)
+                .put(scim::groups::replace_group)
+                .patch(scim::groups::patch_group)
+                .delete(scim::groups::delete_group),
+        )
+        .layer(axum_middleware::from_fn_with_state(
+            state.clone(),
+            scim::require_provisioning_token,
+        ));
+
     public_routes
+        .merge(me_routes)
         .merge(admin_routes)
+        .merge(scim_routes)
         .merge(openapi::swagger_ui())
         .layer(TraceLayer::new_for_http())
-        .layer(CorsLayer::permissive())
+        .layer(cors_layer(&state.config))
         .with_state(state)
 }
 
+/// Cookie sessions need credentialed CORS, which is only granted to the
+/// configured origins. Without any, every origin may make bearer requests.
+fn cors_layer(config: &config::Config) -> CorsLayer {
+    if config.cors_allowed_origins.is_empty() {
+        return CorsLayer::permissive();
+    }
+
+    let origins: Vec<HeaderValue> = config
+        .cors_allowed_origins
+        .iter()
+        .map(|origin| {
+            origin
+                .parse()
+                .expect("CORS_ALLOWED_ORIGINS must be valid origins")
+        })
+        .collect();
+
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_credentials(true)
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+        ])
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            HeaderName::from_static(cookies::CSRF_HEADER),
+            HeaderName::from_static(middleware::IDEMPOTENCY_KEY_HEADER),
+        ])
+}
+
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c()
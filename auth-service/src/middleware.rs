@@ -1,15 +1,23 @@
 use axum::{
     body::Body,
-    extract::{Request, State},
-    http::HeaderMap,
+    extract::{FromRequestParts, Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
 };
 use common::errors::AppError;
 use common::models::Claims;
-use jsonwebtoken::{DecodingKey, Validation, decode};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tracing::{error, warn};
+use uuid::Uuid;
 
+use crate::client_info::ClientInfo;
+use crate::cookies;
+use crate::db::idempotency::IdempotentRequest;
+use crate::db::sessions::Session;
 use crate::handlers::AppState;
+use crate::scope::AdminScope;
 
 /// Middleware to validate JWT token and extract claims
 pub async fn auth_middleware(
@@ -18,41 +26,233 @@ pub async fn auth_middleware(
     mut request: Request<Body>,
     next: Next,
 ) -> Result<Response, AppError> {
-    let auth_header = headers
-        .get("Authorization")
-        .and_then(|h| h.to_str().ok())
-        .ok_or_else(|| AppError::auth("Missing Authorization header"))?;
+    // A bearer header wins; the session cookie is only consulted without one
+    let (token, from_cookie) = match headers.get("Authorization") {
+        Some(value) => {
+            let auth_header = value
+                .to_str()
+                .map_err(|_| AppError::auth("Invalid Authorization header format"))?;
+            let token = auth_header
+                .strip_prefix("Bearer ")
+                .ok_or_else(|| AppError::auth("Invalid Authorization header format"))?;
+            (token, false)
+        }
+        None => match cookies::get(&headers, cookies::ACCESS_COOKIE) {
+            Some(token) if state.config.session_cookies => (token, true),
+            _ => return Err(AppError::auth("Missing Authorization header")),
+        },
+    };
 
-    if !auth_header.starts_with("Bearer ") {
-        return Err(AppError::auth("Invalid Authorization header format"));
-    }
+    let claims = state
+        .jwt
+        .validate_token(token, &state.config.jwt_audience)
+        .map_err(|e| AppError::auth(format!("Invalid token: {}", e)))?;
 
-    let token = &auth_header[7..];
-    let decoding_key = DecodingKey::from_secret(state.jwt_secret.as_ref());
-    let validation = Validation::default();
+    // Tokens bound to a session die with it
+    let session_id = claims
+        .sid
+        .as_deref()
+        .map(|sid| Uuid::parse_str(sid).map_err(|_| AppError::auth("Invalid token: malformed sid")))
+        .transpose()?;
+    if let Some(session_id) = session_id {
+        let active = Session::is_active(&state.pool, session_id)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to check session: {}", e)))?;
+        if !active {
+            return Err(AppError::auth("Session has been revoked or has expired"));
+        }
 
-    let token_data = decode::<Claims>(token, &decoding_key, &validation)
-        .map_err(|e| AppError::auth(format!("Invalid token: {}", e)))?;
+        Session::touch(&state.pool, session_id)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to update session: {}", e)))?;
+    }
+
+    // Browsers attach cookies to cross-site requests, so those must prove they
+    // come from a page that can read the CSRF cookie
+    if from_cookie && !cookies::is_safe(request.method()) {
+        let session_id = session_id.ok_or_else(|| AppError::auth("Invalid token: missing sid"))?;
+        cookies::check_csrf(&state.config, &headers, session_id)?;
+    }
 
     // Insert claims into request extensions for handlers to access
-    request.extensions_mut().insert(token_data.claims);
+    request.extensions_mut().insert(claims);
 
     Ok(next.run(request).await)
 }
 
-/// Middleware to require admin role
-pub async fn require_admin(request: Request<Body>, next: Next) -> Result<Response, AppError> {
+/// Middleware to require an admin role, attaching the [`AdminScope`] the token may manage
+pub async fn require_admin(mut request: Request<Body>, next: Next) -> Result<Response, AppError> {
     let claims = request
         .extensions()
         .get::<Claims>()
-        .ok_or_else(|| AppError::authorization("No claims found in request"))?
-        .clone();
+        .ok_or_else(|| AppError::authorization("No claims found in request"))?;
 
-    if claims.role != "admin" {
+    let scope = AdminScope::from_claims(claims)?;
+    request.extensions_mut().insert(scope);
+
+    Ok(next.run(request).await)
+}
+
+/// Middleware to keep impersonation tokens away from sensitive self-service actions
+pub async fn deny_impersonation(request: Request<Body>, next: Next) -> Result<Response, AppError> {
+    let claims = request
+        .extensions()
+        .get::<Claims>()
+        .ok_or_else(|| AppError::authorization("No claims found in request"))?;
+
+    if claims.act.is_some() {
         return Err(AppError::authorization(
-            "Admin role required for this endpoint",
+            "This action is not permitted while impersonating a user",
         ));
     }
 
     Ok(next.run(request).await)
 }
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Largest request or response body an idempotent request may have
+const IDEMPOTENT_BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+/// How long a retry waits out a first attempt that has not finished before taking it over
+const IDEMPOTENCY_LEASE: Duration = Duration::from_secs(60);
+
+/// Middleware honouring the `Idempotency-Key` header. The first request with a
+/// key runs and its response is stored; repeats with the same method, path and
+/// body get that response replayed, and repeats with anything else are rejected.
+/// Keys are scoped to the authenticated caller, or for anonymous callers to
+/// their address, so clients cannot collide with or replay each other's requests.
+pub async fn idempotency(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(next.run(request).await);
+    };
+    let key = key
+        .to_str()
+        .ok()
+        .filter(|k| !k.is_empty() && k.len() <= 255)
+        .ok_or_else(|| AppError::validation("Idempotency-Key must be 1-255 visible characters"))?
+        .to_string();
+
+    let user = request.extensions().get::<Claims>().map(|c| c.sub.clone());
+
+    let (mut parts, body) = request.into_parts();
+    let Ok(client) = ClientInfo::from_request_parts(&mut parts, &state).await;
+    let body = axum::body::to_bytes(body, IDEMPOTENT_BODY_LIMIT)
+        .await
+        .map_err(|_| AppError::http(413, "Request body too large"))?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(parts.method.as_str());
+    hasher.update(b"\n");
+    hasher.update(parts.uri.path_and_query().map_or("", |p| p.as_str()));
+    hasher.update(b"\n");
+    hasher.update(&body);
+    let fingerprint = format!("{:x}", hasher.finalize());
+
+    // Anonymous keys are scoped to the client address. A replay still needs
+    // the same body, which for registration includes the password, and a
+    // different body under the same key is refused like any other.
+    let scope = user.unwrap_or_else(|| {
+        format!(
+            "anonymous:{}",
+            client.ip_address.as_deref().unwrap_or("unknown")
+        )
+    });
+
+    let existing = IdempotentRequest::claim(
+        &state.pool,
+        &scope,
+        &key,
+        &fingerprint,
+        chrono::Utc::now() + IDEMPOTENCY_LEASE,
+    )
+    .await
+    .map_err(|e| AppError::database(format!("Failed to check idempotency key: {}", e)))?;
+
+    if let Some(existing) = existing {
+        if existing.fingerprint != fingerprint {
+            return Err(AppError::http(
+                422,
+                "Idempotency-Key was already used for a different request",
+            ));
+        }
+        let Some(status_code) = existing.status_code else {
+            return Err(AppError::http(
+                409,
+                "A request with this Idempotency-Key is still in progress",
+            ));
+        };
+        return Ok(replay(
+            status_code,
+            existing.content_type,
+            existing.response_body,
+        ));
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+    // Server errors are not final: give the key up so a retry runs again
+    if response.status().is_server_error() {
+        if let Err(e) = IdempotentRequest::release(&state.pool, &scope, &key).await {
+            error!(error = %e, "Failed to release idempotency key");
+        }
+        return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, IDEMPOTENT_BODY_LIMIT).await {
+        Ok(body) => body,
+        Err(e) => {
+            warn!(error = %e, "Failed to buffer response for idempotency key");
+            if let Err(e) = IdempotentRequest::release(&state.pool, &scope, &key).await {
+                error!(error = %e, "Failed to release idempotency key");
+            }
+            return Err(AppError::internal("Failed to read response"));
+        }
+    };
+
+    let expires_at = chrono::Utc::now() + Duration::from_secs(state.config.idempotency_ttl_seconds);
+    let content_type = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok());
+    if let Err(e) = IdempotentRequest::complete(
+        &state.pool,
+        &scope,
+        &key,
+        parts.status.as_u16() as i32,
+        content_type,
+        &body,
+        expires_at,
+    )
+    .await
+    {
+        // The request itself succeeded; a retry will find the key in progress
+        // until its lease runs out
+        error!(error = %e, "Failed to store idempotent response");
+    }
+
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+/// Rebuild a stored response, marked as a replay
+fn replay(status_code: i32, content_type: Option<String>, body: Option<Vec<u8>>) -> Response {
+    let status = u16::try_from(status_code)
+        .ok()
+        .and_then(|s| StatusCode::from_u16(s).ok())
+        .unwrap_or(StatusCode::OK);
+
+    let mut response = (status, body.unwrap_or_default()).into_response();
+    let headers = response.headers_mut();
+    headers.remove(header::CONTENT_TYPE);
+    if let Some(value) = content_type.and_then(|v| HeaderValue::from_str(&v).ok()) {
+        headers.insert(header::CONTENT_TYPE, value);
+    }
+    headers.insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+    response
+}
@@ -0,0 +1,52 @@
+use std::sync::Arc;
+use tracing::info;
+
+use crate::config::Config;
+use crate::db::queries::User;
+use crate::mailer::{Email, Mailer};
+
+/// A security notice for the owner of an account
+pub struct Notice {
+    pub subject: String,
+    pub body: String,
+}
+
+/// How users are told about security events on their account
+pub enum Notifier {
+    /// Notices are dropped
+    Disabled,
+    /// Notices are logged instead of delivered
+    Log,
+    /// Notices are emailed to the account's address
+    Email(Arc<Mailer>),
+}
+
+impl Notifier {
+    pub fn from_config(config: &Config, mailer: Arc<Mailer>) -> Self {
+        match config.risk_notifier.as_str() {
+            "none" => Self::Disabled,
+            "log" => Self::Log,
+            "email" => Self::Email(mailer),
+            other => panic!("Unknown notifier '{}'", other),
+        }
+    }
+
+    pub async fn notify(&self, user: &User, notice: Notice) -> std::io::Result<()> {
+        match self {
+            Self::Disabled => Ok(()),
+            Self::Log => {
+                info!(user_id = %user.id, subject = %notice.subject, "Security notice (not delivered)");
+                Ok(())
+            }
+            Self::Email(mailer) => {
+                mailer
+                    .send(&Email {
+                        to: user.email.clone(),
+                        subject: notice.subject,
+                        body: notice.body,
+                    })
+                    .await
+            }
+        }
+    }
+}
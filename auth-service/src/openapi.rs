@@ -2,7 +2,20 @@ use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
 use crate::handlers;
-use common::models::{CreateUserRequest, LoginRequest, LoginResponse, UserResponse};
+use common::errors::{ErrorResponse, FieldError};
+use common::models::{
+    AcceptInvitationRequest, AcceptTermsRequest, AuditEntryResponse, ChangePasswordRequest,
+    ConsentRequiredResponse, CreateGroupRequest, CreateInvitationRequest,
+    CreateOrganizationRequest, CreateUserRequest, CreateWebhookRequest, EraseAccountRequest,
+    ExpiredPasswordChangeRequest, GroupPermissionsRequest, GroupResponse, ImpersonationResponse,
+    ImportReport, ImportRowResult, InvitationResponse, LoginChallengeResponse, LoginRequest,
+    LoginResponse, MagicLinkRequest, MembershipResponse, OrganizationResponse,
+    PasswordChangeRequiredResponse, PolicyExplainRequest, PublishTermsRequest, Quota,
+    QuotaResponse, QuotaUsage, RedeemMagicLinkRequest, RefreshRequest, RoleRequest,
+    SessionResponse, TermsAcceptanceResponse, TermsResponse, TermsSummaryResponse, UsageResponse,
+    UserDataExport, UserResponse, VerifyLoginRequest, WebhookDeliveryResponse, WebhookResponse,
+};
+use common::policy::{Effect, Explanation, RuleTrace};
 
 #[derive(OpenApi)]
 #[openapi(
@@ -10,20 +23,122 @@ use common::models::{CreateUserRequest, LoginRequest, LoginResponse, UserRespons
         handlers::health,
         handlers::login,
         handlers::register,
+        handlers::refresh,
+        handlers::logout,
+        handlers::login_risk::verify_login,
+        handlers::account::change_expired_password,
+        handlers::magic_links::request_magic_link,
+        handlers::magic_links::redeem_magic_link,
+        handlers::sessions::list_my_sessions,
+        handlers::sessions::revoke_my_session,
+        handlers::account::change_password,
+        handlers::impersonation::end_impersonation,
+        handlers::organizations::list_my_organizations,
         handlers::list_users,
         handlers::create_user,
         handlers::get_user,
         handlers::delete_user,
         handlers::update_user_role,
+        handlers::sessions::list_user_sessions,
+        handlers::sessions::revoke_user_session,
+        handlers::impersonation::impersonate_user,
+        handlers::audit::list_audit_log,
+        handlers::bulk::import_users,
+        handlers::bulk::export_users,
+        handlers::invitations::list_invitations,
+        handlers::invitations::create_invitation,
+        handlers::invitations::resend_invitation,
+        handlers::invitations::revoke_invitation,
+        handlers::invitations::accept_invitation,
+        handlers::privacy::export_my_data,
+        handlers::privacy::erase_my_account,
+        handlers::privacy::export_user_data,
+        handlers::privacy::erase_user,
+        handlers::organizations::list_organizations,
+        handlers::organizations::create_organization,
+        handlers::organizations::list_members,
+        handlers::organizations::set_member,
+        handlers::organizations::remove_member,
+        handlers::groups::list_groups,
+        handlers::groups::create_group,
+        handlers::groups::get_group,
+        handlers::groups::delete_group,
+        handlers::groups::set_group_permissions,
+        handlers::groups::list_group_members,
+        handlers::groups::add_group_member,
+        handlers::groups::remove_group_member,
+        handlers::policy::explain_policy,
+        handlers::quotas::my_usage,
+        handlers::quotas::list_quotas,
+        handlers::quotas::set_role_quota,
+        handlers::quotas::delete_role_quota,
+        handlers::quotas::set_user_quota,
+        handlers::quotas::delete_user_quota,
+        handlers::webhooks::list_webhooks,
+        handlers::webhooks::create_webhook,
+        handlers::webhooks::get_webhook,
+        handlers::webhooks::delete_webhook,
+        handlers::webhooks::list_deliveries,
+        handlers::webhooks::retry_delivery,
+        handlers::terms::current_terms,
+        handlers::terms::accept_terms,
+        handlers::terms::list_terms,
+        handlers::terms::publish_terms,
+        handlers::terms::list_acceptances,
     ),
     components(schemas(
+        ErrorResponse,
+        FieldError,
         LoginRequest,
         LoginResponse,
         CreateUserRequest,
         UserResponse,
+        RefreshRequest,
+        SessionResponse,
+        ChangePasswordRequest,
+        ImpersonationResponse,
+        AuditEntryResponse,
+        OrganizationResponse,
+        CreateOrganizationRequest,
+        MembershipResponse,
+        RoleRequest,
+        GroupResponse,
+        CreateGroupRequest,
+        GroupPermissionsRequest,
+        ImportReport,
+        ImportRowResult,
+        InvitationResponse,
+        CreateInvitationRequest,
+        AcceptInvitationRequest,
+        UserDataExport,
+        EraseAccountRequest,
+        MagicLinkRequest,
+        RedeemMagicLinkRequest,
+        LoginChallengeResponse,
+        VerifyLoginRequest,
+        PasswordChangeRequiredResponse,
+        ExpiredPasswordChangeRequest,
+        PolicyExplainRequest,
+        Explanation,
+        RuleTrace,
+        Effect,
+        Quota,
+        QuotaResponse,
+        QuotaUsage,
+        UsageResponse,
+        CreateWebhookRequest,
+        WebhookResponse,
+        WebhookDeliveryResponse,
+        ConsentRequiredResponse,
+        AcceptTermsRequest,
+        TermsResponse,
+        PublishTermsRequest,
+        TermsSummaryResponse,
+        TermsAcceptanceResponse,
     )),
     tags(
         (name = "auth", description = "Authentication endpoints"),
+        (name = "me", description = "Self-service endpoints for the current user"),
         (name = "admin", description = "Admin user management endpoints"),
     ),
 )]
@@ -0,0 +1,194 @@
+//! In-process cache of the permissions granted to each role.
+//!
+//! Every token issued resolves the user's permissions, and the grants of a
+//! role change rarely, so they are cached for `PERMISSION_CACHE_TTL_SECONDS`.
+//! Triggers on `role_permissions` and `permissions` send a notification on
+//! [`CHANGED_CHANNEL`] whenever the grants change, and every replica's
+//! [`PermissionCache::listen`] task drops the affected roles right away. The
+//! TTL only bounds staleness while a replica is disconnected from the
+//! channel. Group permissions depend on the user and are always read.
+
+use common::errors::AppError;
+use sqlx::PgPool;
+use sqlx::postgres::PgListener;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::db::queries::User;
+
+/// Channel the migration's triggers notify with the changed role, or with an
+/// empty payload when every role may be affected
+pub const CHANGED_CHANNEL: &str = "role_permissions_changed";
+
+/// Delay before listening again after the connection failed
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Refuse to assign `role` unless `role_permissions` grants it something.
+/// Every path that sets a user or membership role checks it here, so roles
+/// added to the table become assignable without a code change.
+pub async fn require_role(pool: &PgPool, role: &str) -> Result<(), AppError> {
+    let roles = User::roles(pool)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to list roles: {}", e)))?;
+
+    if roles.iter().any(|known| known == role) {
+        Ok(())
+    } else {
+        Err(AppError::validation(format!(
+            "Unknown role '{}', expected one of {}",
+            role,
+            roles.join(", ")
+        )))
+    }
+}
+
+struct Entry {
+    permissions: Arc<Vec<String>>,
+    loaded_at: Instant,
+}
+
+pub struct PermissionCache {
+    ttl: Duration,
+    roles: RwLock<HashMap<String, Entry>>,
+    /// Bumped by every invalidation, so a load that raced one is not stored
+    generation: AtomicU64,
+}
+
+impl PermissionCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            roles: RwLock::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(Duration::from_secs(config.permission_cache_ttl_seconds))
+    }
+
+    /// Permissions granted to `role` together with those granted to any of the
+    /// user's groups in `organization_id`
+    pub async fn resolve(
+        &self,
+        pool: &PgPool,
+        user_id: Uuid,
+        role: &str,
+        organization_id: Option<Uuid>,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let mut permissions = self.role_permissions(pool, role).await?.to_vec();
+        permissions.extend(User::group_permissions(pool, user_id, organization_id).await?);
+        permissions.sort();
+        permissions.dedup();
+
+        Ok(permissions)
+    }
+
+    /// Permissions granted to `role`, from the cache while they are fresh
+    pub async fn role_permissions(
+        &self,
+        pool: &PgPool,
+        role: &str,
+    ) -> Result<Arc<Vec<String>>, sqlx::Error> {
+        if let Some(entry) = self.read().get(role)
+            && entry.loaded_at.elapsed() < self.ttl
+        {
+            return Ok(entry.permissions.clone());
+        }
+
+        let generation = self.generation.load(Ordering::Acquire);
+        let permissions = Arc::new(User::role_permissions(pool, role).await?);
+
+        if !self.ttl.is_zero() {
+            let mut roles = self.write();
+            if self.generation.load(Ordering::Acquire) == generation {
+                roles.insert(
+                    role.to_string(),
+                    Entry {
+                        permissions: permissions.clone(),
+                        loaded_at: Instant::now(),
+                    },
+                );
+            }
+        }
+
+        Ok(permissions)
+    }
+
+    /// Forget the grants of `role`
+    pub fn invalidate(&self, role: &str) {
+        let mut roles = self.write();
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        roles.remove(role);
+    }
+
+    /// Forget the grants of every role
+    pub fn invalidate_all(&self) {
+        let mut roles = self.write();
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        roles.clear();
+    }
+
+    /// Invalidate roles as their grants change, on this and every other
+    /// replica. Everything is invalidated whenever the listening connection
+    /// is (re)established, since notifications sent meanwhile are lost.
+    pub fn listen(self: Arc<Self>, pool: PgPool) {
+        if self.ttl.is_zero() {
+            return;
+        }
+
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = self.listen_once(&pool).await {
+                    error!(error = %e, "Listening for permission changes failed");
+                }
+                self.invalidate_all();
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        });
+    }
+
+    async fn listen_once(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        let mut listener = PgListener::connect_with(pool).await?;
+        listener.listen(CHANGED_CHANNEL).await?;
+        self.invalidate_all();
+        info!(
+            channel = CHANGED_CHANNEL,
+            "Listening for permission changes"
+        );
+
+        loop {
+            // `None` means the connection dropped; the listener reconnects on
+            // the next call, but anything sent in between was missed
+            match listener.try_recv().await? {
+                Some(notification) => match notification.payload() {
+                    "" => {
+                        debug!("Permissions of every role changed");
+                        self.invalidate_all();
+                    }
+                    role => {
+                        debug!(role, "Role permissions changed");
+                        self.invalidate(role);
+                    }
+                },
+                None => {
+                    warn!("Lost the permission change listener connection, reconnecting");
+                    self.invalidate_all();
+                }
+            }
+        }
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, Entry>> {
+        self.roles.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, Entry>> {
+        self.roles.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,524 @@
+//! Field-level encryption of personal data in the `users` table.
+//!
+//! Emails and last login addresses are sealed with envelope encryption: every
+//! value is encrypted with its own data key, which is in turn encrypted
+//! ("wrapped") with a versioned key from `PII_ENCRYPTION_KEYS` or
+//! `PII_KEY_FILE`. Sealed values are stored as
+//! `enc:<version>:<wrapped data key>:<ciphertext>`, so values sealed under
+//! different versions live side by side and rotating keys only rewraps data
+//! keys. Values without the prefix are plaintext from before encryption was
+//! enabled. The [`Reencryptor`] seals those and rewraps values under other
+//! versions in the background.
+//!
+//! Encrypted emails cannot be compared in SQL, so each row also stores a keyed
+//! blind index of its email that lookups and the uniqueness constraint use.
+//!
+//! Only `users` is sealed. Client addresses on sessions, audit entries, login
+//! failures and terms acceptances, invitation and magic link emails, webhook
+//! payloads and stored idempotent responses stay in plaintext; the README
+//! lists them and how erasure handles each.
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::config::Config;
+use crate::db::pii::StoredPii;
+use crate::identity;
+
+/// Columns sealed values are bound to, so one cannot be copied into another
+pub const EMAIL: &str = "users.email";
+pub const LAST_LOGIN_IP: &str = "users.last_login_ip";
+
+const PREFIX: &str = "enc:";
+const NONCE_LEN: usize = 12;
+
+static KEYRING: OnceLock<Keyring> = OnceLock::new();
+
+#[derive(Debug)]
+pub struct PiiError(String);
+
+impl fmt::Display for PiiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for PiiError {}
+
+/// Versioned key-encryption keys and the blind index key
+pub struct Keyring {
+    keys: BTreeMap<u32, Aes256Gcm>,
+    active: u32,
+    index_key: Vec<u8>,
+}
+
+impl Keyring {
+    /// The configured keys, or `None` when personal data is stored in plaintext
+    pub fn from_config(config: &Config) -> Result<Option<Self>, String> {
+        let mut entries = Vec::new();
+        if let Some(path) = &config.pii_key_file {
+            let contents = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read PII_KEY_FILE {}: {}", path, e))?;
+            entries.extend(contents.lines().map(str::to_string));
+        }
+        if let Some(keys) = &config.pii_encryption_keys {
+            entries.extend(keys.split(',').map(str::to_string));
+        }
+
+        let mut keys = BTreeMap::new();
+        let mut index_key = config
+            .pii_index_key
+            .as_deref()
+            .map(|key| decode_key(key, "PII_INDEX_KEY"))
+            .transpose()?;
+        for entry in entries.iter().map(|e| e.trim()) {
+            if entry.is_empty() || entry.starts_with('#') {
+                continue;
+            }
+            let (label, key) = entry.split_once(':').ok_or_else(|| {
+                format!("PII key entries look like <version>:<key>, got '{}'", entry)
+            })?;
+            if label == "index" {
+                index_key.get_or_insert(decode_key(key, "index")?);
+                continue;
+            }
+            let version: u32 = label
+                .parse()
+                .map_err(|_| format!("Invalid PII key version '{}'", label))?;
+            let cipher = Aes256Gcm::new_from_slice(&decode_key(key, label)?)
+                .map_err(|_| format!("PII key {} must be 32 bytes", version))?;
+            if keys.insert(version, cipher).is_some() {
+                return Err(format!("PII key {} is configured twice", version));
+            }
+        }
+
+        let Some(&highest) = keys.keys().next_back() else {
+            return match index_key {
+                Some(_) => Err("PII_INDEX_KEY is set but no encryption keys are".to_string()),
+                None => Ok(None),
+            };
+        };
+        let active = config.pii_active_key_version.unwrap_or(highest);
+        if !keys.contains_key(&active) {
+            return Err(format!(
+                "PII_ACTIVE_KEY_VERSION {} is not configured",
+                active
+            ));
+        }
+        let index_key = index_key.ok_or("Encrypting personal data requires PII_INDEX_KEY")?;
+
+        Ok(Some(Self {
+            keys,
+            active,
+            index_key,
+        }))
+    }
+
+    fn seal(&self, column: &str, plaintext: &str) -> String {
+        let data_key = Aes256Gcm::generate_key(&mut OsRng);
+        let wrapped = encrypt(&self.keys[&self.active], column, &data_key);
+        let sealed = encrypt(&Aes256Gcm::new(&data_key), column, plaintext.as_bytes());
+
+        format!(
+            "{}{}:{}:{}",
+            PREFIX,
+            self.active,
+            STANDARD_NO_PAD.encode(wrapped),
+            STANDARD_NO_PAD.encode(sealed)
+        )
+    }
+
+    fn open(&self, column: &str, envelope: Envelope) -> Result<String, PiiError> {
+        let data_key = Aes256Gcm::new_from_slice(&self.unwrap_data_key(column, &envelope)?)
+            .map_err(|_| PiiError(format!("{} has a malformed data key", column)))?;
+        let plaintext = decrypt(&data_key, column, &envelope.sealed)?;
+
+        String::from_utf8(plaintext).map_err(|_| PiiError(format!("{} is not text", column)))
+    }
+
+    /// `stored` sealed under the active key, or `None` if it already is
+    fn reseal(&self, column: &str, stored: &str) -> Result<Option<String>, PiiError> {
+        let Some(envelope) = Envelope::parse(stored)? else {
+            return Ok(Some(self.seal(column, stored)));
+        };
+        if envelope.version == self.active {
+            return Ok(None);
+        }
+
+        // Only the data key is re-encrypted; the value itself stays as it is
+        let data_key = self.unwrap_data_key(column, &envelope)?;
+        let wrapped = encrypt(&self.keys[&self.active], column, &data_key);
+
+        Ok(Some(format!(
+            "{}{}:{}:{}",
+            PREFIX,
+            self.active,
+            STANDARD_NO_PAD.encode(wrapped),
+            STANDARD_NO_PAD.encode(&envelope.sealed)
+        )))
+    }
+
+    fn unwrap_data_key(&self, column: &str, envelope: &Envelope) -> Result<Vec<u8>, PiiError> {
+        let key = self.keys.get(&envelope.version).ok_or_else(|| {
+            PiiError(format!(
+                "{} is encrypted with PII key {}, which is not configured",
+                column, envelope.version
+            ))
+        })?;
+        decrypt(key, column, &envelope.wrapped)
+    }
+
+    fn blind_index(&self, email: &str) -> String {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.index_key)
+            .expect("HMAC accepts keys of any length");
+        mac.update(identity::canonical_email(email).as_bytes());
+        format!("{:x}", mac.finalize().into_bytes())
+    }
+}
+
+/// A sealed value split into its parts
+struct Envelope {
+    version: u32,
+    wrapped: Vec<u8>,
+    sealed: Vec<u8>,
+}
+
+impl Envelope {
+    /// `None` for plaintext
+    fn parse(stored: &str) -> Result<Option<Self>, PiiError> {
+        let Some(rest) = stored.strip_prefix(PREFIX) else {
+            return Ok(None);
+        };
+        let malformed = || PiiError("Malformed encrypted value".to_string());
+
+        let mut parts = rest.splitn(3, ':');
+        let (Some(version), Some(wrapped), Some(sealed)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(malformed());
+        };
+
+        Ok(Some(Self {
+            version: version.parse().map_err(|_| malformed())?,
+            wrapped: STANDARD_NO_PAD.decode(wrapped).map_err(|_| malformed())?,
+            sealed: STANDARD_NO_PAD.decode(sealed).map_err(|_| malformed())?,
+        }))
+    }
+}
+
+/// A random nonce followed by the ciphertext of `plaintext`
+fn encrypt(cipher: &Aes256Gcm, column: &str, plaintext: &[u8]) -> Vec<u8> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad: column.as_bytes(),
+            },
+        )
+        .expect("AES-GCM encrypts values of any practical size");
+
+    [nonce.as_slice(), &ciphertext].concat()
+}
+
+fn decrypt(cipher: &Aes256Gcm, column: &str, sealed: &[u8]) -> Result<Vec<u8>, PiiError> {
+    if sealed.len() < NONCE_LEN {
+        return Err(PiiError("Malformed encrypted value".to_string()));
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+
+    cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: column.as_bytes(),
+            },
+        )
+        .map_err(|_| PiiError(format!("Failed to decrypt {}", column)))
+}
+
+fn decode_key(key: &str, label: &str) -> Result<Vec<u8>, String> {
+    let key = base64::engine::general_purpose::STANDARD
+        .decode(key.trim())
+        .map_err(|_| format!("PII key {} is not valid base64", label))?;
+    if key.len() < 32 {
+        return Err(format!("PII key {} must be at least 32 bytes", label));
+    }
+
+    Ok(key)
+}
+
+/// Use `keyring` for the rest of the process. Installed once at startup, so
+/// `db::queries::User` can seal and open fields without every query taking it.
+pub fn install(keyring: Keyring) {
+    if KEYRING.set(keyring).is_err() {
+        warn!("PII keyring already installed; keeping the first one");
+    }
+}
+
+pub fn enabled() -> bool {
+    KEYRING.get().is_some()
+}
+
+/// The value to store for `plaintext` in `column`; plaintext itself when
+/// encryption is disabled
+pub fn seal(column: &str, plaintext: &str) -> String {
+    match KEYRING.get() {
+        Some(keyring) => keyring.seal(column, plaintext),
+        None => plaintext.to_string(),
+    }
+}
+
+/// Plaintext of a value read from `column`
+pub fn open(column: &str, stored: &str) -> Result<String, PiiError> {
+    let Some(envelope) = Envelope::parse(stored)? else {
+        return Ok(stored.to_string());
+    };
+
+    match KEYRING.get() {
+        Some(keyring) => keyring.open(column, envelope),
+        None => Err(PiiError(format!(
+            "{} is encrypted but no PII keys are configured",
+            column
+        ))),
+    }
+}
+
+/// Blind index of an email address, or `None` when encryption is disabled.
+/// Addresses that only differ in case or Unicode form share an index.
+pub fn email_index(email: &str) -> Option<String> {
+    KEYRING.get().map(|keyring| keyring.blind_index(email))
+}
+
+/// Outcome of a re-encryption pass
+#[derive(Debug, Default, Serialize)]
+pub struct ReencryptReport {
+    /// Rows sealed or rewrapped under the active key
+    pub resealed: u64,
+    /// Rows left as they were, because their email's blind index is already
+    /// taken by another account or they could not be decrypted
+    pub skipped: u64,
+}
+
+/// Background job moving personal data to the active key
+pub struct Reencryptor {
+    pool: PgPool,
+    interval: Duration,
+    batch_size: i64,
+}
+
+impl Reencryptor {
+    pub fn new(pool: PgPool, config: &Config) -> Self {
+        Self {
+            pool,
+            interval: Duration::from_secs(config.pii_reencrypt_interval_seconds.max(1)),
+            batch_size: config.pii_reencrypt_batch_size.max(1),
+        }
+    }
+
+    /// Run a pass every interval, starting now. Does nothing when encryption is disabled.
+    pub fn spawn(self) {
+        if !enabled() {
+            return;
+        }
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            loop {
+                interval.tick().await;
+                match self.run_once().await {
+                    Ok(report) if report.resealed > 0 || report.skipped > 0 => info!(
+                        resealed = report.resealed,
+                        skipped = report.skipped,
+                        "Re-encrypted personal data"
+                    ),
+                    Ok(_) => {}
+                    Err(e) => error!(error = %e, "Re-encrypting personal data failed"),
+                }
+            }
+        });
+    }
+
+    /// Seal every row not yet under the active key
+    pub async fn run_once(&self) -> Result<ReencryptReport, sqlx::Error> {
+        let mut report = ReencryptReport::default();
+        let Some(keyring) = KEYRING.get() else {
+            return Ok(report);
+        };
+        let current = format!("{}{}:", PREFIX, keyring.active);
+
+        let mut after = None;
+        loop {
+            let rows = StoredPii::stale_page(&self.pool, &current, after, self.batch_size).await?;
+            let Some(last) = rows.last() else {
+                return Ok(report);
+            };
+            after = Some(last.id);
+
+            for row in &rows {
+                match self.reseal(keyring, row).await {
+                    Ok(true) => report.resealed += 1,
+                    // Changed since it was read; the next pass picks it up again
+                    Ok(false) => {}
+                    Err(reason) => {
+                        warn!(user_id = %row.id, reason, "Left personal data as it was");
+                        report.skipped += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn reseal(&self, keyring: &Keyring, row: &StoredPii) -> Result<bool, String> {
+        let email = keyring
+            .reseal(EMAIL, &row.email)
+            .map_err(|e| e.to_string())?
+            .unwrap_or_else(|| row.email.clone());
+        let email_index = keyring.blind_index(&open(EMAIL, &row.email).map_err(|e| e.to_string())?);
+        let last_login_ip = match &row.last_login_ip {
+            Some(ip) => Some(
+                keyring
+                    .reseal(LAST_LOGIN_IP, ip)
+                    .map_err(|e| e.to_string())?
+                    .unwrap_or_else(|| ip.clone()),
+            ),
+            None => None,
+        };
+
+        row.replace(&self.pool, &email, &email_index, last_login_ip.as_deref())
+            .await
+            .map_err(|e| match e {
+                sqlx::Error::Database(db) if db.is_unique_violation() => {
+                    "email is already used by another account".to_string()
+                }
+                e => e.to_string(),
+            })
+    }
+}
+
+/// Lets sealed fields be decoded inside `sqlx::FromRow` implementations
+pub fn decode_error(column: &str, error: PiiError) -> sqlx::Error {
+    sqlx::Error::ColumnDecode {
+        index: column.to_string(),
+        source: Box::new(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyring(keys: &[(u32, u8)], active: u32) -> Keyring {
+        Keyring {
+            keys: keys
+                .iter()
+                .map(|&(version, byte)| (version, Aes256Gcm::new_from_slice(&[byte; 32]).unwrap()))
+                .collect(),
+            active,
+            index_key: vec![7; 32],
+        }
+    }
+
+    fn open_with(keyring: &Keyring, column: &str, stored: &str) -> Result<String, PiiError> {
+        keyring.open(column, Envelope::parse(stored)?.expect("value is sealed"))
+    }
+
+    #[test]
+    fn sealed_values_open_to_the_plaintext() {
+        let keyring = keyring(&[(1, 1)], 1);
+
+        let sealed = keyring.seal(EMAIL, "alice@example.com");
+        assert!(sealed.starts_with("enc:1:"));
+        assert!(!sealed.contains("alice"));
+        assert_eq!(
+            open_with(&keyring, EMAIL, &sealed).unwrap(),
+            "alice@example.com"
+        );
+
+        // Every value gets its own data key and nonce
+        assert_ne!(keyring.seal(EMAIL, "alice@example.com"), sealed);
+    }
+
+    #[test]
+    fn plaintext_is_not_an_envelope() {
+        assert!(Envelope::parse("alice@example.com").unwrap().is_none());
+        assert!(Envelope::parse("enc:1:not base64!").is_err());
+        assert!(Envelope::parse("enc:x:AAAA:AAAA").is_err());
+    }
+
+    #[test]
+    fn rotation_rewraps_the_data_key_only() {
+        let old = keyring(&[(1, 1)], 1);
+        let sealed = old.seal(EMAIL, "alice@example.com");
+
+        let rotated = keyring(&[(1, 1), (2, 2)], 2);
+        let resealed = rotated.reseal(EMAIL, &sealed).unwrap().unwrap();
+        assert!(resealed.starts_with("enc:2:"));
+        assert_eq!(
+            resealed.rsplit(':').next(),
+            sealed.rsplit(':').next(),
+            "the value's ciphertext is kept"
+        );
+        assert_eq!(
+            open_with(&rotated, EMAIL, &resealed).unwrap(),
+            "alice@example.com"
+        );
+
+        // Already under the active key, or sealed from plaintext
+        assert!(rotated.reseal(EMAIL, &resealed).unwrap().is_none());
+        let from_plaintext = rotated.reseal(EMAIL, "bob@example.com").unwrap().unwrap();
+        assert_eq!(
+            open_with(&rotated, EMAIL, &from_plaintext).unwrap(),
+            "bob@example.com"
+        );
+
+        // Once version 1 is retired only the rewrapped value opens
+        let retired = keyring(&[(2, 2)], 2);
+        assert!(open_with(&retired, EMAIL, &sealed).is_err());
+        assert!(open_with(&retired, EMAIL, &resealed).is_ok());
+    }
+
+    #[test]
+    fn wrong_key_or_column_fails_to_open() {
+        let sealed = keyring(&[(1, 1)], 1).seal(EMAIL, "alice@example.com");
+
+        // Same version, different key material
+        assert!(open_with(&keyring(&[(1, 9)], 1), EMAIL, &sealed).is_err());
+        // Sealed values are bound to their column
+        assert!(open_with(&keyring(&[(1, 1)], 1), LAST_LOGIN_IP, &sealed).is_err());
+
+        let mut tampered = sealed.clone();
+        tampered.pop();
+        tampered.push(if sealed.ends_with('A') { 'B' } else { 'A' });
+        assert!(open_with(&keyring(&[(1, 1)], 1), EMAIL, &tampered).is_err());
+    }
+
+    #[test]
+    fn blind_index_is_deterministic_and_case_folded() {
+        let keyring = keyring(&[(1, 1)], 1);
+
+        let index = keyring.blind_index("alice@example.com");
+        assert_eq!(keyring.blind_index("alice@example.com"), index);
+        assert_eq!(keyring.blind_index(" Alice@EXAMPLE.com "), index);
+        assert_ne!(keyring.blind_index("bob@example.com"), index);
+
+        let other = Keyring {
+            index_key: vec![8; 32],
+            ..keyring
+        };
+        assert_ne!(other.blind_index("alice@example.com"), index);
+    }
+}
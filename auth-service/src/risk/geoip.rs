@@ -0,0 +1,65 @@
+use maxminddb::{Reader, geoip2};
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Where a GeoIP database places an address
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub city: Option<String>,
+    pub country: Option<String>,
+}
+
+impl Location {
+    /// Great-circle distance in kilometres
+    pub fn distance_km(&self, other: &Location) -> f64 {
+        const EARTH_RADIUS_KM: f64 = 6371.0;
+
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.longitude - self.longitude).to_radians();
+
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.city, &self.country) {
+            (Some(city), Some(country)) => write!(f, "{}, {}", city, country),
+            (None, Some(country)) => write!(f, "{}", country),
+            _ => write!(f, "{:.2}, {:.2}", self.latitude, self.longitude),
+        }
+    }
+}
+
+/// A MaxMind GeoIP2 or GeoLite2 City database loaded from disk
+pub struct GeoIp {
+    reader: Reader<Vec<u8>>,
+}
+
+impl GeoIp {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, maxminddb::MaxMindDBError> {
+        Ok(Self {
+            reader: Reader::open_readfile(path)?,
+        })
+    }
+
+    /// Location of `ip`, if the database knows its coordinates
+    pub fn locate(&self, ip: &str) -> Option<Location> {
+        let ip: IpAddr = ip.parse().ok()?;
+        let record = self.reader.lookup::<geoip2::City>(ip).ok()?;
+        let location = record.location?;
+
+        Some(Location {
+            latitude: location.latitude?,
+            longitude: location.longitude?,
+            city: record
+                .city
+                .and_then(|c| c.names)
+                .and_then(|names| names.get("en").map(|n| n.to_string())),
+            country: record.country.and_then(|c| c.iso_code).map(String::from),
+        })
+    }
+}
@@ -0,0 +1,357 @@
+//! Risk assessment of successful logins.
+//!
+//! A login is compared with the user's recent sessions and with the failed
+//! attempts before it. Suspicious logins are audited and reported to the user;
+//! signals listed in `RISK_STEP_UP` also hold the login until the user enters
+//! a code emailed to them.
+
+mod geoip;
+
+use common::errors::AppError;
+use sqlx::PgPool;
+use std::time::Duration;
+
+use crate::client_info::ClientInfo;
+use crate::config::Config;
+use crate::db::login_failures::LoginFailure;
+use crate::db::queries::User;
+use crate::db::sessions::Session;
+
+pub use geoip::{GeoIp, Location};
+
+/// GeoIP locations are approximate, so shorter hops never count as travel
+const MIN_TRAVEL_KM: f64 = 100.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Signal {
+    /// No recent login came from this IP address
+    NewIp,
+    /// No recent login used this user agent
+    NewUserAgent,
+    /// The previous login was too far away to have travelled here since
+    ImpossibleTravel,
+    /// Many failed attempts came before this success
+    RepeatedFailures,
+}
+
+impl Signal {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::NewIp => "new_ip",
+            Self::NewUserAgent => "new_user_agent",
+            Self::ImpossibleTravel => "impossible_travel",
+            Self::RepeatedFailures => "repeated_failures",
+        }
+    }
+
+    /// The signal explained to the account's owner
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::NewIp => "It came from a network you have not signed in from recently.",
+            Self::NewUserAgent => {
+                "It used a browser or device you have not signed in with recently."
+            }
+            Self::ImpossibleTravel => {
+                "It came from too far away from your previous sign-in to have travelled there since."
+            }
+            Self::RepeatedFailures => "Several wrong passwords were tried before it succeeded.",
+        }
+    }
+}
+
+pub struct Assessment {
+    pub signals: Vec<Signal>,
+    /// Where the login came from, when the GeoIP database knows
+    pub location: Option<Location>,
+}
+
+impl Assessment {
+    pub fn is_suspicious(&self) -> bool {
+        !self.signals.is_empty()
+    }
+
+    pub fn signal_names(&self) -> Vec<&'static str> {
+        self.signals.iter().map(|s| s.as_str()).collect()
+    }
+}
+
+pub struct RiskPolicy {
+    geoip: Option<GeoIp>,
+    history_size: i64,
+    max_travel_kmh: f64,
+    failed_attempts: i64,
+    failure_window: Duration,
+    step_up: Vec<String>,
+}
+
+impl RiskPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        let geoip = config.geoip_database.as_ref().map(|path| {
+            GeoIp::open(path)
+                .unwrap_or_else(|e| panic!("Failed to open GEOIP_DATABASE '{}': {}", path, e))
+        });
+
+        Self {
+            geoip,
+            history_size: config.risk_history_size,
+            max_travel_kmh: config.risk_max_travel_kmh,
+            failed_attempts: config.risk_failed_attempts,
+            failure_window: Duration::from_secs(config.risk_failure_window_seconds),
+            step_up: config.risk_step_up.clone(),
+        }
+    }
+
+    fn locate(&self, ip: &str) -> Option<Location> {
+        self.geoip.as_ref()?.locate(ip)
+    }
+
+    /// Compare a successful login by `user` with their recent history. Must run
+    /// before the login's own session is opened. The client's address is the
+    /// one [`ClientInfo`] verified, so a forged `X-Forwarded-For` cannot pose
+    /// as a network the user signed in from before.
+    pub async fn assess(
+        &self,
+        pool: &PgPool,
+        user: &User,
+        client: &ClientInfo,
+    ) -> Result<Assessment, AppError> {
+        let history = Session::recent_for_user(pool, user.id, self.history_size)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to list sessions: {}", e)))?;
+
+        // Failures only count since the last successful login
+        let window_start = chrono::Utc::now() - self.failure_window;
+        let since = user
+            .last_login_at
+            .map_or(window_start, |t| t.max(window_start));
+        let failures = LoginFailure::count_since(pool, &user.username, since)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to count login failures: {}", e)))?;
+
+        Ok(self.evaluate(&history, client, failures, |ip| self.locate(ip)))
+    }
+
+    /// The signals a login raises against `history`, newest session first,
+    /// after `failures` failed attempts
+    fn evaluate(
+        &self,
+        history: &[Session],
+        client: &ClientInfo,
+        failures: i64,
+        locate: impl Fn(&str) -> Option<Location>,
+    ) -> Assessment {
+        let location = client.ip_address.as_deref().and_then(&locate);
+
+        let mut signals = Vec::new();
+
+        // A first login has nothing to be compared with
+        if let Some(previous) = history.first() {
+            if let Some(ip) = client.ip_address.as_deref()
+                && !history.iter().any(|s| s.ip_address.as_deref() == Some(ip))
+            {
+                signals.push(Signal::NewIp);
+            }
+            if let Some(agent) = client.user_agent.as_deref()
+                && !history
+                    .iter()
+                    .any(|s| s.user_agent.as_deref() == Some(agent))
+            {
+                signals.push(Signal::NewUserAgent);
+            }
+            if let Some(here) = &location
+                && let Some(there) = previous.ip_address.as_deref().and_then(&locate)
+                && self.is_impossible_travel(here, &there, previous.created_at)
+            {
+                signals.push(Signal::ImpossibleTravel);
+            }
+        }
+
+        if failures >= self.failed_attempts {
+            signals.push(Signal::RepeatedFailures);
+        }
+
+        Assessment { signals, location }
+    }
+
+    fn is_impossible_travel(
+        &self,
+        here: &Location,
+        there: &Location,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> bool {
+        let distance = here.distance_km(there);
+        if distance < MIN_TRAVEL_KM {
+            return false;
+        }
+
+        let elapsed = (chrono::Utc::now() - since).num_seconds().max(1);
+        distance / (elapsed as f64 / 3600.0) > self.max_travel_kmh
+    }
+
+    /// Whether the login must be confirmed with an emailed code
+    pub fn requires_step_up(&self, assessment: &Assessment) -> bool {
+        assessment
+            .signals
+            .iter()
+            .any(|s| self.step_up.iter().any(|name| name == s.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderMap;
+    use common::client_ip::client_ip;
+    use ipnet::IpNet;
+    use uuid::Uuid;
+
+    const HOME: &str = "198.51.100.7";
+    const ATTACKER: &str = "203.0.113.50";
+    const PROXY: &str = "10.0.0.1";
+    const BROWSER: &str = "Mozilla/5.0 (X11; Linux x86_64)";
+
+    fn policy(step_up: &[&str]) -> RiskPolicy {
+        RiskPolicy {
+            geoip: None,
+            history_size: 20,
+            max_travel_kmh: 1000.0,
+            failed_attempts: 5,
+            failure_window: Duration::from_secs(3600),
+            step_up: step_up.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn session(ip: &str, hours_ago: i64) -> Session {
+        let created_at = chrono::Utc::now() - chrono::Duration::hours(hours_ago);
+        Session {
+            id: Uuid::new_v4(),
+            user_id: Uuid::nil(),
+            organization_id: None,
+            user_agent: Some(BROWSER.to_string()),
+            ip_address: Some(ip.to_string()),
+            created_at,
+            last_seen_at: created_at,
+            expires_at: created_at + chrono::Duration::days(30),
+            impersonator_id: None,
+        }
+    }
+
+    /// HOME is in London, ATTACKER in Sydney
+    fn locate(ip: &str) -> Option<Location> {
+        let (latitude, longitude) = match ip {
+            HOME => (51.5, -0.1),
+            ATTACKER => (-33.9, 151.2),
+            _ => return None,
+        };
+        Some(Location {
+            latitude,
+            longitude,
+            city: None,
+            country: None,
+        })
+    }
+
+    /// The client as [`ClientInfo`] sees a request from `peer` with `forwarded_for`
+    fn client(peer: &str, forwarded_for: Option<&str>, trusted: &[IpNet]) -> ClientInfo {
+        let mut headers = HeaderMap::new();
+        if let Some(value) = forwarded_for {
+            headers.insert("X-Forwarded-For", value.parse().unwrap());
+        }
+        ClientInfo {
+            ip_address: client_ip(peer.parse().ok(), &headers, trusted).map(|ip| ip.to_string()),
+            user_agent: Some(BROWSER.to_string()),
+        }
+    }
+
+    fn signals(client: &ClientInfo, history: &[Session], failures: i64) -> Vec<&'static str> {
+        policy(&[])
+            .evaluate(history, client, failures, locate)
+            .signal_names()
+    }
+
+    #[test]
+    fn familiar_login_raises_nothing() {
+        let history = [session(HOME, 24)];
+        assert!(signals(&client(HOME, None, &[]), &history, 0).is_empty());
+    }
+
+    #[test]
+    fn first_login_has_nothing_to_compare() {
+        assert!(signals(&client(ATTACKER, None, &[]), &[], 0).is_empty());
+    }
+
+    #[test]
+    fn new_network_and_device_are_flagged() {
+        let history = [session(HOME, 24)];
+        let mut other = client("192.0.2.99", None, &[]);
+        other.user_agent = Some("curl/8.0".to_string());
+
+        assert_eq!(signals(&other, &history, 0), ["new_ip", "new_user_agent"]);
+    }
+
+    #[test]
+    fn impossible_travel_is_flagged() {
+        let history = [session(HOME, 1)];
+        assert_eq!(
+            signals(&client(ATTACKER, None, &[]), &history, 0),
+            ["new_ip", "impossible_travel"]
+        );
+
+        // A day is long enough to fly there
+        let history = [session(HOME, 24)];
+        assert_eq!(
+            signals(&client(ATTACKER, None, &[]), &history, 0),
+            ["new_ip"]
+        );
+    }
+
+    #[test]
+    fn forged_forwarded_for_from_an_untrusted_peer_changes_nothing() {
+        let history = [session(HOME, 1)];
+        let honest = signals(&client(ATTACKER, None, &[]), &history, 0);
+        let forged = signals(&client(ATTACKER, Some(HOME), &[]), &history, 0);
+
+        assert_eq!(forged, honest);
+        assert_eq!(forged, ["new_ip", "impossible_travel"]);
+    }
+
+    #[test]
+    fn forged_hop_before_a_trusted_proxy_changes_nothing() {
+        let trusted: Vec<IpNet> = vec!["10.0.0.0/8".parse().unwrap()];
+        let history = [session(HOME, 1)];
+
+        // The proxy appends the address it saw after the client's forged one
+        let forwarded = format!("{}, {}", HOME, ATTACKER);
+        let forged = signals(&client(PROXY, Some(&forwarded), &trusted), &history, 0);
+        let honest = signals(&client(PROXY, Some(ATTACKER), &trusted), &history, 0);
+
+        assert_eq!(forged, honest);
+        assert_eq!(forged, ["new_ip", "impossible_travel"]);
+
+        // The real client behind the proxy is still recognised
+        let home = signals(&client(PROXY, Some(HOME), &trusted), &history, 0);
+        assert!(home.is_empty());
+    }
+
+    #[test]
+    fn repeated_failures_are_flagged_at_the_threshold() {
+        let history = [session(HOME, 24)];
+        let home = client(HOME, None, &[]);
+
+        assert!(signals(&home, &history, 4).is_empty());
+        assert_eq!(signals(&home, &history, 5), ["repeated_failures"]);
+        // Even on a first login
+        assert_eq!(signals(&home, &[], 5), ["repeated_failures"]);
+    }
+
+    #[test]
+    fn step_up_only_for_listed_signals() {
+        let history = [session(HOME, 1)];
+        let attacker = client(ATTACKER, None, &[]);
+        let travel = policy(&[]).evaluate(&history, &attacker, 0, locate);
+
+        assert!(!policy(&[]).requires_step_up(&travel));
+        assert!(!policy(&["repeated_failures"]).requires_step_up(&travel));
+        assert!(policy(&["impossible_travel"]).requires_step_up(&travel));
+    }
+}
@@ -0,0 +1,487 @@
+//! SCIM filter expressions (RFC 7644 section 3.4.2.2), evaluated against the
+//! JSON representation of a resource.
+
+use serde_json::Value;
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Co,
+    Sw,
+    Ew,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+#[derive(Debug)]
+pub enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Present(String),
+    Compare(String, CompareOp, Value),
+    /// `emails[type eq "work"]`: some element of a multi-valued attribute matches
+    ValuePath(String, Box<Filter>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Literal(Value),
+    OpenParen,
+    CloseParen,
+    OpenBracket,
+    CloseBracket,
+}
+
+impl Filter {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let filter = parser.or()?;
+
+        match parser.peek() {
+            None => Ok(filter),
+            Some(token) => Err(format!("Unexpected {:?} in filter", token)),
+        }
+    }
+
+    pub fn matches(&self, resource: &Value) -> bool {
+        match self {
+            Filter::And(a, b) => a.matches(resource) && b.matches(resource),
+            Filter::Or(a, b) => a.matches(resource) || b.matches(resource),
+            Filter::Not(f) => !f.matches(resource),
+            Filter::Present(path) => resolve(resource, path).into_iter().any(is_present),
+            Filter::Compare(path, op, expected) => resolve(resource, path)
+                .into_iter()
+                .any(|actual| compare(actual, *op, expected)),
+            Filter::ValuePath(path, inner) => resolve(resource, path)
+                .into_iter()
+                .any(|v| inner.matches(v)),
+        }
+    }
+
+    /// The value of a top-level `attr eq "value"` comparison, if this filter is one.
+    /// Lets callers look a resource up directly instead of scanning.
+    pub fn equality(&self, attribute: &str) -> Option<&str> {
+        match self {
+            Filter::Compare(path, CompareOp::Eq, Value::String(value))
+                if attribute_name(path).eq_ignore_ascii_case(attribute) =>
+            {
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Strip a schema URN prefix, e.g. `urn:ietf:params:scim:schemas:core:2.0:User:userName`
+fn attribute_name(path: &str) -> &str {
+    path.rsplit(':').next().unwrap_or(path)
+}
+
+/// Every value at `path`, flattening multi-valued attributes along the way
+fn resolve<'a>(resource: &'a Value, path: &str) -> Vec<&'a Value> {
+    let mut current = vec![resource];
+
+    for segment in attribute_name(path).split('.') {
+        current = current
+            .into_iter()
+            .flat_map(|value| match value {
+                Value::Array(items) => items.iter().collect(),
+                other => vec![other],
+            })
+            .filter_map(|value| {
+                value.as_object().and_then(|object| {
+                    object
+                        .iter()
+                        .find(|(key, _)| key.eq_ignore_ascii_case(segment))
+                        .map(|(_, v)| v)
+                })
+            })
+            .collect();
+    }
+
+    current
+        .into_iter()
+        .flat_map(|value| match value {
+            Value::Array(items) => items.iter().collect(),
+            other => vec![other],
+        })
+        .collect()
+}
+
+fn is_present(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        _ => true,
+    }
+}
+
+fn compare(actual: &Value, op: CompareOp, expected: &Value) -> bool {
+    // String comparisons are case-insensitive: none of our attributes are caseExact
+    if let (Value::String(a), Value::String(b)) = (actual, expected) {
+        let (a, b) = (a.to_lowercase(), b.to_lowercase());
+        return match op {
+            CompareOp::Eq => a == b,
+            CompareOp::Ne => a != b,
+            CompareOp::Co => a.contains(&b),
+            CompareOp::Sw => a.starts_with(&b),
+            CompareOp::Ew => a.ends_with(&b),
+            CompareOp::Gt => a > b,
+            CompareOp::Ge => a >= b,
+            CompareOp::Lt => a < b,
+            CompareOp::Le => a <= b,
+        };
+    }
+
+    match op {
+        CompareOp::Eq => actual == expected,
+        CompareOp::Ne => actual != expected,
+        CompareOp::Gt | CompareOp::Ge | CompareOp::Lt | CompareOp::Le => {
+            let ordering = match (actual.as_f64(), expected.as_f64()) {
+                (Some(a), Some(b)) => a.partial_cmp(&b),
+                _ => None,
+            };
+            match ordering {
+                Some(Ordering::Greater) => matches!(op, CompareOp::Gt | CompareOp::Ge),
+                Some(Ordering::Equal) => matches!(op, CompareOp::Ge | CompareOp::Le),
+                Some(Ordering::Less) => matches!(op, CompareOp::Lt | CompareOp::Le),
+                None => false,
+            }
+        }
+        CompareOp::Co | CompareOp::Sw | CompareOp::Ew => false,
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | '[' | ']' => {
+                chars.next();
+                tokens.push(match c {
+                    '(' => Token::OpenParen,
+                    ')' => Token::CloseParen,
+                    '[' => Token::OpenBracket,
+                    _ => Token::CloseBracket,
+                });
+            }
+            '"' => {
+                chars.next();
+                let mut escaped = false;
+                let mut end = None;
+                for (i, c) in chars.by_ref() {
+                    match c {
+                        '\\' if !escaped => escaped = true,
+                        '"' if !escaped => {
+                            end = Some(i);
+                            break;
+                        }
+                        _ => escaped = false,
+                    }
+                }
+                let end = end.ok_or("Unterminated string in filter")?;
+                let literal: Value = serde_json::from_str(&input[start..=end])
+                    .map_err(|_| "Invalid string in filter".to_string())?;
+                tokens.push(Token::Literal(literal));
+            }
+            _ => {
+                let mut end = input.len();
+                while let Some(&(i, c)) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | '[' | ']' | '"') {
+                        end = i;
+                        break;
+                    }
+                    chars.next();
+                }
+                tokens.push(Token::Word(input[start..end].to_string()));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            other => Err(format!(
+                "Expected {:?} in filter, found {:?}",
+                expected, other
+            )),
+        }
+    }
+
+    fn keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn or(&mut self) -> Result<Filter, String> {
+        let mut filter = self.and()?;
+        while self.keyword("or") {
+            self.pos += 1;
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<Filter, String> {
+        let mut filter = self.unary()?;
+        while self.keyword("and") {
+            self.pos += 1;
+            filter = Filter::And(Box::new(filter), Box::new(self.unary()?));
+        }
+        Ok(filter)
+    }
+
+    fn unary(&mut self) -> Result<Filter, String> {
+        if self.keyword("not") {
+            self.pos += 1;
+            self.expect(Token::OpenParen)?;
+            let inner = self.or()?;
+            self.expect(Token::CloseParen)?;
+            return Ok(Filter::Not(Box::new(inner)));
+        }
+
+        match self.next() {
+            Some(Token::OpenParen) => {
+                let inner = self.or()?;
+                self.expect(Token::CloseParen)?;
+                Ok(inner)
+            }
+            Some(Token::Word(path)) => self.attribute(path),
+            other => Err(format!("Expected attribute in filter, found {:?}", other)),
+        }
+    }
+
+    fn attribute(&mut self, path: String) -> Result<Filter, String> {
+        if self.peek() == Some(&Token::OpenBracket) {
+            self.pos += 1;
+            let inner = self.or()?;
+            self.expect(Token::CloseBracket)?;
+            return Ok(Filter::ValuePath(path, Box::new(inner)));
+        }
+
+        let op = match self.next() {
+            Some(Token::Word(op)) => op.to_ascii_lowercase(),
+            other => {
+                return Err(format!(
+                    "Expected operator after '{}', found {:?}",
+                    path, other
+                ));
+            }
+        };
+        let op = match op.as_str() {
+            "pr" => return Ok(Filter::Present(path)),
+            "eq" => CompareOp::Eq,
+            "ne" => CompareOp::Ne,
+            "co" => CompareOp::Co,
+            "sw" => CompareOp::Sw,
+            "ew" => CompareOp::Ew,
+            "gt" => CompareOp::Gt,
+            "ge" => CompareOp::Ge,
+            "lt" => CompareOp::Lt,
+            "le" => CompareOp::Le,
+            other => return Err(format!("Unsupported filter operator '{}'", other)),
+        };
+
+        let value = match self.next() {
+            Some(Token::Literal(value)) => value,
+            Some(Token::Word(word)) => match word.as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                "null" => Value::Null,
+                number => serde_json::from_str::<serde_json::Number>(number)
+                    .map(Value::Number)
+                    .map_err(|_| format!("Invalid value '{}' in filter", number))?,
+            },
+            other => {
+                return Err(format!(
+                    "Expected value after '{}', found {:?}",
+                    path, other
+                ));
+            }
+        };
+
+        Ok(Filter::Compare(path, op, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn user() -> Value {
+        json!({
+            "userName": "Alice.Smith",
+            "active": true,
+            "displayName": "",
+            "meta": { "version": 3 },
+            "emails": [
+                { "value": "alice@work.example", "type": "work", "primary": true },
+                { "value": "alice@home.example", "type": "home" }
+            ]
+        })
+    }
+
+    fn matches(filter: &str) -> bool {
+        Filter::parse(filter)
+            .unwrap_or_else(|e| panic!("'{}' should parse: {}", filter, e))
+            .matches(&user())
+    }
+
+    #[test]
+    fn eq_ignores_case_of_values_and_attributes() {
+        assert!(matches(r#"userName eq "alice.smith""#));
+        assert!(matches(r#"USERNAME EQ "ALICE.SMITH""#));
+        assert!(!matches(r#"userName eq "alice""#));
+    }
+
+    #[test]
+    fn schema_urn_prefix_is_stripped() {
+        assert!(matches(
+            r#"urn:ietf:params:scim:schemas:core:2.0:User:userName eq "Alice.Smith""#
+        ));
+    }
+
+    #[test]
+    fn co_sw_and_ew_match_substrings() {
+        assert!(matches(r#"userName co "ce.sm""#));
+        assert!(matches(r#"userName sw "ali""#));
+        assert!(matches(r#"userName ew "SMITH""#));
+        assert!(!matches(r#"userName sw "smith""#));
+    }
+
+    #[test]
+    fn pr_requires_a_non_empty_value() {
+        assert!(matches("userName pr"));
+        assert!(matches("emails pr"));
+        assert!(!matches("displayName pr"));
+        assert!(!matches("nickName pr"));
+    }
+
+    #[test]
+    fn booleans_numbers_and_ordering() {
+        assert!(matches("active eq true"));
+        assert!(!matches("active eq false"));
+        assert!(matches("meta.version gt 2"));
+        assert!(matches("meta.version ge 3"));
+        assert!(!matches("meta.version lt 3"));
+        assert!(matches("meta.version le 3"));
+    }
+
+    #[test]
+    fn multi_valued_attributes_match_any_element() {
+        assert!(matches(r#"emails.value eq "alice@home.example""#));
+        assert!(matches(r#"emails[type eq "work" and primary eq true]"#));
+        assert!(!matches(r#"emails[type eq "home" and primary eq true]"#));
+    }
+
+    #[test]
+    fn and_or_and_not_combine() {
+        assert!(matches(r#"userName sw "a" and active eq true"#));
+        assert!(!matches(r#"userName sw "b" and active eq true"#));
+        assert!(matches(r#"userName sw "b" or active eq true"#));
+        assert!(matches(r#"not (userName sw "b")"#));
+        assert!(!matches(r#"not (active eq true)"#));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let filter = Filter::parse("a eq 1 or b eq 2 and c eq 3").unwrap();
+        match filter {
+            Filter::Or(left, right) => {
+                assert!(matches!(*left, Filter::Compare(ref p, CompareOp::Eq, _) if p == "a"));
+                assert!(matches!(*right, Filter::And(_, _)));
+            }
+            other => panic!("expected or at the top, got {:?}", other),
+        }
+
+        // Reads as `true or (false and false)` unless grouped otherwise
+        assert!(matches(
+            r#"active eq true or userName eq "x" and userName eq "y""#
+        ));
+        assert!(!matches(
+            r#"(active eq true or userName eq "x") and userName eq "y""#
+        ));
+    }
+
+    #[test]
+    fn quoted_strings_keep_spaces_brackets_and_escapes() {
+        let filter = Filter::parse(r#"displayName eq "A \"quoted\" (name) [x]""#).unwrap();
+        match filter {
+            Filter::Compare(_, CompareOp::Eq, Value::String(value)) => {
+                assert_eq!(value, r#"A "quoted" (name) [x]"#);
+            }
+            other => panic!("expected a comparison, got {:?}", other),
+        }
+
+        let filter = Filter::parse(r#"userName eq "back\\slash""#).unwrap();
+        assert_eq!(filter.equality("userName"), Some(r"back\slash"));
+    }
+
+    #[test]
+    fn equality_only_answers_top_level_eq() {
+        let filter = Filter::parse(r#"userName eq "bob""#).unwrap();
+        assert_eq!(filter.equality("username"), Some("bob"));
+        assert_eq!(filter.equality("externalId"), None);
+
+        let filter = Filter::parse(r#"userName eq "bob" or userName eq "eve""#).unwrap();
+        assert_eq!(filter.equality("userName"), None);
+        let filter = Filter::parse(r#"userName sw "bob""#).unwrap();
+        assert_eq!(filter.equality("userName"), None);
+    }
+
+    #[test]
+    fn malformed_filters_are_rejected() {
+        for filter in [
+            "",
+            "userName",
+            "userName eq",
+            r#"userName xx "a""#,
+            r#"userName eq "unterminated"#,
+            r#"userName eq "a" and"#,
+            r#"(userName eq "a""#,
+            r#"userName eq "a")"#,
+            r#"emails[type eq "work""#,
+            r#"not userName eq "a""#,
+            "userName eq bare",
+            r#"userName eq "a" userName eq "b""#,
+        ] {
+            assert!(
+                Filter::parse(filter).is_err(),
+                "'{}' should be rejected",
+                filter
+            );
+        }
+    }
+}
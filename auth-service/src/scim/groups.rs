@@ -0,0 +1,408 @@
+use axum::{
+    Extension,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::Response,
+};
+use common::errors::AppError;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tracing::info;
+use uuid::Uuid;
+
+use super::filter::Filter;
+use super::{
+    GROUP_SCHEMA, ListQuery, PatchOp, PatchRequest, ScimContext, ScimError, as_str, check_if_match,
+    list_response, not_modified, parse_body, resource_response, with_version,
+};
+use crate::db::groups::Group;
+use crate::db::organizations::Membership;
+use crate::db::queries::User;
+use crate::handlers::AppState;
+
+#[derive(Debug, Deserialize)]
+struct MemberRef {
+    value: String,
+}
+
+/// Group resource as sent by the provisioning client
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScimGroup {
+    display_name: String,
+    #[serde(default)]
+    members: Vec<MemberRef>,
+}
+
+/// The provisioned state of a group, which PUT and PATCH both resolve to
+struct Provisioned {
+    display_name: String,
+    members: Vec<Uuid>,
+}
+
+impl Provisioned {
+    fn apply(
+        &mut self,
+        op: PatchOp,
+        path: Option<&str>,
+        value: Option<&Value>,
+    ) -> Result<(), ScimError> {
+        let required = || {
+            value
+                .ok_or_else(|| ScimError::bad_request("invalidValue", "Operation requires a value"))
+        };
+
+        let Some(path) = path else {
+            let attributes = required()?
+                .as_object()
+                .ok_or_else(|| ScimError::bad_request("invalidValue", "Expected an object"))?;
+            for (attribute, value) in attributes {
+                self.apply(op, Some(&attribute.to_ascii_lowercase()), Some(value))?;
+            }
+            return Ok(());
+        };
+
+        // `members[value eq "..."]` selects members to remove
+        if let Some(selector) = path
+            .strip_prefix("members[")
+            .and_then(|p| p.strip_suffix(']'))
+        {
+            if op != PatchOp::Remove {
+                return Err(ScimError::bad_request(
+                    "invalidPath",
+                    "Member filters are only supported when removing members",
+                ));
+            }
+            let filter =
+                Filter::parse(selector).map_err(|e| ScimError::bad_request("invalidFilter", e))?;
+            self.members
+                .retain(|id| !filter.matches(&json!({ "value": id.to_string() })));
+            return Ok(());
+        }
+
+        match (path, op) {
+            ("displayname", PatchOp::Remove) => {
+                return Err(ScimError::bad_request(
+                    "mutability",
+                    "'displayName' cannot be removed",
+                ));
+            }
+            ("displayname", _) => self.display_name = as_str(required()?)?.to_string(),
+            ("members", PatchOp::Remove) => match value {
+                Some(value) => {
+                    let removed = member_ids(value)?;
+                    self.members.retain(|id| !removed.contains(id));
+                }
+                None => self.members.clear(),
+            },
+            ("members", PatchOp::Add) => {
+                for id in member_ids(required()?)? {
+                    if !self.members.contains(&id) {
+                        self.members.push(id);
+                    }
+                }
+            }
+            ("members", PatchOp::Replace) => self.members = member_ids(required()?)?,
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_member(value: &str) -> Result<Uuid, ScimError> {
+    Uuid::parse_str(value)
+        .map_err(|_| ScimError::bad_request("invalidValue", format!("Invalid member '{}'", value)))
+}
+
+fn member_ids(value: &Value) -> Result<Vec<Uuid>, ScimError> {
+    let members: Vec<MemberRef> = serde_json::from_value(value.clone())
+        .map_err(|e| ScimError::bad_request("invalidValue", e.to_string()))?;
+    members.iter().map(|m| parse_member(&m.value)).collect()
+}
+
+/// Group resource; `members` is omitted (along with the version) when not loaded
+fn to_resource(group: &Group, members: Option<&[User]>) -> Value {
+    let mut resource = json!({
+        "schemas": [GROUP_SCHEMA],
+        "id": group.id.to_string(),
+        "displayName": group.name,
+        "meta": {
+            "resourceType": "Group",
+            "created": group.created_at.to_rfc3339(),
+            "location": format!("/scim/v2/Groups/{}", group.id),
+        },
+    });
+
+    match members {
+        Some(members) => {
+            resource["members"] = members
+                .iter()
+                .map(|u| {
+                    json!({
+                        "value": u.id.to_string(),
+                        "display": u.username,
+                        "$ref": format!("/scim/v2/Users/{}", u.id),
+                    })
+                })
+                .collect();
+            with_version(resource)
+        }
+        None => resource,
+    }
+}
+
+async fn find_group(
+    state: &AppState,
+    ctx: ScimContext,
+    id: &str,
+) -> Result<(Group, Vec<User>), ScimError> {
+    let group_id = Uuid::parse_str(id).map_err(|_| ScimError::not_found("Group not found"))?;
+
+    let group = Group::find_by_id(&state.pool, group_id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get group: {}", e)))?
+        .filter(|g| g.organization_id == ctx.organization_id)
+        .ok_or_else(|| ScimError::not_found("Group not found"))?;
+
+    let members = Group::list_members(&state.pool, group.id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to list group members: {}", e)))?;
+
+    Ok((group, members))
+}
+
+/// Only members of the provisioning organization can join its groups
+async fn check_members(
+    state: &AppState,
+    ctx: ScimContext,
+    members: &[Uuid],
+) -> Result<(), ScimError> {
+    for &user_id in members {
+        Membership::find(&state.pool, ctx.organization_id, user_id)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to get membership: {}", e)))?
+            .ok_or_else(|| {
+                ScimError::bad_request("invalidValue", format!("Unknown member '{}'", user_id))
+            })?;
+    }
+    Ok(())
+}
+
+/// Persist the changes from `group` to `target`
+async fn save(
+    state: &AppState,
+    ctx: ScimContext,
+    group: &Group,
+    current: &[User],
+    target: Provisioned,
+) -> Result<(Group, Vec<User>), ScimError> {
+    if target.display_name.is_empty() {
+        return Err(ScimError::bad_request(
+            "invalidValue",
+            "displayName is required",
+        ));
+    }
+
+    let added: Vec<Uuid> = target
+        .members
+        .iter()
+        .copied()
+        .filter(|id| !current.iter().any(|u| u.id == *id))
+        .collect();
+    check_members(state, ctx, &added).await?;
+
+    if target.display_name != group.name {
+        Group::rename(&state.pool, group.id, &target.display_name)
+            .await
+            .map_err(|e| {
+                if e.to_string().contains("unique") {
+                    ScimError::conflict("displayName already exists")
+                } else {
+                    AppError::database(format!("Failed to rename group: {}", e)).into()
+                }
+            })?;
+    }
+
+    for user in current.iter().filter(|u| !target.members.contains(&u.id)) {
+        Group::remove_member(&state.pool, group.id, user.id)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to remove group member: {}", e)))?;
+    }
+
+    for &user_id in &added {
+        Group::add_member(&state.pool, group.id, user_id)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to add group member: {}", e)))?;
+    }
+
+    find_group(state, ctx, &group.id.to_string()).await
+}
+
+/// GET /scim/v2/Groups
+pub async fn list_groups(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<ScimContext>,
+    Query(query): Query<ListQuery>,
+) -> Result<Response, ScimError> {
+    let filter = query.parsed_filter()?;
+    let groups = Group::list(&state.pool, Some(ctx.organization_id))
+        .await
+        .map_err(|e| AppError::database(format!("Failed to list groups: {}", e)))?;
+
+    let mut resources = Vec::with_capacity(groups.len());
+    for group in &groups {
+        if query.excludes("members") {
+            resources.push(to_resource(group, None));
+            continue;
+        }
+
+        let members = Group::list_members(&state.pool, group.id)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to list group members: {}", e)))?;
+        resources.push(to_resource(group, Some(&members)));
+    }
+
+    Ok(list_response(resources, filter.as_ref(), &query))
+}
+
+/// GET /scim/v2/Groups/{id}
+pub async fn get_group(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<ScimContext>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Response, ScimError> {
+    let (group, members) = find_group(&state, ctx, &id).await?;
+    let resource = to_resource(&group, Some(&members));
+
+    Ok(not_modified(&headers, &resource)
+        .unwrap_or_else(|| resource_response(StatusCode::OK, &resource)))
+}
+
+/// POST /scim/v2/Groups
+pub async fn create_group(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<ScimContext>,
+    body: Bytes,
+) -> Result<Response, ScimError> {
+    let payload: ScimGroup = parse_body(&body)?;
+    if payload.display_name.is_empty() {
+        return Err(ScimError::bad_request(
+            "invalidValue",
+            "displayName is required",
+        ));
+    }
+    let target = Provisioned {
+        members: payload
+            .members
+            .iter()
+            .map(|m| parse_member(&m.value))
+            .collect::<Result<_, _>>()?,
+        display_name: payload.display_name,
+    };
+
+    check_members(&state, ctx, &target.members).await?;
+
+    let group = Group::create(&state.pool, ctx.organization_id, &target.display_name, None)
+        .await
+        .map_err(|e| {
+            if e.to_string().contains("unique") {
+                ScimError::conflict("displayName already exists")
+            } else {
+                AppError::database(format!("Failed to create group: {}", e)).into()
+            }
+        })?;
+
+    let (group, members) = save(&state, ctx, &group, &[], target).await?;
+
+    info!(group_id = %group.id, organization_id = %ctx.organization_id, "SCIM group provisioned");
+
+    Ok(resource_response(
+        StatusCode::CREATED,
+        &to_resource(&group, Some(&members)),
+    ))
+}
+
+/// PUT /scim/v2/Groups/{id}
+pub async fn replace_group(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<ScimContext>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    body: Bytes,
+) -> Result<Response, ScimError> {
+    let (group, members) = find_group(&state, ctx, &id).await?;
+    check_if_match(&headers, &to_resource(&group, Some(&members)))?;
+
+    let payload: ScimGroup = parse_body(&body)?;
+    let target = Provisioned {
+        members: payload
+            .members
+            .iter()
+            .map(|m| parse_member(&m.value))
+            .collect::<Result<_, _>>()?,
+        display_name: payload.display_name,
+    };
+
+    let (group, members) = save(&state, ctx, &group, &members, target).await?;
+
+    Ok(resource_response(
+        StatusCode::OK,
+        &to_resource(&group, Some(&members)),
+    ))
+}
+
+/// PATCH /scim/v2/Groups/{id}
+pub async fn patch_group(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<ScimContext>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    body: Bytes,
+) -> Result<Response, ScimError> {
+    let (group, members) = find_group(&state, ctx, &id).await?;
+    check_if_match(&headers, &to_resource(&group, Some(&members)))?;
+
+    let patch: PatchRequest = parse_body(&body)?;
+    patch.validate()?;
+
+    let mut target = Provisioned {
+        display_name: group.name.clone(),
+        members: members.iter().map(|u| u.id).collect(),
+    };
+    for operation in &patch.operations {
+        target.apply(
+            operation.kind()?,
+            operation.attribute().as_deref(),
+            operation.value.as_ref(),
+        )?;
+    }
+
+    let (group, members) = save(&state, ctx, &group, &members, target).await?;
+
+    Ok(resource_response(
+        StatusCode::OK,
+        &to_resource(&group, Some(&members)),
+    ))
+}
+
+/// DELETE /scim/v2/Groups/{id}
+pub async fn delete_group(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<ScimContext>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ScimError> {
+    let (group, members) = find_group(&state, ctx, &id).await?;
+    check_if_match(&headers, &to_resource(&group, Some(&members)))?;
+
+    Group::delete(&state.pool, group.id)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to delete group: {}", e)))?;
+
+    info!(group_id = %group.id, "SCIM group deleted");
+
+    Ok(StatusCode::NO_CONTENT)
+}
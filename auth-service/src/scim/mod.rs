@@ -0,0 +1,377 @@
+//! SCIM 2.0 provisioning API (RFC 7643 / RFC 7644) for users and groups of one
+//! organization, authenticated with a dedicated provisioning token.
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use common::errors::AppError;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use uuid::Uuid;
+
+use crate::db::organizations::Organization;
+use crate::handlers::AppState;
+use crate::tokens;
+
+pub mod filter;
+pub mod groups;
+pub mod users;
+
+use filter::Filter;
+
+pub const USER_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:User";
+pub const GROUP_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:Group";
+const LIST_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:ListResponse";
+const ERROR_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:Error";
+const PATCH_SCHEMA: &str = "urn:ietf:params:scim:api:messages:2.0:PatchOp";
+const CONFIG_SCHEMA: &str = "urn:ietf:params:scim:schemas:core:2.0:ServiceProviderConfig";
+
+const CONTENT_TYPE: &str = "application/scim+json";
+const DEFAULT_COUNT: usize = 100;
+const MAX_COUNT: usize = 1000;
+
+/// Organization the provisioning token acts on
+#[derive(Debug, Clone, Copy)]
+pub struct ScimContext {
+    pub organization_id: Uuid,
+}
+
+/// Error in the SCIM error format (RFC 7644 section 3.12)
+#[derive(Debug)]
+pub struct ScimError {
+    status: StatusCode,
+    scim_type: Option<&'static str>,
+    detail: String,
+}
+
+impl ScimError {
+    pub fn new(
+        status: StatusCode,
+        scim_type: Option<&'static str>,
+        detail: impl Into<String>,
+    ) -> Self {
+        Self {
+            status,
+            scim_type,
+            detail: detail.into(),
+        }
+    }
+
+    pub fn bad_request(scim_type: &'static str, detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, Some(scim_type), detail)
+    }
+
+    pub fn not_found(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, None, detail)
+    }
+
+    pub fn conflict(detail: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, Some("uniqueness"), detail)
+    }
+}
+
+impl From<AppError> for ScimError {
+    fn from(err: AppError) -> Self {
+        let status = match &err {
+            AppError::HttpError { status, .. } => {
+                StatusCode::from_u16(*status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+            AppError::AuthError(_) => StatusCode::UNAUTHORIZED,
+            AppError::AuthorizationError(_) => StatusCode::FORBIDDEN,
+            AppError::ValidationError(_) | AppError::InvalidFields(_) | AppError::ParseError(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let scim_type = (status == StatusCode::BAD_REQUEST).then_some("invalidValue");
+
+        Self::new(status, scim_type, err.to_string())
+    }
+}
+
+impl IntoResponse for ScimError {
+    fn into_response(self) -> Response {
+        let mut body = json!({
+            "schemas": [ERROR_SCHEMA],
+            "status": self.status.as_u16().to_string(),
+            "detail": self.detail,
+        });
+        if let Some(scim_type) = self.scim_type {
+            body["scimType"] = json!(scim_type);
+        }
+
+        scim_json(self.status, &body)
+    }
+}
+
+/// Middleware accepting only the configured SCIM provisioning token
+pub async fn require_provisioning_token(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    mut request: Request<Body>,
+    next: Next,
+) -> Result<Response, ScimError> {
+    let unauthorized = |detail: &str| ScimError::new(StatusCode::UNAUTHORIZED, None, detail);
+
+    let expected = state
+        .config
+        .scim_token
+        .as_deref()
+        .ok_or_else(|| unauthorized("SCIM provisioning is not enabled"))?;
+
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or_else(|| unauthorized("Missing provisioning token"))?;
+
+    // Compare digests so the comparison time does not depend on the secret
+    if tokens::hash_token(token) != tokens::hash_token(expected) {
+        return Err(unauthorized("Invalid provisioning token"));
+    }
+
+    let organization = Organization::find_by_slug(&state.pool, &state.config.scim_organization)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get organization: {}", e)))?
+        .ok_or_else(|| {
+            ScimError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                None,
+                "SCIM organization does not exist",
+            )
+        })?;
+
+    request.extensions_mut().insert(ScimContext {
+        organization_id: organization.id,
+    });
+
+    Ok(next.run(request).await)
+}
+
+/// Query parameters of list endpoints
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListQuery {
+    pub filter: Option<String>,
+    pub start_index: Option<usize>,
+    pub count: Option<usize>,
+    pub excluded_attributes: Option<String>,
+}
+
+impl ListQuery {
+    pub fn parsed_filter(&self) -> Result<Option<Filter>, ScimError> {
+        self.filter
+            .as_deref()
+            .map(Filter::parse)
+            .transpose()
+            .map_err(|e| ScimError::bad_request("invalidFilter", e))
+    }
+
+    pub fn excludes(&self, attribute: &str) -> bool {
+        self.excluded_attributes.as_deref().is_some_and(|list| {
+            list.split(',')
+                .any(|a| a.trim().eq_ignore_ascii_case(attribute))
+        })
+    }
+}
+
+/// A page of resources matching `filter`, as a SCIM ListResponse
+pub fn list_response(
+    resources: Vec<Value>,
+    filter: Option<&Filter>,
+    query: &ListQuery,
+) -> Response {
+    let matching: Vec<Value> = resources
+        .into_iter()
+        .filter(|r| filter.is_none_or(|f| f.matches(r)))
+        .collect();
+
+    let start_index = query.start_index.unwrap_or(1).max(1);
+    let count = query.count.unwrap_or(DEFAULT_COUNT).min(MAX_COUNT);
+    let page: Vec<Value> = matching
+        .iter()
+        .skip(start_index - 1)
+        .take(count)
+        .cloned()
+        .collect();
+
+    scim_json(
+        StatusCode::OK,
+        &json!({
+            "schemas": [LIST_SCHEMA],
+            "totalResults": matching.len(),
+            "startIndex": start_index,
+            "itemsPerPage": page.len(),
+            "Resources": page,
+        }),
+    )
+}
+
+/// Stamp `meta.version` with a weak ETag derived from the resource's content
+pub fn with_version(mut resource: Value) -> Value {
+    let digest = tokens::hash_token(&resource.to_string());
+    resource["meta"]["version"] = json!(format!("W/\"{}\"", &digest[..16]));
+    resource
+}
+
+fn version_of(resource: &Value) -> &str {
+    resource["meta"]["version"].as_str().unwrap_or_default()
+}
+
+/// A single resource with its `ETag` and `Location` headers
+pub fn resource_response(status: StatusCode, resource: &Value) -> Response {
+    let mut response = scim_json(status, resource);
+
+    if let Ok(etag) = HeaderValue::from_str(version_of(resource)) {
+        response.headers_mut().insert(header::ETAG, etag);
+    }
+    if status == StatusCode::CREATED
+        && let Some(location) = resource["meta"]["location"].as_str()
+        && let Ok(location) = HeaderValue::from_str(location)
+    {
+        response.headers_mut().insert(header::LOCATION, location);
+    }
+
+    response
+}
+
+/// `304 Not Modified` when the client's `If-None-Match` already names this version
+pub fn not_modified(headers: &HeaderMap, resource: &Value) -> Option<Response> {
+    let matches = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|h| etag_matches(h, version_of(resource)));
+
+    matches.then(|| StatusCode::NOT_MODIFIED.into_response())
+}
+
+/// Reject modifications made against a stale version (`If-Match`)
+pub fn check_if_match(headers: &HeaderMap, resource: &Value) -> Result<(), ScimError> {
+    match headers.get(header::IF_MATCH).and_then(|h| h.to_str().ok()) {
+        Some(h) if !etag_matches(h, version_of(resource)) => Err(ScimError::new(
+            StatusCode::PRECONDITION_FAILED,
+            None,
+            "Resource has been modified",
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn etag_matches(header: &str, version: &str) -> bool {
+    header
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag == version)
+}
+
+fn scim_json(status: StatusCode, body: &Value) -> Response {
+    (
+        status,
+        [(header::CONTENT_TYPE, CONTENT_TYPE)],
+        body.to_string(),
+    )
+        .into_response()
+}
+
+/// Parse a request body, reporting malformed JSON the SCIM way
+pub fn parse_body<T: DeserializeOwned>(body: &Bytes) -> Result<T, ScimError> {
+    serde_json::from_slice(body).map_err(|e| ScimError::bad_request("invalidSyntax", e.to_string()))
+}
+
+/// PATCH request body (RFC 7644 section 3.5.2)
+#[derive(Debug, Deserialize)]
+pub struct PatchRequest {
+    #[serde(default)]
+    pub schemas: Vec<String>,
+    #[serde(rename = "Operations")]
+    pub operations: Vec<PatchOperation>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PatchOperation {
+    pub op: String,
+    pub path: Option<String>,
+    pub value: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchOp {
+    Add,
+    Replace,
+    Remove,
+}
+
+impl PatchRequest {
+    pub fn validate(&self) -> Result<(), ScimError> {
+        if !self.schemas.iter().any(|s| s == PATCH_SCHEMA) {
+            return Err(ScimError::bad_request(
+                "invalidSyntax",
+                format!("PATCH requests must use the {} schema", PATCH_SCHEMA),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl PatchOperation {
+    pub fn kind(&self) -> Result<PatchOp, ScimError> {
+        match self.op.to_ascii_lowercase().as_str() {
+            "add" => Ok(PatchOp::Add),
+            "replace" => Ok(PatchOp::Replace),
+            "remove" => Ok(PatchOp::Remove),
+            other => Err(ScimError::bad_request(
+                "invalidSyntax",
+                format!("Unsupported PATCH operation '{}'", other),
+            )),
+        }
+    }
+
+    /// The attribute path, lowercased and without a schema URN prefix
+    pub fn attribute(&self) -> Option<String> {
+        self.path
+            .as_deref()
+            .map(|p| p.rsplit(':').next().unwrap_or(p).to_ascii_lowercase())
+    }
+}
+
+/// Booleans may arrive as strings from some identity providers, e.g. `"False"`
+pub fn as_bool(value: &Value) -> Result<bool, ScimError> {
+    match value {
+        Value::Bool(b) => Ok(*b),
+        Value::String(s) if s.eq_ignore_ascii_case("true") => Ok(true),
+        Value::String(s) if s.eq_ignore_ascii_case("false") => Ok(false),
+        _ => Err(ScimError::bad_request("invalidValue", "Expected a boolean")),
+    }
+}
+
+pub fn as_str(value: &Value) -> Result<&str, ScimError> {
+    value
+        .as_str()
+        .ok_or_else(|| ScimError::bad_request("invalidValue", "Expected a string"))
+}
+
+/// GET /scim/v2/ServiceProviderConfig
+pub async fn service_provider_config() -> Response {
+    scim_json(
+        StatusCode::OK,
+        &json!({
+            "schemas": [CONFIG_SCHEMA],
+            "patch": { "supported": true },
+            "bulk": { "supported": false, "maxOperations": 0, "maxPayloadSize": 0 },
+            "filter": { "supported": true, "maxResults": MAX_COUNT },
+            "changePassword": { "supported": true },
+            "sort": { "supported": false },
+            "etag": { "supported": true },
+            "authenticationSchemes": [{
+                "type": "oauthbearertoken",
+                "name": "Provisioning token",
+                "description": "Bearer token configured with SCIM_TOKEN",
+            }],
+        }),
+    )
+}
@@ -0,0 +1,454 @@
+use axum::{
+    Extension,
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::Response,
+};
+use common::errors::AppError;
+use common::validation;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tracing::info;
+use uuid::Uuid;
+
+use super::{
+    ListQuery, PatchOp, PatchRequest, ScimContext, ScimError, USER_SCHEMA, as_bool, as_str,
+    check_if_match, list_response, not_modified, parse_body, resource_response, with_version,
+};
+use crate::db::organizations::{Membership, Organization};
+use crate::db::queries::User;
+use crate::db::sessions::Session;
+use crate::handlers::{AppState, account, create_member, remove_from_organization};
+use crate::identity;
+use crate::permissions;
+use crate::scope::AdminScope;
+use crate::tokens;
+use crate::webhooks::{self, UserEvent};
+
+/// Multi-valued attribute entry such as an email or role
+#[derive(Debug, Deserialize)]
+struct MultiValue {
+    value: String,
+    #[serde(default)]
+    primary: bool,
+}
+
+/// User resource as sent by the provisioning client. Attributes we don't store
+/// (name, externalId, ...) are accepted and ignored.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScimUser {
+    user_name: String,
+    #[serde(default)]
+    emails: Vec<MultiValue>,
+    #[serde(default)]
+    roles: Vec<MultiValue>,
+    active: Option<bool>,
+    password: Option<String>,
+}
+
+/// The provisioned state of a user, which PUT and PATCH both resolve to
+struct Provisioned {
+    user_name: String,
+    email: String,
+    role: String,
+    active: bool,
+    password: Option<String>,
+}
+
+impl Provisioned {
+    fn from_user(user: &User) -> Self {
+        Self {
+            user_name: user.username.clone(),
+            email: user.email.clone(),
+            role: user.role.clone(),
+            active: user.is_active,
+            password: None,
+        }
+    }
+
+    fn from_resource(resource: ScimUser) -> Result<Self, ScimError> {
+        Ok(Self {
+            email: primary(&resource.emails).ok_or_else(|| {
+                ScimError::bad_request("invalidValue", "At least one email is required")
+            })?,
+            role: primary(&resource.roles).unwrap_or_else(|| "user".to_string()),
+            active: resource.active.unwrap_or(true),
+            password: resource.password,
+            user_name: resource.user_name,
+        })
+    }
+
+    /// Check and normalize the attributes. A `current` username left unchanged
+    /// is kept as is, even if it predates the username rules.
+    fn validate(&mut self, current: Option<&User>) -> Result<(), ScimError> {
+        if self.user_name.is_empty() || self.email.is_empty() {
+            return Err(ScimError::bad_request(
+                "invalidValue",
+                "userName and email are required",
+            ));
+        }
+        if current.is_none_or(|u| u.username != self.user_name) {
+            self.user_name = identity::normalize_username(&self.user_name)
+                .map_err(|e| ScimError::bad_request("invalidValue", e))?;
+        }
+        self.email = identity::normalize_email(&self.email)
+            .map_err(|e| ScimError::bad_request("invalidValue", e))?;
+        if let Some(password) = &self.password {
+            validation::password(password)
+                .map_err(|e| ScimError::bad_request("invalidValue", format!("password: {}", e)))?;
+        }
+        if self.role.is_empty() || self.role == "super_admin" {
+            return Err(ScimError::bad_request(
+                "invalidValue",
+                format!("Role '{}' cannot be provisioned", self.role),
+            ));
+        }
+        Ok(())
+    }
+
+    fn apply(
+        &mut self,
+        op: PatchOp,
+        path: Option<&str>,
+        value: Option<&Value>,
+    ) -> Result<(), ScimError> {
+        let required = || {
+            value
+                .ok_or_else(|| ScimError::bad_request("invalidValue", "Operation requires a value"))
+        };
+
+        let Some(path) = path else {
+            // No path: the value is an object of attributes to set
+            let attributes = required()?
+                .as_object()
+                .ok_or_else(|| ScimError::bad_request("invalidValue", "Expected an object"))?;
+            for (attribute, value) in attributes {
+                self.apply(op, Some(&attribute.to_ascii_lowercase()), Some(value))?;
+            }
+            return Ok(());
+        };
+
+        let attribute = path.split(['[', '.']).next().unwrap_or(path);
+        match (attribute, op) {
+            ("username" | "emails" | "active", PatchOp::Remove) => {
+                return Err(ScimError::bad_request(
+                    "mutability",
+                    format!("'{}' cannot be removed", attribute),
+                ));
+            }
+            ("roles", PatchOp::Remove) => self.role = "user".to_string(),
+            ("username", _) => self.user_name = as_str(required()?)?.to_string(),
+            ("active", _) => self.active = as_bool(required()?)?,
+            ("password", _) => self.password = Some(as_str(required()?)?.to_string()),
+            ("emails", _) => self.email = multi_value(required()?)?,
+            ("roles", _) => self.role = multi_value(required()?)?,
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// The primary entry of a multi-valued attribute, or its first
+fn primary(values: &[MultiValue]) -> Option<String> {
+    values
+        .iter()
+        .find(|v| v.primary)
+        .or_else(|| values.first())
+        .map(|v| v.value.clone())
+}
+
+/// A PATCH value for a multi-valued attribute: a bare string or a list of entries
+fn multi_value(value: &Value) -> Result<String, ScimError> {
+    if let Some(s) = value.as_str() {
+        return Ok(s.to_string());
+    }
+
+    let entries: Vec<MultiValue> = serde_json::from_value(value.clone())
+        .map_err(|e| ScimError::bad_request("invalidValue", e.to_string()))?;
+    primary(&entries).ok_or_else(|| ScimError::bad_request("invalidValue", "Expected a value"))
+}
+
+fn to_resource(user: &User) -> Value {
+    with_version(json!({
+        "schemas": [USER_SCHEMA],
+        "id": user.id.to_string(),
+        "userName": user.username,
+        "displayName": user.username,
+        "emails": [{ "value": user.email, "primary": true }],
+        "roles": [{ "value": user.role, "primary": true }],
+        "active": user.is_active,
+        "meta": {
+            "resourceType": "User",
+            "created": user.created_at.to_rfc3339(),
+            "lastModified": user.updated_at.to_rfc3339(),
+            "location": format!("/scim/v2/Users/{}", user.id),
+        },
+    }))
+}
+
+async fn find_member(ctx: ScimContext, state: &AppState, id: &str) -> Result<User, ScimError> {
+    let user_id = Uuid::parse_str(id).map_err(|_| ScimError::not_found("User not found"))?;
+
+    Ok(AdminScope::Organization(ctx.organization_id)
+        .find_user(&state.pool, user_id)
+        .await?)
+}
+
+/// Persist the changes from `user` to `target`
+async fn save(
+    state: &AppState,
+    ctx: ScimContext,
+    user: User,
+    mut target: Provisioned,
+) -> Result<User, ScimError> {
+    target.validate(Some(&user))?;
+
+    // The password and active flag belong to the account, not the membership,
+    // so an organization may only change them for accounts that are its alone
+    let mut account = None;
+    if target.password.is_some() || target.active != user.is_active {
+        let found = User::find_by_id(&state.pool, user.id)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to get user: {}", e)))?
+            .ok_or_else(|| ScimError::not_found("User not found"))?;
+        let memberships = Membership::count_for_user(&state.pool, user.id)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to count memberships: {}", e)))?;
+
+        if found.role == "super_admin" || memberships > 1 {
+            return Err(AppError::authorization(
+                "password and active cannot be changed for users who belong to other organizations",
+            )
+            .into());
+        }
+        if let Some(password) = &target.password {
+            account::ensure_not_reused(state, &found, password).await?;
+        }
+        account = Some(found);
+    }
+
+    if target.user_name != user.username || target.email != user.email {
+        User::update_profile(&state.pool, user.id, &target.user_name, &target.email)
+            .await
+            .map_err(|e| {
+                if e.to_string().contains("unique") {
+                    ScimError::conflict("userName or email already exists")
+                } else {
+                    AppError::database(format!("Failed to update user: {}", e)).into()
+                }
+            })?;
+    }
+
+    if target.role != user.role {
+        permissions::require_role(&state.pool, &target.role).await?;
+
+        let mut tx = state
+            .pool
+            .begin()
+            .await
+            .map_err(|e| AppError::database(format!("Failed to start transaction: {}", e)))?;
+        Organization::set_member(&mut *tx, ctx.organization_id, user.id, &target.role)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to update role: {}", e)))?;
+        webhooks::enqueue(
+            &mut *tx,
+            UserEvent::RoleChanged,
+            &user,
+            json!({
+                "role": target.role,
+                "previous_role": user.role,
+                "organization_id": ctx.organization_id,
+            }),
+        )
+        .await
+        .map_err(|e| AppError::database(format!("Failed to queue webhook: {}", e)))?;
+        tx.commit()
+            .await
+            .map_err(|e| AppError::database(format!("Failed to update role: {}", e)))?;
+    }
+
+    if let (Some(password), Some(account)) = (&target.password, &account) {
+        let hash = bcrypt::hash(password, bcrypt::DEFAULT_COST)
+            .map_err(|e| AppError::internal(format!("Password hashing failed: {}", e)))?;
+        let keep_previous = state.config.previous_passwords_kept(&account.role);
+        User::update_password(&state.pool, user.id, &hash, keep_previous)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to update password: {}", e)))?;
+    }
+
+    if target.active != user.is_active {
+        User::set_active(&state.pool, user.id, target.active)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to update user: {}", e)))?;
+
+        if !target.active {
+            Session::revoke_all_for_user(&state.pool, user.id, None)
+                .await
+                .map_err(|e| AppError::database(format!("Failed to revoke sessions: {}", e)))?;
+        }
+        info!(user_id = %user.id, active = target.active, "SCIM user activation changed");
+    }
+
+    find_member(ctx, state, &user.id.to_string()).await
+}
+
+/// GET /scim/v2/Users
+pub async fn list_users(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<ScimContext>,
+    Query(query): Query<ListQuery>,
+) -> Result<Response, ScimError> {
+    let filter = query.parsed_filter()?;
+
+    // Identity providers look users up by userName before creating them
+    let users = match filter.as_ref().and_then(|f| f.equality("userName")) {
+        Some(username) => match User::find_by_username(&state.pool, username)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to get user: {}", e)))?
+        {
+            Some(user) => match Membership::find(&state.pool, ctx.organization_id, user.id)
+                .await
+                .map_err(|e| AppError::database(format!("Failed to get membership: {}", e)))?
+            {
+                Some(m) => vec![User {
+                    role: m.role,
+                    ..user
+                }],
+                None => vec![],
+            },
+            None => vec![],
+        },
+        None => Organization::list_members(&state.pool, ctx.organization_id)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to list users: {}", e)))?,
+    };
+
+    let resources = users.iter().map(to_resource).collect();
+
+    Ok(list_response(resources, filter.as_ref(), &query))
+}
+
+/// GET /scim/v2/Users/{id}
+pub async fn get_user(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<ScimContext>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Response, ScimError> {
+    let resource = to_resource(&find_member(ctx, &state, &id).await?);
+
+    Ok(not_modified(&headers, &resource)
+        .unwrap_or_else(|| resource_response(StatusCode::OK, &resource)))
+}
+
+/// POST /scim/v2/Users
+pub async fn create_user(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<ScimContext>,
+    body: Bytes,
+) -> Result<Response, ScimError> {
+    let mut target = Provisioned::from_resource(parse_body(&body)?)?;
+    target.validate(None)?;
+    permissions::require_role(&state.pool, &target.role).await?;
+
+    let existing = User::find_by_username(&state.pool, &target.user_name)
+        .await
+        .map_err(|e| AppError::database(format!("Failed to get user: {}", e)))?;
+    if existing.is_some() {
+        return Err(ScimError::conflict("userName already exists"));
+    }
+
+    // Without a password the account can only be used once one is set
+    let password = target
+        .password
+        .clone()
+        .unwrap_or_else(tokens::generate_opaque_token);
+    let password_hash = bcrypt::hash(&password, bcrypt::DEFAULT_COST)
+        .map_err(|e| AppError::internal(format!("Password hashing failed: {}", e)))?;
+
+    let user = create_member(
+        &state,
+        ctx.organization_id,
+        &target.user_name,
+        &target.email,
+        &password_hash,
+        &target.role,
+    )
+    .await?;
+
+    if !target.active {
+        User::set_active(&state.pool, user.id, false)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to update user: {}", e)))?;
+    }
+
+    info!(user_id = %user.id, organization_id = %ctx.organization_id, "SCIM user provisioned");
+
+    let user = find_member(ctx, &state, &user.id.to_string()).await?;
+    Ok(resource_response(StatusCode::CREATED, &to_resource(&user)))
+}
+
+/// PUT /scim/v2/Users/{id}
+pub async fn replace_user(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<ScimContext>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    body: Bytes,
+) -> Result<Response, ScimError> {
+    let user = find_member(ctx, &state, &id).await?;
+    check_if_match(&headers, &to_resource(&user))?;
+
+    let target = Provisioned::from_resource(parse_body(&body)?)?;
+    let user = save(&state, ctx, user, target).await?;
+
+    Ok(resource_response(StatusCode::OK, &to_resource(&user)))
+}
+
+/// PATCH /scim/v2/Users/{id}
+pub async fn patch_user(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<ScimContext>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    body: Bytes,
+) -> Result<Response, ScimError> {
+    let user = find_member(ctx, &state, &id).await?;
+    check_if_match(&headers, &to_resource(&user))?;
+
+    let patch: PatchRequest = parse_body(&body)?;
+    patch.validate()?;
+
+    let mut target = Provisioned::from_user(&user);
+    for operation in &patch.operations {
+        target.apply(
+            operation.kind()?,
+            operation.attribute().as_deref(),
+            operation.value.as_ref(),
+        )?;
+    }
+
+    let user = save(&state, ctx, user, target).await?;
+
+    Ok(resource_response(StatusCode::OK, &to_resource(&user)))
+}
+
+/// DELETE /scim/v2/Users/{id}
+pub async fn delete_user(
+    State(state): State<AppState>,
+    Extension(ctx): Extension<ScimContext>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ScimError> {
+    let user = find_member(ctx, &state, &id).await?;
+    check_if_match(&headers, &to_resource(&user))?;
+
+    remove_from_organization(&state, ctx.organization_id, user.id).await?;
+
+    info!(user_id = %user.id, organization_id = %ctx.organization_id, "SCIM user deprovisioned");
+
+    Ok(StatusCode::NO_CONTENT)
+}
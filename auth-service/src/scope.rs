@@ -0,0 +1,102 @@
+use common::errors::AppError;
+use common::models::Claims;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::db::organizations::{Membership, Organization};
+use crate::db::queries::User;
+
+/// Which users an admin token may manage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdminScope {
+    /// Platform super admin: every user in every organization
+    Platform,
+    /// Organization admin: members of a single organization
+    Organization(Uuid),
+}
+
+impl AdminScope {
+    pub fn from_claims(claims: &Claims) -> Result<Self, AppError> {
+        if claims.role == "super_admin" {
+            return Ok(Self::Platform);
+        }
+
+        match (claims.org.as_deref(), claims.org_role.as_deref()) {
+            (Some(org), Some("admin")) => Uuid::parse_str(org)
+                .map(Self::Organization)
+                .map_err(|_| AppError::auth("Invalid token: malformed org")),
+            _ => Err(AppError::authorization(
+                "Admin role required for this endpoint",
+            )),
+        }
+    }
+
+    pub fn organization(&self) -> Option<Uuid> {
+        match self {
+            Self::Platform => None,
+            Self::Organization(id) => Some(*id),
+        }
+    }
+
+    pub fn require_platform(&self) -> Result<(), AppError> {
+        match self {
+            Self::Platform => Ok(()),
+            Self::Organization(_) => Err(AppError::authorization(
+                "Super admin role required for this endpoint",
+            )),
+        }
+    }
+
+    pub fn require_organization(&self, organization_id: Uuid) -> Result<(), AppError> {
+        match self {
+            Self::Organization(id) if *id != organization_id => {
+                Err(AppError::http(404, "Organization not found"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Load a user this scope may manage. For organization admins the user's
+    /// `role` is their role within the organization; users outside it are not found.
+    pub async fn find_user(&self, pool: &PgPool, user_id: Uuid) -> Result<User, AppError> {
+        let user = User::find_by_id(pool, user_id)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to get user: {}", e)))?
+            .ok_or_else(|| AppError::http(404, "User not found"))?;
+
+        match self {
+            Self::Platform => Ok(user),
+            Self::Organization(org) => {
+                let membership = Membership::find(pool, *org, user_id)
+                    .await
+                    .map_err(|e| AppError::database(format!("Failed to get membership: {}", e)))?
+                    .ok_or_else(|| AppError::http(404, "User not found"))?;
+
+                Ok(User {
+                    role: membership.role,
+                    ..user
+                })
+            }
+        }
+    }
+
+    /// Organization new users created by this admin join
+    pub async fn home_organization(
+        &self,
+        pool: &PgPool,
+        claims: &Claims,
+    ) -> Result<Uuid, AppError> {
+        if let Some(org) = self.organization() {
+            return Ok(org);
+        }
+        if let Some(org) = claims.org.as_deref().and_then(|o| Uuid::parse_str(o).ok()) {
+            return Ok(org);
+        }
+
+        Organization::find_by_slug(pool, Organization::DEFAULT_SLUG)
+            .await
+            .map_err(|e| AppError::database(format!("Failed to get organization: {}", e)))?
+            .map(|org| org.id)
+            .ok_or_else(|| AppError::internal("Default organization is missing"))
+    }
+}
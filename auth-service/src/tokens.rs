@@ -0,0 +1,43 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+/// Generate a random, URL-safe opaque token (256 bits of entropy)
+pub fn generate_opaque_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Hash an opaque token for storage; only the hash is ever persisted
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+fn mac(secret: &str, purpose: &str, payload: &str) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(purpose.as_bytes());
+    mac.update(b".");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+/// Append an HMAC-SHA256 signature to `payload`. The signature covers `purpose`
+/// so a token issued for one flow is rejected by every other.
+pub fn sign(secret: &str, purpose: &str, payload: &str) -> String {
+    let signature = mac(secret, purpose, payload).finalize().into_bytes();
+    format!("{}.{}", payload, URL_SAFE_NO_PAD.encode(signature))
+}
+
+/// Check a token produced by [`sign`] and return its payload
+pub fn verify<'a>(secret: &str, purpose: &str, token: &'a str) -> Option<&'a str> {
+    let (payload, signature) = token.rsplit_once('.')?;
+    let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+    mac(secret, purpose, payload)
+        .verify_slice(&signature)
+        .ok()
+        .map(|_| payload)
+}
@@ -0,0 +1,376 @@
+//! Outgoing webhooks for user lifecycle events.
+//!
+//! Events are written to the `webhook_deliveries` outbox in the transaction
+//! that changes the user, one row per matching subscription. A background
+//! dispatcher posts due rows to their subscription, retrying failures with
+//! exponential backoff until they are delivered or run out of attempts and
+//! are dead-lettered.
+//!
+//! Subscriptions are chosen by admins, so their URLs must not reach the
+//! service's own network: unless `WEBHOOK_ALLOW_INSECURE` is set, only https
+//! URLs whose host resolves to public addresses are accepted, they are checked
+//! again before every delivery, and the dispatcher connects only to public
+//! addresses and does not follow redirects.
+
+use hmac::{Hmac, Mac};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{Url, redirect};
+use sha2::Sha256;
+use sqlx::{PgExecutor, PgPool};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::db::queries::User;
+use crate::db::webhooks::{Delivery, DueDelivery};
+
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+pub const TIMESTAMP_HEADER: &str = "x-webhook-timestamp";
+
+/// Deliveries claimed per poll
+const BATCH_SIZE: i64 = 50;
+
+/// A user lifecycle event subscriptions can ask for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UserEvent {
+    Created,
+    Deleted,
+    RoleChanged,
+    Removed,
+}
+
+impl UserEvent {
+    pub const ALL: [UserEvent; 4] = [
+        Self::Created,
+        Self::Deleted,
+        Self::RoleChanged,
+        Self::Removed,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Created => "user.created",
+            Self::Deleted => "user.deleted",
+            Self::RoleChanged => "user.role_changed",
+            Self::Removed => "user.removed",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.as_str() == name)
+    }
+}
+
+/// Queue `event` about `user` for every subscription covering them. `details`
+/// is merged into the user's fields under `data`.
+pub async fn enqueue<'e>(
+    executor: impl PgExecutor<'e>,
+    event: UserEvent,
+    user: &User,
+    details: serde_json::Value,
+) -> Result<(), sqlx::Error> {
+    let mut data = serde_json::json!({
+        "user_id": user.id,
+        "username": user.username,
+        "email": user.email,
+        "role": user.role,
+    });
+    if let (Some(data), serde_json::Value::Object(details)) = (data.as_object_mut(), details) {
+        data.extend(details);
+    }
+
+    let payload = serde_json::json!({
+        "id": Uuid::new_v4(),
+        "type": event.as_str(),
+        "created_at": chrono::Utc::now().to_rfc3339(),
+        "data": data,
+    });
+
+    Delivery::enqueue(executor, event.as_str(), user.id, &payload).await?;
+    Ok(())
+}
+
+/// `sha256=<hex>` HMAC of `{timestamp}.{body}` under the subscription secret
+pub fn signature(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+/// Check that `url` may receive webhooks: https, unless `allow_insecure`,
+/// and with a host that only resolves to public addresses
+pub async fn check_url(url: &str, allow_insecure: bool) -> Result<(), String> {
+    let url = Url::parse(url).map_err(|_| "Webhook URL is not a valid URL".to_string())?;
+    match url.scheme() {
+        "https" => {}
+        "http" if allow_insecure => {}
+        _ if allow_insecure => return Err("Webhook URL must be http or https".to_string()),
+        _ => return Err("Webhook URL must be https".to_string()),
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| "Webhook URL has no host".to_string())?;
+    if allow_insecure {
+        return Ok(());
+    }
+
+    // IPv6 literals are bracketed in URLs
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    resolve_public(host).await.map(|_| ())
+}
+
+/// Addresses of `host`, refused if any of them is internal
+async fn resolve_public(host: &str) -> Result<Vec<SocketAddr>, String> {
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, 0))
+        .await
+        .map_err(|e| format!("Failed to resolve webhook host {}: {}", host, e))?
+        .collect();
+
+    if addrs.is_empty() {
+        return Err(format!("Webhook host {} has no addresses", host));
+    }
+    if let Some(internal) = addrs.iter().find(|a| is_internal(a.ip())) {
+        return Err(format!(
+            "Webhook host {} resolves to internal address {}",
+            host,
+            internal.ip()
+        ));
+    }
+
+    Ok(addrs)
+}
+
+/// Loopback, private, link-local, unspecified and other non-public addresses
+fn is_internal(ip: IpAddr) -> bool {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                // "This network" and carrier-grade NAT
+                || a == 0
+                || (a == 100 && (64..128).contains(&b))
+        }
+        IpAddr::V6(ip) => {
+            ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_unique_local()
+                || ip.is_unicast_link_local()
+        }
+    }
+}
+
+/// Resolves webhook hosts at connection time, so a name that passed
+/// [`check_url`] cannot be rebound to an internal address before delivery
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs = resolve_public(&host).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Drains the outbox in the background
+pub struct Dispatcher {
+    pool: PgPool,
+    client: reqwest::Client,
+    poll_interval: Duration,
+    timeout: Duration,
+    max_attempts: i32,
+    retry_base: Duration,
+    allow_insecure: bool,
+}
+
+impl Dispatcher {
+    pub fn new(pool: PgPool, config: &Config) -> Self {
+        // Redirects could lead anywhere, and a proxy would resolve hosts itself
+        let mut client = reqwest::Client::builder()
+            .redirect(redirect::Policy::none())
+            .no_proxy();
+        if !config.webhook_allow_insecure {
+            client = client.dns_resolver(PublicResolver);
+        }
+
+        Self {
+            pool,
+            client: client
+                .build()
+                .expect("webhook HTTP client configuration is valid"),
+            poll_interval: Duration::from_secs(config.webhook_poll_interval_seconds.max(1)),
+            timeout: Duration::from_secs(config.webhook_timeout_seconds),
+            max_attempts: config.webhook_max_attempts,
+            retry_base: Duration::from_secs(config.webhook_retry_base_seconds),
+            allow_insecure: config.webhook_allow_insecure,
+        }
+    }
+
+    pub fn spawn(self) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.poll_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.run_once().await {
+                    error!(error = %e, "Webhook dispatch failed");
+                }
+            }
+        });
+    }
+
+    async fn run_once(&self) -> Result<(), sqlx::Error> {
+        loop {
+            // Leased for longer than an attempt can take, so a crashed
+            // dispatcher's claims become due again
+            let lease_until = chrono::Utc::now() + self.timeout * 2 + Duration::from_secs(30);
+            let due = Delivery::claim_due(&self.pool, BATCH_SIZE, lease_until).await?;
+            let claimed = due.len() as i64;
+
+            futures::future::join_all(due.iter().map(|d| self.deliver(d))).await;
+
+            if claimed < BATCH_SIZE {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn deliver(&self, delivery: &DueDelivery) {
+        let outcome = match check_url(&delivery.url, self.allow_insecure).await {
+            Ok(()) => self.post(delivery).await,
+            Err(e) => Err((None, e)),
+        };
+
+        let recorded = match outcome {
+            Ok(status_code) => {
+                info!(delivery_id = %delivery.id, event = %delivery.event, "Webhook delivered");
+                Delivery::mark_delivered(&self.pool, delivery.id, status_code as i32).await
+            }
+            Err((status_code, error)) => {
+                let attempts = delivery.attempts + 1;
+                let retry_at = (attempts < self.max_attempts)
+                    .then(|| chrono::Utc::now() + self.backoff(attempts));
+                match retry_at {
+                    Some(at) => {
+                        warn!(delivery_id = %delivery.id, attempts, error = %error, retry_at = %at, "Webhook delivery failed, will retry")
+                    }
+                    None => {
+                        warn!(delivery_id = %delivery.id, attempts, error = %error, "Webhook delivery failed, dead-lettered")
+                    }
+                }
+                Delivery::mark_failed(&self.pool, delivery.id, status_code, &error, retry_at).await
+            }
+        };
+
+        if let Err(e) = recorded {
+            error!(delivery_id = %delivery.id, error = %e, "Failed to record webhook delivery");
+        }
+    }
+
+    /// Post `delivery` once; the status code on success, or the status
+    /// code, if any, and error on failure
+    async fn post(&self, delivery: &DueDelivery) -> Result<u16, (Option<i32>, String)> {
+        let body = delivery.payload.0.to_string();
+        let timestamp = chrono::Utc::now().timestamp();
+
+        let result = self
+            .client
+            .post(&delivery.url)
+            .timeout(self.timeout)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("x-webhook-id", delivery.id.to_string())
+            .header("x-webhook-event", &delivery.event)
+            .header(TIMESTAMP_HEADER, timestamp)
+            .header(
+                SIGNATURE_HEADER,
+                signature(&delivery.secret, timestamp, &body),
+            )
+            .body(body)
+            .send()
+            .await;
+
+        match result {
+            Ok(response) if response.status().is_success() => Ok(response.status().as_u16()),
+            Ok(response) => Err((
+                Some(response.status().as_u16() as i32),
+                format!("Endpoint returned {}", response.status()),
+            )),
+            Err(e) => Err((None, e.to_string())),
+        }
+    }
+
+    /// Delay before the next attempt: the base doubled per attempt made, capped at a day
+    fn backoff(&self, attempts: i32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1) as u32);
+        self.retry_base
+            .saturating_mul(factor)
+            .min(Duration::from_secs(24 * 60 * 60))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn internal(ip: &str) -> bool {
+        is_internal(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn internal_addresses_are_recognised() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "0.0.0.0",
+            "100.64.0.1",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(internal(ip), "{} is internal", ip);
+        }
+        for ip in ["93.184.216.34", "8.8.8.8", "2606:4700::1111"] {
+            assert!(!internal(ip), "{} is public", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn only_https_urls_to_public_hosts_are_accepted() {
+        assert!(check_url("https://93.184.216.34/hook", false).await.is_ok());
+
+        for url in [
+            "http://93.184.216.34/hook",
+            "ftp://93.184.216.34/hook",
+            "https://127.0.0.1/hook",
+            "https://169.254.169.254/latest/meta-data",
+            "https://[::1]:8443/hook",
+            "https://[::ffff:10.0.0.1]/hook",
+            "https://localhost/hook",
+            "not a url",
+        ] {
+            assert!(check_url(url, false).await.is_err(), "{} is refused", url);
+        }
+    }
+
+    #[tokio::test]
+    async fn insecure_mode_allows_http_and_internal_hosts() {
+        assert!(check_url("http://localhost:8080/hook", true).await.is_ok());
+        assert!(check_url("https://10.0.0.1/hook", true).await.is_ok());
+        assert!(check_url("ftp://localhost/hook", true).await.is_err());
+    }
+}
@@ -0,0 +1,177 @@
+//! The address a request came from, behind any number of reverse proxies.
+
+use axum::http::HeaderMap;
+use ipnet::IpNet;
+use std::net::IpAddr;
+
+const FORWARDED_FOR: &str = "X-Forwarded-For";
+
+/// Parse a comma separated list of addresses and CIDR ranges, as configured
+/// for the proxies in front of a service
+pub fn parse_trusted_proxies(value: &str) -> Result<Vec<IpNet>, String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|entry| {
+            entry
+                .parse()
+                .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| format!("'{}' is not an address or CIDR range", entry))
+        })
+        .collect()
+}
+
+/// The address a request came from. `X-Forwarded-For` is only honoured when
+/// the peer is a trusted proxy, and then read right to left: each trusted hop
+/// vouches for the one before it, so the right-most untrusted hop is the
+/// client. Anything further left was written by the client and is ignored.
+pub fn client_ip(peer: Option<IpAddr>, headers: &HeaderMap, trusted: &[IpNet]) -> Option<IpAddr> {
+    let peer = peer.map(canonical)?;
+    let is_trusted = |ip: &IpAddr| trusted.iter().any(|net| net.contains(ip));
+    if !is_trusted(&peer) {
+        return Some(peer);
+    }
+
+    let hops: Vec<&str> = headers
+        .get_all(FORWARDED_FOR)
+        .iter()
+        .filter_map(|h| h.to_str().ok())
+        .flat_map(|h| h.split(','))
+        .map(str::trim)
+        .collect();
+
+    let mut client = peer;
+    for hop in hops.iter().rev() {
+        // A hop that is not an address cannot be vouched for; stop at the
+        // proxy that passed it on
+        let Ok(ip) = hop.parse::<IpAddr>().map(canonical) else {
+            break;
+        };
+        client = ip;
+        if !is_trusted(&ip) {
+            break;
+        }
+    }
+
+    Some(client)
+}
+
+/// IPv4 clients of a dual-stack listener appear as `::ffff:a.b.c.d`
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        v4 => v4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trusted() -> Vec<IpNet> {
+        vec![
+            "10.0.0.0/8".parse().unwrap(),
+            "192.0.2.1/32".parse().unwrap(),
+        ]
+    }
+
+    fn forwarded(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(FORWARDED_FOR, value.parse().unwrap());
+        }
+        headers
+    }
+
+    fn ip(s: &str) -> Option<IpAddr> {
+        Some(s.parse().unwrap())
+    }
+
+    #[test]
+    fn untrusted_peer_ignores_forwarded_for() {
+        let headers = forwarded(&["198.51.100.7"]);
+        assert_eq!(
+            client_ip(ip("203.0.113.9"), &headers, &trusted()),
+            ip("203.0.113.9")
+        );
+    }
+
+    #[test]
+    fn no_trusted_proxies_ignores_forwarded_for() {
+        let headers = forwarded(&["198.51.100.7"]);
+        assert_eq!(client_ip(ip("10.1.2.3"), &headers, &[]), ip("10.1.2.3"));
+    }
+
+    #[test]
+    fn trusted_peer_takes_right_most_untrusted_hop() {
+        // The client forged the first hop; the proxy appended the real one
+        let headers = forwarded(&["198.51.100.7, 203.0.113.9, 10.0.0.2"]);
+        assert_eq!(
+            client_ip(ip("192.0.2.1"), &headers, &trusted()),
+            ip("203.0.113.9")
+        );
+    }
+
+    #[test]
+    fn hops_are_read_across_repeated_headers() {
+        let headers = forwarded(&["198.51.100.7", "203.0.113.9"]);
+        assert_eq!(
+            client_ip(ip("10.0.0.1"), &headers, &trusted()),
+            ip("203.0.113.9")
+        );
+    }
+
+    #[test]
+    fn trusted_peer_without_header_is_the_client() {
+        assert_eq!(
+            client_ip(ip("10.0.0.1"), &HeaderMap::new(), &trusted()),
+            ip("10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn malformed_hop_stops_at_last_trusted_address() {
+        let headers = forwarded(&["203.0.113.9, not-an-ip, 10.0.0.2"]);
+        assert_eq!(
+            client_ip(ip("10.0.0.1"), &headers, &trusted()),
+            ip("10.0.0.2")
+        );
+    }
+
+    #[test]
+    fn every_hop_trusted_yields_the_left_most() {
+        let headers = forwarded(&["10.0.0.3, 10.0.0.2"]);
+        assert_eq!(
+            client_ip(ip("10.0.0.1"), &headers, &trusted()),
+            ip("10.0.0.3")
+        );
+    }
+
+    #[test]
+    fn ipv4_mapped_peer_matches_ipv4_ranges() {
+        let headers = forwarded(&["203.0.113.9"]);
+        assert_eq!(
+            client_ip(ip("::ffff:10.0.0.1"), &headers, &trusted()),
+            ip("203.0.113.9")
+        );
+    }
+
+    #[test]
+    fn missing_peer_is_unknown() {
+        let headers = forwarded(&["203.0.113.9"]);
+        assert_eq!(client_ip(None, &headers, &trusted()), None);
+    }
+
+    #[test]
+    fn trusted_proxies_accept_addresses_and_ranges() {
+        let proxies = parse_trusted_proxies(" 10.0.0.0/8, 192.0.2.1,,::1 ").unwrap();
+        assert_eq!(proxies.len(), 3);
+        assert!(proxies[1].contains(&"192.0.2.1".parse::<IpAddr>().unwrap()));
+        assert!(!proxies[1].contains(&"192.0.2.2".parse::<IpAddr>().unwrap()));
+
+        assert!(parse_trusted_proxies("10.0.0.0/33").is_err());
+        assert!(parse_trusted_proxies("proxy.internal").is_err());
+        assert_eq!(parse_trusted_proxies("").unwrap(), vec![]);
+    }
+}
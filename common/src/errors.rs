@@ -1,10 +1,12 @@
 use axum::{
     Json,
-    http::StatusCode,
+    http::{StatusCode, header::RETRY_AFTER},
     response::{IntoResponse, Response},
 };
 use serde::Serialize;
 use thiserror::Error;
+use utoipa::ToSchema;
+use validator::{ValidationErrors, ValidationErrorsKind};
 
 /// Structured error types for the microservices
 #[derive(Error, Debug)]
@@ -33,13 +35,45 @@ pub enum AppError {
     #[error("Validation error: {0}")]
     ValidationError(String),
 
+    /// Payload fields that broke their declared constraints
+    #[error("Validation error: {}", describe(.0))]
+    InvalidFields(Vec<FieldError>),
+
+    #[error("Quota exceeded: {message}")]
+    QuotaExceeded {
+        message: String,
+        /// Seconds until the quota resets, sent as `Retry-After`
+        retry_after: Option<u64>,
+    },
+
     #[error("Internal error: {0}")]
     InternalError(String),
 }
 
-#[derive(Serialize)]
-struct ErrorResponse {
-    error: String,
+/// Error response body
+#[derive(Serialize, ToSchema)]
+pub struct ErrorResponse {
+    pub error: String,
+    /// Each field that failed validation, for 400 responses to typed payloads
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<FieldError>,
+}
+
+/// A payload field that failed validation
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    /// Machine-readable reason, e.g. `length`, `email`, `charset` or `unknown_role`
+    pub code: String,
+    pub message: String,
+}
+
+fn describe(fields: &[FieldError]) -> String {
+    fields
+        .iter()
+        .map(|f| format!("{}: {}", f.field, f.message))
+        .collect::<Vec<_>>()
+        .join("; ")
 }
 
 impl AppError {
@@ -73,6 +107,53 @@ impl AppError {
     pub fn internal(message: impl Into<String>) -> Self {
         Self::InternalError(message.into())
     }
+
+    pub fn quota_exceeded(message: impl Into<String>, retry_after: Option<u64>) -> Self {
+        Self::QuotaExceeded {
+            message: message.into(),
+            retry_after,
+        }
+    }
+}
+
+impl From<ValidationErrors> for AppError {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut fields: Vec<FieldError> = errors
+            .errors()
+            .iter()
+            .filter_map(|(field, kind)| match kind {
+                ValidationErrorsKind::Field(errors) => Some((field, errors)),
+                _ => None,
+            })
+            .flat_map(|(field, errors)| {
+                errors.iter().map(move |e| FieldError {
+                    field: field.to_string(),
+                    code: e.code.to_string(),
+                    message: e
+                        .message
+                        .as_ref()
+                        .map(|m| m.to_string())
+                        .unwrap_or_else(|| default_message(e)),
+                })
+            })
+            .collect();
+        fields.sort_by(|a, b| a.field.cmp(&b.field));
+
+        Self::InvalidFields(fields)
+    }
+}
+
+/// Message for the built-in rules, which carry none
+fn default_message(error: &validator::ValidationError) -> String {
+    let param = |name: &str| error.params.get(name).map(|v| v.to_string());
+
+    match (error.code.as_ref(), param("min"), param("max")) {
+        ("length", Some(min), Some(max)) => format!("Must be {}-{} characters", min, max),
+        ("length", Some(min), None) => format!("Must be at least {} characters", min),
+        ("length", None, Some(max)) => format!("Must be at most {} characters", max),
+        ("email", _, _) => "Must be a valid email address".to_string(),
+        (code, _, _) => format!("Failed the {} check", code),
+    }
 }
 
 impl From<sqlx::Error> for AppError {
@@ -83,24 +164,42 @@ impl From<sqlx::Error> for AppError {
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let status = match self {
+        let status = match &self {
             AppError::TimeoutError(_) => StatusCode::GATEWAY_TIMEOUT,
             AppError::HttpError { status, .. } => {
-                StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY)
+                StatusCode::from_u16(*status).unwrap_or(StatusCode::BAD_GATEWAY)
             }
             AppError::NetworkError(_) => StatusCode::BAD_GATEWAY,
             AppError::ParseError(_) => StatusCode::BAD_REQUEST,
             AppError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::AuthError(_) => StatusCode::UNAUTHORIZED,
             AppError::AuthorizationError(_) => StatusCode::FORBIDDEN,
-            AppError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            AppError::ValidationError(_) | AppError::InvalidFields(_) => StatusCode::BAD_REQUEST,
+            AppError::QuotaExceeded { .. } => StatusCode::TOO_MANY_REQUESTS,
             AppError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
+        let retry_after = match &self {
+            AppError::QuotaExceeded { retry_after, .. } => *retry_after,
+            _ => None,
+        };
+
+        let details = match &self {
+            AppError::InvalidFields(fields) => fields.clone(),
+            _ => Vec::new(),
+        };
+
         let body = Json(ErrorResponse {
             error: self.to_string(),
+            details,
         });
 
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+        if let Some(seconds) = retry_after {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, seconds.to_string().parse().unwrap());
+        }
+        response
     }
 }
@@ -1,4 +1,7 @@
+pub mod client_ip;
 pub mod errors;
 pub mod http_client;
 pub mod models;
+pub mod policy;
 pub mod tracing;
+pub mod validation;
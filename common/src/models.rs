@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::validation;
 
 /// City data aggregation response
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -17,6 +20,18 @@ pub struct WeatherData {
     pub condition: String,
     pub humidity: Option<f64>,
     pub wind_speed: Option<f64>,
+    /// Daily forecast, present when forecast days were requested
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub forecast: Vec<DailyForecast>,
+}
+
+/// One day of a weather forecast
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct DailyForecast {
+    pub date: String,
+    pub temperature_max: f64,
+    pub temperature_min: f64,
+    pub condition: String,
 }
 
 /// Time information from external API
@@ -45,18 +60,68 @@ pub struct ResponseSummary {
 /// JWT Claims structure
 #[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
 pub struct Claims {
-    pub sub: String, // user_id
-    pub exp: usize,  // expiration timestamp
-    pub role: String,
+    pub sub: String,      // user_id
+    pub iss: String,      // issuing service
+    pub aud: Vec<String>, // services the token is valid for
+    pub exp: usize,       // expiration timestamp
+    pub iat: usize,       // issued-at timestamp
+    pub nbf: usize,       // not-before timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>, // login session the token belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>, // admin acting on the subject's behalf
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org: Option<String>, // active organization id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org_role: Option<String>, // role within the active organization
+    pub role: String,     // effective role: platform role for super admins, otherwise org role
     pub permissions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quota: Option<Quota>, // usage limits enforced by weather-service
+}
+
+/// Usage limits of a user; a missing limit is unlimited
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, ToSchema)]
+pub struct Quota {
+    /// Weather requests per UTC day; an aggregate request counts as one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_day: Option<i32>,
+    /// Cities in a single aggregate request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cities: Option<i32>,
+    /// Days of forecast in a single weather request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forecast_days: Option<i32>,
+}
+
+/// Actor claim of an impersonation token (RFC 8693)
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct Actor {
+    pub sub: String, // impersonating admin's user_id
 }
 
 /// User creation request
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
 pub struct CreateUserRequest {
+    /// ASCII letters, digits and `.`, `_`, `-`, `@`, starting with a letter or digit
+    #[validate(length(min = 1, max = 64), custom(function = "validation::username"))]
+    #[schema(
+        min_length = 1,
+        max_length = 64,
+        pattern = "^[A-Za-z0-9][A-Za-z0-9._@-]*$"
+    )]
     pub username: String,
+    #[validate(email, length(max = 255))]
+    #[schema(format = Email, max_length = 255)]
     pub email: String,
+    /// bcrypt only reads the first 72 bytes
+    #[validate(custom(function = "validation::password"))]
+    #[schema(format = Password, min_length = 8, max_length = 72)]
     pub password: String,
+    /// A role granted permissions in `role_permissions`, such as `user`,
+    /// `admin` or `super_admin`; defaults to `user`. Ignored on
+    /// self-registration, which always creates a `user`.
+    #[schema(example = "user")]
     pub role: Option<String>,
 }
 
@@ -69,18 +134,446 @@ pub struct UserResponse {
     pub role: String,
     pub created_at: String,
     pub updated_at: String,
+    pub last_login_at: Option<String>,
+    pub last_login_ip: Option<String>,
+    pub is_active: bool,
 }
 
 /// Login request
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
 pub struct LoginRequest {
+    #[validate(length(min = 1, max = 255))]
+    #[schema(min_length = 1, max_length = 255)]
     pub username: String,
+    #[validate(length(min = 1, max = 1024))]
+    #[schema(format = Password, min_length = 1, max_length = 1024)]
     pub password: String,
+    /// Restrict the token to a single service audience (defaults to all)
+    pub audience: Option<String>,
+    /// Slug of the organization to sign in to (defaults to the oldest membership)
+    pub organization: Option<String>,
+    /// Set the tokens as HttpOnly cookies instead of returning them
+    pub cookie: Option<bool>,
 }
 
 /// Login response
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct LoginResponse {
+    /// Omitted when the token was set as a cookie
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub token: String,
+    /// Omitted when the token was set as a cookie
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub refresh_token: String,
     pub user: UserResponse,
 }
+
+/// Refresh token exchange request
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RefreshRequest {
+    /// Read from the refresh cookie when omitted
+    #[serde(default)]
+    pub refresh_token: String,
+    /// Restrict the new token to a single service audience (defaults to all)
+    pub audience: Option<String>,
+}
+
+/// Returned with 202 when a login must be confirmed with a one-time code
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LoginChallengeResponse {
+    pub challenge_id: String,
+    /// How the code was delivered, currently always `email`
+    pub method: String,
+    pub expires_at: String,
+}
+
+/// Finish a held login with the code sent to the user
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct VerifyLoginRequest {
+    pub challenge_id: String,
+    pub code: String,
+    /// Set the tokens as HttpOnly cookies instead of returning them
+    pub cookie: Option<bool>,
+}
+
+/// Returned with 202 when a login is held until the current terms of service are accepted
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ConsentRequiredResponse {
+    pub challenge_id: String,
+    /// Proves the holder passed the login; sent back with the acceptance
+    pub consent_token: String,
+    pub terms: TermsResponse,
+    pub expires_at: String,
+}
+
+/// Accept the terms of service to finish a held login
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AcceptTermsRequest {
+    pub challenge_id: String,
+    pub consent_token: String,
+    /// The version being accepted, which must still be the current one
+    pub version: String,
+    /// Set the tokens as HttpOnly cookies instead of returning them
+    pub cookie: Option<bool>,
+}
+
+/// A version of the terms of service
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TermsResponse {
+    pub id: String,
+    pub version: String,
+    pub content: String,
+    pub published_at: String,
+}
+
+/// Publish a new version of the terms of service, which becomes current at once
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct PublishTermsRequest {
+    #[validate(length(min = 1, max = 50))]
+    #[schema(min_length = 1, max_length = 50, example = "2026-01")]
+    pub version: String,
+    #[validate(length(min = 1))]
+    #[schema(min_length = 1)]
+    pub content: String,
+}
+
+/// A version of the terms of service and how many users accepted it
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TermsSummaryResponse {
+    pub id: String,
+    pub version: String,
+    pub published_at: String,
+    /// Whether this is the version users must accept to sign in
+    pub current: bool,
+    pub accepted: i64,
+    /// Users who have not accepted it
+    pub pending: i64,
+}
+
+/// Whether a user accepted a version of the terms of service
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TermsAcceptanceResponse {
+    pub user_id: String,
+    pub username: String,
+    pub version: String,
+    /// `None` while the user has not accepted it
+    pub accepted_at: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+/// A request to run through the access policy without performing it
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PolicyExplainRequest {
+    pub method: String,
+    /// Path with an optional query string, e.g. `/api/aggregate?city=London`
+    pub path: String,
+    /// Claims to evaluate as (defaults to the caller's own)
+    #[schema(value_type = Option<Object>)]
+    pub principal: Option<serde_json::Value>,
+}
+
+/// Request for an emailed sign-in link
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MagicLinkRequest {
+    pub email: String,
+}
+
+/// Sign in with the token from an emailed link
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RedeemMagicLinkRequest {
+    pub token: String,
+    /// Restrict the token to a single service audience (defaults to all)
+    pub audience: Option<String>,
+    /// Slug of the organization to sign in to (defaults to the oldest membership)
+    pub organization: Option<String>,
+    /// Set the tokens as HttpOnly cookies instead of returning them
+    pub cookie: Option<bool>,
+}
+
+/// An active login session
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SessionResponse {
+    pub id: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: String,
+    pub last_seen_at: String,
+    pub expires_at: String,
+    /// Whether this is the session the request was made with
+    pub current: bool,
+    /// Admin who opened the session through impersonation
+    pub impersonated_by: Option<String>,
+}
+
+/// Short-lived token letting an admin act as another user
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ImpersonationResponse {
+    pub token: String,
+    pub session_id: String,
+    pub expires_at: String,
+    pub user: UserResponse,
+}
+
+/// Password change request
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct ChangePasswordRequest {
+    #[validate(length(min = 1))]
+    #[schema(format = Password, min_length = 1)]
+    pub current_password: String,
+    /// bcrypt only reads the first 72 bytes
+    #[validate(custom(function = "validation::password"))]
+    #[schema(format = Password, min_length = 8, max_length = 72)]
+    pub new_password: String,
+}
+
+/// Returned with 202 when a login is held until an expired password is replaced
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PasswordChangeRequiredResponse {
+    pub challenge_id: String,
+    /// Proves the holder passed the login; sent back with the new password
+    pub change_token: String,
+    /// When the password expired
+    pub password_expired_at: String,
+    pub expires_at: String,
+}
+
+/// Replace an expired password to finish a held login
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct ExpiredPasswordChangeRequest {
+    pub challenge_id: String,
+    pub change_token: String,
+    /// bcrypt only reads the first 72 bytes
+    #[validate(custom(function = "validation::password"))]
+    #[schema(format = Password, min_length = 8, max_length = 72)]
+    pub new_password: String,
+    /// Set the tokens as HttpOnly cookies instead of returning them
+    pub cookie: Option<bool>,
+}
+
+/// Audit log entry
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntryResponse {
+    pub id: String,
+    pub actor_id: Option<String>,
+    pub action: String,
+    pub target_user_id: Option<String>,
+    pub ip_address: Option<String>,
+    #[schema(value_type = Object)]
+    pub details: serde_json::Value,
+    pub created_at: String,
+}
+
+/// Organization (tenant)
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OrganizationResponse {
+    pub id: String,
+    pub name: String,
+    pub slug: String,
+    pub created_at: String,
+}
+
+/// Organization creation request
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreateOrganizationRequest {
+    pub name: String,
+    pub slug: String,
+}
+
+/// A user's membership of an organization
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MembershipResponse {
+    pub organization_id: String,
+    pub slug: String,
+    pub name: String,
+    pub role: String,
+}
+
+/// Role assignment request, used for user and membership roles
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RoleRequest {
+    /// A role granted permissions in `role_permissions`, such as `user`,
+    /// `admin` or `super_admin`
+    #[schema(example = "admin")]
+    pub role: String,
+}
+
+/// Group of users within an organization
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GroupResponse {
+    pub id: String,
+    pub organization_id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub permissions: Vec<String>,
+    pub created_at: String,
+}
+
+/// Group creation request
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreateGroupRequest {
+    pub name: String,
+    pub description: Option<String>,
+    /// Organization to create the group in; super admins only, defaults to the admin's organization
+    pub organization_id: Option<String>,
+}
+
+/// Replacement set of permissions granted to a group
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GroupPermissionsRequest {
+    pub permissions: Vec<String>,
+}
+
+/// Outcome of importing one row
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ImportRowResult {
+    /// Line of the input the row starts on
+    pub line: usize,
+    pub username: Option<String>,
+    /// `created`, `valid` (dry run) or `error`
+    pub status: String,
+    pub user_id: Option<String>,
+    pub error: Option<String>,
+    pub invited: bool,
+}
+
+/// Bulk user import report
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub rows: Vec<ImportRowResult>,
+}
+
+/// Pending invitation to join an organization
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InvitationResponse {
+    pub id: String,
+    pub organization_id: String,
+    pub email: String,
+    pub role: String,
+    pub invited_by: Option<String>,
+    pub created_at: String,
+    pub expires_at: String,
+}
+
+/// Invitation request
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreateInvitationRequest {
+    pub email: String,
+    /// Role the invitee receives, defaults to `user`
+    pub role: Option<String>,
+    /// Organization to invite into; super admins only, defaults to the admin's organization
+    pub organization_id: Option<String>,
+}
+
+/// Redeem an invitation by choosing credentials
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct AcceptInvitationRequest {
+    pub token: String,
+    /// ASCII letters, digits and `.`, `_`, `-`, `@`, starting with a letter or digit
+    #[validate(length(min = 1, max = 64), custom(function = "validation::username"))]
+    #[schema(
+        min_length = 1,
+        max_length = 64,
+        pattern = "^[A-Za-z0-9][A-Za-z0-9._@-]*$"
+    )]
+    pub username: String,
+    /// bcrypt only reads the first 72 bytes
+    #[validate(custom(function = "validation::password"))]
+    #[schema(format = Password, min_length = 8, max_length = 72)]
+    pub password: String,
+}
+
+/// Everything the auth service stores about a user
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UserDataExport {
+    pub exported_at: String,
+    pub user: UserResponse,
+    pub memberships: Vec<MembershipResponse>,
+    pub groups: Vec<GroupResponse>,
+    /// Every session, including revoked and expired ones
+    pub sessions: Vec<SessionResponse>,
+    /// Audit entries the user performed or was the target of
+    pub audit_log: Vec<AuditEntryResponse>,
+    /// Invitations the user sent or accepted
+    pub invitations: Vec<InvitationResponse>,
+    /// Versions of the terms of service the user accepted
+    pub terms_acceptances: Vec<TermsAcceptanceResponse>,
+}
+
+/// Confirmation required to erase one's own account
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct EraseAccountRequest {
+    pub password: String,
+}
+
+/// A quota assigned to a role or to a single user
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct QuotaResponse {
+    pub role: Option<String>,
+    pub user_id: Option<String>,
+    #[serde(flatten)]
+    pub quota: Quota,
+    pub updated_at: String,
+}
+
+/// Requests a user has made against their daily quota
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct QuotaUsage {
+    pub requests_today: u32,
+    pub requests_per_day: Option<i32>,
+    /// Start of the next UTC day, when the count resets
+    pub resets_at: String,
+}
+
+/// A user's quota and current usage
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UsageResponse {
+    /// The quota new tokens carry; null when unlimited
+    pub quota: Option<Quota>,
+    /// Counters reported by the weather service; null when it is not configured or unreachable
+    pub usage: Option<QuotaUsage>,
+}
+
+/// Subscribe a URL to user lifecycle events
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreateWebhookRequest {
+    /// HTTP(S) endpoint events are POSTed to
+    pub url: String,
+    /// Events to receive: `user.created`, `user.deleted`, `user.role_changed`
+    pub events: Vec<String>,
+    /// Organization whose users the subscription covers; super admins only,
+    /// defaults to the admin's organization and to every user for super admins
+    pub organization_id: Option<String>,
+}
+
+/// A webhook subscription
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WebhookResponse {
+    pub id: String,
+    pub organization_id: Option<String>,
+    pub url: String,
+    pub events: Vec<String>,
+    /// Key payloads are signed with; only returned when the subscription is created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    pub created_at: String,
+}
+
+/// One event queued for a webhook and how delivering it went
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WebhookDeliveryResponse {
+    pub id: String,
+    pub event: String,
+    /// `pending`, `delivered` or `dead`
+    pub status: String,
+    pub attempts: i32,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub next_attempt_at: Option<String>,
+    pub created_at: String,
+    pub delivered_at: Option<String>,
+    pub payload: serde_json::Value,
+}
@@ -0,0 +1,274 @@
+use serde::Deserialize;
+use serde_json::Value;
+use std::cmp::Ordering;
+
+/// A value in a condition: a literal, or derived from the evaluation context
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Operand {
+    /// Dotted path into the context, e.g. `principal.role` or `request.query.city`
+    Var {
+        var: String,
+    },
+    /// Number of elements in an array, characters in a string, 0 for null
+    Count {
+        count: Box<Operand>,
+    },
+    /// Lowercase string, or array of lowercased strings
+    Lower {
+        lower: Box<Operand>,
+    },
+    /// Entry of an object by key, e.g. a region's cities from the policy's `data`
+    Lookup {
+        lookup: (Box<Operand>, Box<Operand>),
+    },
+    Literal(Value),
+}
+
+impl Operand {
+    fn resolve(&self, context: &Value) -> Value {
+        match self {
+            Self::Var { var } => var
+                .split('.')
+                .try_fold(context, |value, key| match value {
+                    Value::Object(map) => map.get(key),
+                    Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+                    _ => None,
+                })
+                .cloned()
+                .unwrap_or(Value::Null),
+            Self::Count { count } => match count.resolve(context) {
+                Value::Array(items) => items.len().into(),
+                Value::String(s) => s.chars().count().into(),
+                Value::Object(map) => map.len().into(),
+                Value::Null => 0.into(),
+                _ => 1.into(),
+            },
+            Self::Lower { lower } => match lower.resolve(context) {
+                Value::String(s) => s.to_lowercase().into(),
+                Value::Array(items) => items
+                    .into_iter()
+                    .map(|item| match item {
+                        Value::String(s) => s.to_lowercase().into(),
+                        other => other,
+                    })
+                    .collect(),
+                other => other,
+            },
+            Self::Lookup {
+                lookup: (table, key),
+            } => {
+                let key = match key.resolve(context) {
+                    Value::String(s) => s,
+                    Value::Null => return Value::Null,
+                    other => other.to_string(),
+                };
+                table
+                    .resolve(context)
+                    .get(&key)
+                    .cloned()
+                    .unwrap_or(Value::Null)
+            }
+            Self::Literal(value) => value.clone(),
+        }
+    }
+}
+
+/// A boolean test over the evaluation context, written as a single-key object
+/// such as `{"eq": [{"var": "principal.role"}, "analyst"]}`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+    Not(Box<Condition>),
+    Eq(Operand, Operand),
+    Ne(Operand, Operand),
+    Lt(Operand, Operand),
+    Le(Operand, Operand),
+    Gt(Operand, Operand),
+    Ge(Operand, Operand),
+    /// The left value is an element of the right array; an array on the left
+    /// must be a subset of it
+    In(Operand, Operand),
+    /// The left array has the right value as an element
+    Contains(Operand, Operand),
+}
+
+impl Condition {
+    pub fn evaluate(&self, context: &Value) -> bool {
+        match self {
+            Self::All(conditions) => conditions.iter().all(|c| c.evaluate(context)),
+            Self::Any(conditions) => conditions.iter().any(|c| c.evaluate(context)),
+            Self::Not(condition) => !condition.evaluate(context),
+            Self::Eq(a, b) => a.resolve(context) == b.resolve(context),
+            Self::Ne(a, b) => a.resolve(context) != b.resolve(context),
+            Self::Lt(a, b) => compare(a, b, context) == Some(Ordering::Less),
+            Self::Le(a, b) => compare(a, b, context).is_some_and(Ordering::is_le),
+            Self::Gt(a, b) => compare(a, b, context) == Some(Ordering::Greater),
+            Self::Ge(a, b) => compare(a, b, context).is_some_and(Ordering::is_ge),
+            Self::In(needle, haystack) => {
+                let Value::Array(haystack) = haystack.resolve(context) else {
+                    return false;
+                };
+                match needle.resolve(context) {
+                    Value::Array(needles) => needles.iter().all(|n| haystack.contains(n)),
+                    needle => haystack.contains(&needle),
+                }
+            }
+            Self::Contains(haystack, needle) => match haystack.resolve(context) {
+                Value::Array(items) => items.contains(&needle.resolve(context)),
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Order two numbers or two strings; anything else is incomparable
+fn compare(a: &Operand, b: &Operand, context: &Value) -> Option<Ordering> {
+    match (a.resolve(context), b.resolve(context)) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(&b)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn context() -> Value {
+        json!({
+            "principal": { "role": "analyst", "org": "org-1", "permissions": ["read", "write"] },
+            "request": { "query": { "city": ["London", "Paris"] }, "params": { "city": "PARIS" } },
+            "data": { "regions": { "org-1": ["london", "paris"] } }
+        })
+    }
+
+    fn holds(condition: Value) -> bool {
+        serde_json::from_value::<Condition>(condition)
+            .expect("condition should parse")
+            .evaluate(&context())
+    }
+
+    #[test]
+    fn vars_resolve_dotted_paths_and_array_indices() {
+        assert!(holds(
+            json!({ "eq": [{ "var": "principal.role" }, "analyst"] })
+        ));
+        assert!(holds(
+            json!({ "eq": [{ "var": "request.query.city.1" }, "Paris"] })
+        ));
+        assert!(!holds(
+            json!({ "eq": [{ "var": "principal.role" }, "Analyst"] })
+        ));
+    }
+
+    #[test]
+    fn unknown_attributes_resolve_to_null() {
+        assert!(holds(
+            json!({ "eq": [{ "var": "principal.missing" }, null] })
+        ));
+        assert!(holds(
+            json!({ "eq": [{ "var": "principal.role.deeper" }, null] })
+        ));
+        assert!(!holds(
+            json!({ "eq": [{ "var": "principal.missing" }, "analyst"] })
+        ));
+        // so a negative test on a missing attribute holds
+        assert!(holds(
+            json!({ "ne": [{ "var": "principal.missing" }, "analyst"] })
+        ));
+        assert!(holds(
+            json!({ "eq": [{ "count": { "var": "principal.missing" } }, 0] })
+        ));
+    }
+
+    #[test]
+    fn ordering_needs_two_numbers_or_two_strings() {
+        assert!(holds(
+            json!({ "gt": [{ "count": { "var": "request.query.city" } }, 1] })
+        ));
+        assert!(holds(
+            json!({ "le": [{ "count": { "var": "request.query.city" } }, 2] })
+        ));
+        assert!(!holds(
+            json!({ "lt": [{ "count": { "var": "request.query.city" } }, 2] })
+        ));
+        assert!(holds(json!({ "lt": ["a", "b"] })));
+        // Incomparable values satisfy neither side
+        assert!(!holds(json!({ "lt": [{ "var": "principal.missing" }, 1] })));
+        assert!(!holds(json!({ "ge": [{ "var": "principal.missing" }, 1] })));
+        assert!(!holds(json!({ "gt": ["10", 1] })));
+    }
+
+    #[test]
+    fn in_and_contains() {
+        assert!(holds(
+            json!({ "in": [{ "var": "principal.role" }, ["admin", "analyst"]] })
+        ));
+        assert!(!holds(
+            json!({ "in": [{ "var": "principal.role" }, ["admin"]] })
+        ));
+        // An array on the left must be a subset
+        assert!(holds(
+            json!({ "in": [{ "lower": { "var": "request.query.city" } }, ["london", "paris", "rome"]] })
+        ));
+        assert!(!holds(
+            json!({ "in": [{ "var": "request.query.city" }, ["London"]] })
+        ));
+        // Nothing is in a value that is not an array
+        assert!(!holds(
+            json!({ "in": [{ "var": "principal.role" }, { "var": "principal.missing" }] })
+        ));
+
+        assert!(holds(
+            json!({ "contains": [{ "var": "principal.permissions" }, "write"] })
+        ));
+        assert!(!holds(
+            json!({ "contains": [{ "var": "principal.permissions" }, "admin"] })
+        ));
+        assert!(!holds(
+            json!({ "contains": [{ "var": "principal.role" }, "analyst"] })
+        ));
+    }
+
+    #[test]
+    fn lower_and_lookup() {
+        assert!(holds(
+            json!({ "eq": [{ "lower": { "var": "request.params.city" } }, "paris"] })
+        ));
+        assert!(holds(json!({
+            "in": [
+                { "lower": { "var": "request.params.city" } },
+                { "lookup": [{ "var": "data.regions" }, { "var": "principal.org" }] }
+            ]
+        })));
+        // A missing key looks up nothing
+        assert!(holds(json!({
+            "eq": [{ "lookup": [{ "var": "data.regions" }, { "var": "principal.missing" }] }, null]
+        })));
+    }
+
+    #[test]
+    fn all_any_and_not() {
+        let role = json!({ "eq": [{ "var": "principal.role" }, "analyst"] });
+        let other = json!({ "eq": [{ "var": "principal.role" }, "admin"] });
+
+        assert!(holds(json!({ "all": [role, role] })));
+        assert!(!holds(json!({ "all": [role, other] })));
+        assert!(holds(json!({ "any": [other, role] })));
+        assert!(!holds(json!({ "any": [other, other] })));
+        assert!(holds(json!({ "not": other })));
+        // Empty lists are vacuously true and false
+        assert!(holds(json!({ "all": [] })));
+        assert!(!holds(json!({ "any": [] })));
+    }
+
+    #[test]
+    fn unknown_operators_are_rejected() {
+        assert!(serde_json::from_value::<Condition>(json!({ "matches": ["a", "b"] })).is_err());
+        assert!(serde_json::from_value::<Condition>(json!({ "eq": ["a"] })).is_err());
+    }
+}
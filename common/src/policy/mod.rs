@@ -0,0 +1,637 @@
+//! Declarative attribute-based access policies.
+//!
+//! A policy file is JSON holding a list of rules. Each rule names the
+//! requests it covers (a path pattern and methods) and a condition over the
+//! caller's token claims, the request and the policy's own `data`:
+//!
+//! ```json
+//! {
+//!   "default": "allow",
+//!   "data": { "max_cities": { "analyst": 5 } },
+//!   "rules": [{
+//!     "id": "analyst-aggregate-limit",
+//!     "description": "Analysts may aggregate at most 5 cities",
+//!     "effect": "deny",
+//!     "resource": "/api/aggregate",
+//!     "methods": ["GET"],
+//!     "condition": { "all": [
+//!       { "eq": [{ "var": "principal.role" }, "analyst"] },
+//!       { "gt": [{ "count": { "var": "request.query.city" } }, 5] }
+//!     ]}
+//!   }]
+//! }
+//! ```
+//!
+//! Conditions see `principal` (the claims, or null without a token),
+//! `request.method`, `request.path`, `request.params` (segments captured by
+//! the resource pattern), `request.query` (every query parameter as an array)
+//! and `data`. A matching `deny` rule always wins; otherwise a matching
+//! `allow` rule allows, and requests no rule matches get the default.
+
+mod condition;
+
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{Method, Uri},
+    middleware::Next,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::path::Path;
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::errors::AppError;
+use crate::models::Claims;
+
+pub use condition::{Condition, Operand};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Effect {
+    #[default]
+    Allow,
+    Deny,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Rule {
+    pub id: String,
+    pub description: Option<String>,
+    pub effect: Effect,
+    /// Path pattern: `{name}` captures one segment and a trailing `*` matches
+    /// the rest. The rule covers every path when omitted.
+    pub resource: Option<String>,
+    /// Methods the rule covers; every method when empty
+    #[serde(default)]
+    pub methods: Vec<String>,
+    /// The rule matches whenever it covers the request when omitted
+    pub condition: Option<Condition>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Policy {
+    /// Effect for requests no rule matches
+    #[serde(default)]
+    pub default: Effect,
+    /// Lookup tables conditions can reference as `data.*`
+    #[serde(default)]
+    pub data: Value,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, Error)]
+pub enum PolicyError {
+    #[error("Failed to read policy file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Failed to parse policy file: {0}")]
+    Parse(#[from] serde_json::Error),
+
+    #[error("Invalid policy: {0}")]
+    Invalid(String),
+}
+
+/// Whether denials are enforced or only logged
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PolicyMode {
+    Enforce,
+    /// Denials are logged with their explanation and the request proceeds
+    DryRun,
+}
+
+/// The request a decision is made about
+pub struct AccessRequest {
+    pub method: String,
+    pub path: String,
+    pub query: BTreeMap<String, Vec<String>>,
+    /// The caller's claims, null for anonymous requests
+    pub principal: Value,
+}
+
+impl AccessRequest {
+    pub fn new(method: &Method, uri: &Uri, claims: Option<&Claims>) -> Self {
+        let principal = claims
+            .and_then(|c| serde_json::to_value(c).ok())
+            .unwrap_or(Value::Null);
+        Self::from_parts(method.as_str(), uri.path(), uri.query(), principal)
+    }
+
+    pub fn from_parts(method: &str, path: &str, query: Option<&str>, principal: Value) -> Self {
+        let mut parameters: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for pair in query
+            .unwrap_or_default()
+            .split('&')
+            .filter(|p| !p.is_empty())
+        {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            parameters
+                .entry(decode(key))
+                .or_default()
+                .push(decode(value));
+        }
+
+        Self {
+            method: method.to_uppercase(),
+            path: path.to_string(),
+            query: parameters,
+            principal,
+        }
+    }
+}
+
+/// Percent-decode a query component, where `+` stands for a space
+fn decode(component: &str) -> String {
+    let component = component.replace('+', " ");
+    urlencoding::decode(&component)
+        .map(|s| s.into_owned())
+        .unwrap_or(component)
+}
+
+/// The outcome for one request
+pub struct Decision {
+    pub effect: Effect,
+    /// The rule that decided, `None` when the default applied
+    pub rule: Option<String>,
+    pub description: Option<String>,
+}
+
+/// How one rule related to a request
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RuleTrace {
+    pub id: String,
+    pub effect: Effect,
+    /// The rule's resource pattern and methods cover the request
+    pub applies: bool,
+    /// The rule applies and its condition holds
+    pub matched: bool,
+    /// Segments the resource pattern captured
+    pub params: BTreeMap<String, String>,
+}
+
+/// Every rule's verdict on a request, for debugging denials
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Explanation {
+    pub effect: Effect,
+    /// The rule that decided, null when the default applied
+    pub deciding_rule: Option<String>,
+    /// The context conditions were evaluated against
+    #[schema(value_type = Object)]
+    pub context: Value,
+    pub rules: Vec<RuleTrace>,
+}
+
+pub struct PolicyEngine {
+    policy: Policy,
+    mode: PolicyMode,
+}
+
+impl PolicyEngine {
+    pub fn new(policy: Policy, mode: PolicyMode) -> Result<Self, PolicyError> {
+        let mut ids = HashSet::new();
+        for rule in &policy.rules {
+            if !ids.insert(rule.id.as_str()) {
+                return Err(PolicyError::Invalid(format!(
+                    "duplicate rule id '{}'",
+                    rule.id
+                )));
+            }
+            if let Some(resource) = &rule.resource
+                && !resource.starts_with('/')
+            {
+                return Err(PolicyError::Invalid(format!(
+                    "resource of rule '{}' must start with '/'",
+                    rule.id
+                )));
+            }
+        }
+
+        Ok(Self { policy, mode })
+    }
+
+    pub fn load(path: impl AsRef<Path>, mode: PolicyMode) -> Result<Self, PolicyError> {
+        let policy = serde_json::from_slice(&std::fs::read(path)?)?;
+        Self::new(policy, mode)
+    }
+
+    /// Engine for `POLICY_FILE` in `POLICY_MODE` (`enforce` or `dry-run`).
+    /// Without a policy file every request is allowed.
+    pub fn from_env() -> Result<Self, PolicyError> {
+        let mode = match env::var("POLICY_MODE").as_deref() {
+            Ok("dry-run") => PolicyMode::DryRun,
+            Ok("enforce") | Err(_) => PolicyMode::Enforce,
+            Ok(other) => {
+                return Err(PolicyError::Invalid(format!(
+                    "unknown POLICY_MODE '{}'",
+                    other
+                )));
+            }
+        };
+
+        match env::var("POLICY_FILE").ok().filter(|s| !s.is_empty()) {
+            Some(path) => {
+                let engine = Self::load(&path, mode)?;
+                info!(path = %path, rules = engine.policy.rules.len(), ?mode, "Access policy loaded");
+                Ok(engine)
+            }
+            None => Self::new(Policy::default(), mode),
+        }
+    }
+
+    fn context(&self, request: &AccessRequest, params: &BTreeMap<String, String>) -> Value {
+        serde_json::json!({
+            "principal": request.principal,
+            "request": {
+                "method": request.method,
+                "path": request.path,
+                "params": params,
+                "query": request.query,
+            },
+            "data": self.policy.data,
+        })
+    }
+
+    /// Evaluate every rule against `request`
+    pub fn explain(&self, request: &AccessRequest) -> Explanation {
+        let mut traces = Vec::with_capacity(self.policy.rules.len());
+        let mut context = self.context(request, &BTreeMap::new());
+
+        for rule in &self.policy.rules {
+            let params = covers(rule, request);
+            let applies = params.is_some();
+            let params = params.unwrap_or_default();
+
+            let matched = applies && {
+                set_params(&mut context, &params);
+                rule.condition.as_ref().is_none_or(|c| c.evaluate(&context))
+            };
+
+            traces.push(RuleTrace {
+                id: rule.id.clone(),
+                effect: rule.effect,
+                applies,
+                matched,
+                params,
+            });
+        }
+        set_params(&mut context, &BTreeMap::new());
+
+        let deciding = [Effect::Deny, Effect::Allow]
+            .into_iter()
+            .find_map(|effect| traces.iter().find(|t| t.matched && t.effect == effect));
+
+        Explanation {
+            effect: deciding.map_or(self.policy.default, |t| t.effect),
+            deciding_rule: deciding.map(|t| t.id.clone()),
+            context,
+            rules: traces,
+        }
+    }
+
+    pub fn decide(&self, request: &AccessRequest) -> Decision {
+        let explanation = self.explain(request);
+        let description = explanation.deciding_rule.as_ref().and_then(|id| {
+            self.policy
+                .rules
+                .iter()
+                .find(|r| &r.id == id)
+                .and_then(|r| r.description.clone())
+        });
+
+        Decision {
+            effect: explanation.effect,
+            rule: explanation.deciding_rule,
+            description,
+        }
+    }
+
+    /// `Ok` when `request` is allowed. In dry-run mode denials are logged
+    /// with their explanation instead of returned.
+    pub fn authorize(&self, request: &AccessRequest) -> Result<(), AppError> {
+        if self.policy.rules.is_empty() && self.policy.default == Effect::Allow {
+            return Ok(());
+        }
+
+        let decision = self.decide(request);
+        if decision.effect == Effect::Allow {
+            return Ok(());
+        }
+
+        let reason = match (&decision.rule, &decision.description) {
+            (Some(rule), Some(description)) => format!("{} ({})", description, rule),
+            (Some(rule), None) => format!("rule '{}'", rule),
+            (None, _) => "default policy".to_string(),
+        };
+
+        if self.mode == PolicyMode::DryRun {
+            let explanation = serde_json::to_string(&self.explain(request)).unwrap_or_default();
+            warn!(
+                method = %request.method,
+                path = %request.path,
+                reason = %reason,
+                explanation = %explanation,
+                "Request would be denied by access policy (dry run)"
+            );
+            return Ok(());
+        }
+
+        Err(AppError::authorization(format!(
+            "Denied by access policy: {}",
+            reason
+        )))
+    }
+}
+
+/// Replace `request.params` in an evaluation context
+fn set_params(context: &mut Value, params: &BTreeMap<String, String>) {
+    let params: Map<String, Value> = params
+        .iter()
+        .map(|(k, v)| (k.clone(), Value::String(v.clone())))
+        .collect();
+    context["request"]["params"] = Value::Object(params);
+}
+
+/// The segments `rule` captures from the request, or `None` if it does not cover it
+fn covers(rule: &Rule, request: &AccessRequest) -> Option<BTreeMap<String, String>> {
+    if !rule.methods.is_empty()
+        && !rule
+            .methods
+            .iter()
+            .any(|m| m.eq_ignore_ascii_case(&request.method))
+    {
+        return None;
+    }
+
+    let Some(pattern) = &rule.resource else {
+        return Some(BTreeMap::new());
+    };
+
+    let mut params = BTreeMap::new();
+    let mut segments = request.path.trim_end_matches('/').split('/');
+    for expected in pattern.trim_end_matches('/').split('/') {
+        if expected == "*" {
+            return Some(params);
+        }
+        let segment = segments.next()?;
+        match expected.strip_prefix('{').and_then(|e| e.strip_suffix('}')) {
+            Some(name) => {
+                let value = urlencoding::decode(segment).map_or(segment.into(), |s| s.into_owned());
+                params.insert(name.to_string(), value);
+            }
+            None if expected == segment => {}
+            None => return None,
+        }
+    }
+
+    segments.next().is_none().then_some(params)
+}
+
+/// Middleware enforcing `engine` on every request, with the [`Claims`] an
+/// earlier authentication layer put in the request extensions
+pub async fn enforce(
+    State(engine): State<Arc<PolicyEngine>>,
+    request: Request<Body>,
+    next: Next,
+) -> Result<Response, AppError> {
+    let access = AccessRequest::new(
+        request.method(),
+        request.uri(),
+        request.extensions().get::<Claims>(),
+    );
+    engine.authorize(&access)?;
+
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn engine(policy: Value) -> PolicyEngine {
+        PolicyEngine::new(
+            serde_json::from_value(policy).expect("policy should parse"),
+            PolicyMode::Enforce,
+        )
+        .expect("policy should be valid")
+    }
+
+    fn request(method: &str, uri: &str, principal: Value) -> AccessRequest {
+        let (path, query) = uri
+            .split_once('?')
+            .map_or((uri, None), |(p, q)| (p, Some(q)));
+        AccessRequest::from_parts(method, path, query, principal)
+    }
+
+    fn analyst() -> Value {
+        json!({ "sub": "u1", "role": "analyst", "org": "00000000-0000-0000-0000-000000000001" })
+    }
+
+    fn role_is(role: &str) -> Value {
+        json!({ "eq": [{ "var": "principal.role" }, role] })
+    }
+
+    #[test]
+    fn deny_wins_over_allow_regardless_of_order() {
+        let engine = engine(json!({
+            "default": "deny",
+            "rules": [
+                { "id": "allow-analysts", "effect": "allow", "condition": role_is("analyst") },
+                { "id": "deny-deletes", "effect": "deny", "methods": ["DELETE"] }
+            ]
+        }));
+
+        let get = engine.decide(&request("GET", "/api/me", analyst()));
+        assert_eq!(get.effect, Effect::Allow);
+        assert_eq!(get.rule.as_deref(), Some("allow-analysts"));
+
+        let delete = engine.decide(&request("DELETE", "/api/me", analyst()));
+        assert_eq!(delete.effect, Effect::Deny);
+        assert_eq!(delete.rule.as_deref(), Some("deny-deletes"));
+    }
+
+    #[test]
+    fn default_applies_when_no_rule_matches() {
+        let rules =
+            json!([{ "id": "allow-analysts", "effect": "allow", "condition": role_is("analyst") }]);
+        let deny_by_default = engine(json!({ "default": "deny", "rules": rules }));
+        let user = json!({ "sub": "u2", "role": "user" });
+
+        let decision = deny_by_default.decide(&request("GET", "/api/me", user.clone()));
+        assert_eq!(decision.effect, Effect::Deny);
+        assert_eq!(decision.rule, None);
+        assert!(
+            deny_by_default
+                .authorize(&request("GET", "/api/me", user))
+                .is_err()
+        );
+
+        let allow_by_default = engine(json!({ "rules": [] }));
+        assert!(
+            allow_by_default
+                .authorize(&request("GET", "/anything", Value::Null))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn roles_are_matched_exactly() {
+        let engine = engine(json!({
+            "rules": [{ "id": "no-admins", "effect": "deny", "condition": role_is("admin") }]
+        }));
+
+        for (role, effect) in [
+            ("admin", Effect::Deny),
+            ("Admin", Effect::Allow),
+            ("super_admin", Effect::Allow),
+            ("analyst", Effect::Allow),
+        ] {
+            let principal = json!({ "sub": "u", "role": role });
+            assert_eq!(
+                engine.decide(&request("GET", "/", principal)).effect,
+                effect,
+                "{}",
+                role
+            );
+        }
+    }
+
+    #[test]
+    fn anonymous_and_unknown_attributes_do_not_match_positive_conditions() {
+        let engine = engine(json!({
+            "default": "deny",
+            "rules": [
+                { "id": "allow-analysts", "effect": "allow", "condition": role_is("analyst") },
+                {
+                    "id": "deny-unless-tier",
+                    "effect": "deny",
+                    "condition": { "ne": [{ "var": "principal.tier" }, "gold"] }
+                }
+            ]
+        }));
+
+        // No token: principal is null, so nothing about it holds but negations
+        let anonymous = engine.explain(&request("GET", "/api/weather/london", Value::Null));
+        assert_eq!(anonymous.effect, Effect::Deny);
+        assert!(!anonymous.rules[0].matched);
+        assert_eq!(anonymous.deciding_rule.as_deref(), Some("deny-unless-tier"));
+
+        // A claim the token does not carry is null, and `ne` holds against it
+        let decision = engine.decide(&request("GET", "/", analyst()));
+        assert_eq!(decision.rule.as_deref(), Some("deny-unless-tier"));
+    }
+
+    #[test]
+    fn resources_and_methods_limit_what_a_rule_covers() {
+        let engine = engine(json!({
+            "rules": [
+                { "id": "city", "effect": "deny", "resource": "/api/weather/{city}", "methods": ["get"] },
+                { "id": "admin", "effect": "deny", "resource": "/api/admin/*" }
+            ]
+        }));
+
+        let explained = engine.explain(&request("GET", "/api/weather/New%20York", Value::Null));
+        assert_eq!(explained.deciding_rule.as_deref(), Some("city"));
+        assert_eq!(explained.rules[0].params["city"], "New York");
+
+        for (method, uri, rule) in [
+            ("POST", "/api/weather/london", None),
+            ("GET", "/api/weather/london/", Some("city")),
+            ("GET", "/api/weather", None),
+            ("GET", "/api/weather/london/extra", None),
+            ("DELETE", "/api/admin/users/1", Some("admin")),
+            ("GET", "/api/admin", Some("admin")),
+            ("GET", "/api/administrator", None),
+        ] {
+            let decision = engine.decide(&request(method, uri, Value::Null));
+            assert_eq!(decision.rule.as_deref(), rule, "{} {}", method, uri);
+        }
+    }
+
+    #[test]
+    fn conditions_see_query_params_and_data() {
+        let engine = engine(
+            serde_json::from_str(include_str!("../../../docs/policies/example.json")).unwrap(),
+        );
+
+        let five = "/api/aggregate?city=a&city=b&city=c&city=d&city=e";
+        assert_eq!(
+            engine.decide(&request("GET", five, analyst())).effect,
+            Effect::Allow
+        );
+        let six = format!("{}&city=f", five);
+        let decision = engine.decide(&request("GET", &six, analyst()));
+        assert_eq!(decision.rule.as_deref(), Some("analyst-aggregate-limit"));
+        assert_eq!(
+            decision.description.as_deref(),
+            Some("Analysts may aggregate at most 5 cities")
+        );
+
+        let mut user = analyst();
+        user["role"] = json!("user");
+        for (city, effect) in [("London", Effect::Allow), ("Tokyo", Effect::Deny)] {
+            let uri = format!("/api/weather/{}", city);
+            assert_eq!(
+                engine.decide(&request("GET", &uri, user.clone())).effect,
+                effect
+            );
+        }
+
+        // A user of an organization without a region may read nothing
+        user["org"] = json!("unknown");
+        let decision = engine.decide(&request("GET", "/api/weather/London", user));
+        assert_eq!(decision.effect, Effect::Deny);
+    }
+
+    #[test]
+    fn query_parameters_are_decoded_and_grouped() {
+        let request = request(
+            "get",
+            "/x?city=New+York&city=S%C3%A3o%20Paulo&flag",
+            Value::Null,
+        );
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.query["city"], ["New York", "São Paulo"]);
+        assert_eq!(request.query["flag"], [""]);
+    }
+
+    #[test]
+    fn dry_run_allows_what_it_would_deny() {
+        let policy = json!({ "default": "deny", "rules": [] });
+        let dry_run =
+            PolicyEngine::new(serde_json::from_value(policy).unwrap(), PolicyMode::DryRun).unwrap();
+
+        assert!(dry_run.authorize(&request("GET", "/", Value::Null)).is_ok());
+        assert_eq!(
+            dry_run.decide(&request("GET", "/", Value::Null)).effect,
+            Effect::Deny
+        );
+    }
+
+    #[test]
+    fn invalid_policies_are_rejected() {
+        let duplicate: Policy = serde_json::from_value(json!({
+            "rules": [{ "id": "a", "effect": "deny" }, { "id": "a", "effect": "allow" }]
+        }))
+        .unwrap();
+        assert!(PolicyEngine::new(duplicate, PolicyMode::Enforce).is_err());
+
+        let relative: Policy = serde_json::from_value(json!({
+            "rules": [{ "id": "a", "effect": "deny", "resource": "api/me" }]
+        }))
+        .unwrap();
+        assert!(PolicyEngine::new(relative, PolicyMode::Enforce).is_err());
+
+        let unknown_effect = serde_json::from_value::<Policy>(json!({
+            "rules": [{ "id": "a", "effect": "block" }]
+        }));
+        assert!(unknown_effect.is_err());
+    }
+}
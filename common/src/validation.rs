@@ -0,0 +1,69 @@
+//! Rules shared by request payloads that derive [`validator::Validate`].
+
+use std::borrow::Cow;
+use validator::ValidationError;
+
+pub const USERNAME_MAX_LENGTH: usize = 64;
+pub const PASSWORD_MIN_LENGTH: usize = 8;
+/// bcrypt only reads the first 72 bytes
+pub const PASSWORD_MAX_BYTES: usize = 72;
+
+fn error(code: &'static str, message: impl Into<Cow<'static, str>>) -> ValidationError {
+    ValidationError::new(code).with_message(message.into())
+}
+
+/// ASCII letters, digits and `.`, `_`, `-`, `@`, starting with a letter or digit
+pub fn username(value: &str) -> Result<(), ValidationError> {
+    let allowed = value.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '@'));
+
+    if allowed {
+        Ok(())
+    } else {
+        Err(error(
+            "charset",
+            "May only contain letters, digits and . _ - @, and must start with a letter or digit",
+        ))
+    }
+}
+
+/// At least [`PASSWORD_MIN_LENGTH`] characters and at most
+/// [`PASSWORD_MAX_BYTES`] bytes. Every path that sets a password checks it
+/// here, including those that do not deserialize a request payload.
+pub fn password(value: &str) -> Result<(), ValidationError> {
+    if value.chars().count() < PASSWORD_MIN_LENGTH {
+        return Err(error(
+            "length",
+            format!("Must be at least {} characters", PASSWORD_MIN_LENGTH),
+        ));
+    }
+    if value.len() > PASSWORD_MAX_BYTES {
+        return Err(error(
+            "length",
+            format!("Must be at most {} bytes", PASSWORD_MAX_BYTES),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passwords_need_eight_characters_and_fit_bcrypt() {
+        assert!(password("").is_err());
+        assert!(password("seven77").is_err());
+        assert!(password("eight888").is_ok());
+        assert!(password(&"a".repeat(72)).is_ok());
+        assert!(password(&"a".repeat(73)).is_err());
+
+        // Characters count towards the minimum, bytes towards the maximum
+        assert!(password("ééééééé").is_err());
+        assert!(password("éééééééé").is_ok());
+        assert!(password(&"é".repeat(37)).is_err());
+    }
+}
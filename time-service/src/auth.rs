@@ -0,0 +1,133 @@
+use axum::{
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts},
+};
+use common::errors::AppError;
+use common::models::Claims;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode};
+
+use crate::config::Config;
+use crate::handlers::AppState;
+
+/// Checks access tokens issued by auth-service, with the same issuer,
+/// audience and lifetime checks as the other services
+pub struct TokenValidator {
+    decoding_key: DecodingKey,
+    validation: Validation,
+}
+
+impl TokenValidator {
+    /// `None` when no `JWT_SECRET` is configured and every request is anonymous
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let secret = config.jwt_secret.as_ref()?;
+
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_issuer(&[&config.jwt_issuer]);
+        validation.set_audience(&[&config.jwt_audience]);
+        validation.set_required_spec_claims(&["exp", "nbf", "iat", "iss", "aud", "sub"]);
+        validation.validate_nbf = true;
+        validation.leeway = config.jwt_leeway_seconds;
+
+        Some(Self {
+            decoding_key: DecodingKey::from_secret(secret.as_ref()),
+            validation,
+        })
+    }
+
+    pub fn validate(&self, token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
+        decode::<Claims>(token, &self.decoding_key, &self.validation).map(|data| data.claims)
+    }
+}
+
+/// The caller's claims, or `None` for a request without a bearer token
+pub struct Principal(pub Option<Claims>);
+
+impl FromRequestParts<AppState> for Principal {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, AppError> {
+        let (Some(header), Some(validator)) = (parts.headers.get(AUTHORIZATION), &state.tokens)
+        else {
+            return Ok(Self(None));
+        };
+
+        let token = header
+            .to_str()
+            .ok()
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .ok_or_else(|| AppError::auth("Invalid Authorization header format"))?;
+
+        validator
+            .validate(token)
+            .map(|claims| Self(Some(claims)))
+            .map_err(|e| AppError::auth(format!("Invalid token: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{EncodingKey, Header, encode, errors::ErrorKind, get_current_timestamp};
+
+    const SECRET: &str = "test-secret";
+
+    fn config() -> Config {
+        Config {
+            port: 0,
+            world_time_api_url: String::new(),
+            jwt_secret: Some(SECRET.to_string()),
+            jwt_issuer: "auth-service".to_string(),
+            jwt_audience: "time-service".to_string(),
+            jwt_leeway_seconds: 60,
+        }
+    }
+
+    fn token(issuer: &str, audience: &str) -> String {
+        let now = get_current_timestamp() as usize;
+        let claims = Claims {
+            sub: "user-1".to_string(),
+            iss: issuer.to_string(),
+            aud: vec![audience.to_string()],
+            exp: now + 3600,
+            iat: now,
+            nbf: now,
+            sid: None,
+            act: None,
+            org: None,
+            org_role: None,
+            role: "user".to_string(),
+            permissions: vec![],
+            quota: None,
+        };
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(SECRET.as_ref()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn tokens_for_this_service_are_accepted() {
+        let validator = TokenValidator::from_config(&config()).unwrap();
+        let claims = validator
+            .validate(&token("auth-service", "time-service"))
+            .unwrap();
+        assert_eq!(claims.sub, "user-1");
+    }
+
+    #[test]
+    fn tokens_for_other_audiences_or_issuers_are_refused() {
+        let validator = TokenValidator::from_config(&config()).unwrap();
+
+        let error = validator
+            .validate(&token("auth-service", "weather-service"))
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::InvalidAudience);
+
+        let error = validator
+            .validate(&token("someone-else", "time-service"))
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::InvalidIssuer);
+    }
+}
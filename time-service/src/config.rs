@@ -3,6 +3,11 @@ use std::env;
 pub struct Config {
     pub port: u16,
     pub world_time_api_url: String,
+    /// Shared with auth-service; bearer tokens are only checked when set
+    pub jwt_secret: Option<String>,
+    pub jwt_issuer: String,
+    pub jwt_audience: String,
+    pub jwt_leeway_seconds: u64,
 }
 
 impl Config {
@@ -14,7 +19,16 @@ impl Config {
                 .unwrap_or(3003),
             world_time_api_url: env::var("WORLD_TIME_API_URL")
                 .unwrap_or_else(|_| "http://worldtimeapi.org/api/timezone".to_string()),
+            jwt_secret: env::var("JWT_SECRET").ok().filter(|s| !s.is_empty()),
+            jwt_issuer: env::var("JWT_ISSUER").unwrap_or_else(|_| "auth-service".to_string()),
+            // Prefixed like the weather service's, so services may share an
+            // environment file
+            jwt_audience: env::var("TIME_JWT_AUDIENCE")
+                .unwrap_or_else(|_| "time-service".to_string()),
+            jwt_leeway_seconds: env::var("TIME_JWT_LEEWAY_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(60),
         }
     }
 }
-
@@ -8,6 +8,14 @@ use std::sync::Arc;
 use tracing::info;
 
 use crate::api_client::WorldTimeApiClient;
+use crate::auth::{Principal, TokenValidator};
+
+#[derive(Clone)]
+pub struct AppState {
+    pub client: Arc<WorldTimeApiClient>,
+    /// Checks bearer tokens; `None` when `JWT_SECRET` is unset
+    pub tokens: Option<Arc<TokenValidator>>,
+}
 
 #[utoipa::path(
     get,
@@ -29,16 +37,19 @@ pub async fn health() -> Json<serde_json::Value> {
     responses(
         (status = 200, description = "Time data for the city", body = TimeData),
         (status = 400, description = "Invalid request"),
+        (status = 401, description = "Invalid bearer token"),
         (status = 500, description = "Internal server error")
     )
 )]
 pub async fn get_time(
-    State(client): State<Arc<WorldTimeApiClient>>,
+    State(state): State<AppState>,
+    Principal(claims): Principal,
     Path(city): Path<String>,
 ) -> Result<Json<TimeData>, AppError> {
-    info!(city = %city, "Time request received");
+    let user_id = claims.as_ref().map(|c| c.sub.as_str());
+    info!(city = %city, user_id = ?user_id, "Time request received");
     
-    let time = client.get_time(&city).await?;
+    let time = state.client.get_time(&city).await?;
     
     Ok(Json(time))
 }
@@ -1,4 +1,5 @@
 mod api_client;
+mod auth;
 mod cache;
 mod config;
 mod handlers;
@@ -37,7 +38,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .merge(openapi::swagger_ui())
         .layer(TraceLayer::new_for_http())
         .layer(CorsLayer::permissive())
-        .with_state(api_client);
+        .with_state(handlers::AppState {
+            client: api_client,
+            tokens: auth::TokenValidator::from_config(&config).map(Arc::new),
+        });
 
     let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
     info!("Time service starting on {}", addr);
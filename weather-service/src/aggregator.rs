@@ -11,6 +11,20 @@ use tracing::{Instrument, error, info, instrument, warn};
 
 use crate::api_client::OpenMeteoClient;
 
+/// Most cities one aggregate request may ask for
+pub const MAX_CITIES: usize = 20;
+
+/// Refuse an aggregate request for no cities or more than [`MAX_CITIES`]
+pub fn validate_cities(cities: &[String]) -> Result<(), AppError> {
+    if cities.is_empty() || cities.len() > MAX_CITIES {
+        return Err(AppError::validation(format!(
+            "Must provide between 1 and {} cities",
+            MAX_CITIES
+        )));
+    }
+    Ok(())
+}
+
 pub struct Aggregator {
     weather_client: Arc<OpenMeteoClient>,
     time_service_url: String,
@@ -36,9 +50,7 @@ impl Aggregator {
 
     #[instrument(skip(self), fields(city_count = cities.len()))]
     pub async fn aggregate(&self, cities: Vec<String>) -> Result<AggregateResponse, AppError> {
-        if cities.is_empty() || cities.len() > 20 {
-            return Err(AppError::validation("Must provide between 1 and 20 cities"));
-        }
+        validate_cities(&cities)?;
 
         info!(count = cities.len(), "Starting aggregation for cities");
 
@@ -178,7 +190,7 @@ async fn fetch_weather(
     city: &str,
     weather_client: &OpenMeteoClient,
 ) -> Result<WeatherData, AppError> {
-    timeout(Duration::from_secs(10), weather_client.get_weather(city))
+    timeout(Duration::from_secs(10), weather_client.get_weather(city, 0))
         .await
         .map_err(|_| AppError::timeout(format!("Weather fetch for {} timed out", city)))?
 }
@@ -1,7 +1,7 @@
 use crate::cache::WeatherCache;
 use common::errors::AppError;
 use common::http_client::HttpClient;
-use common::models::WeatherData;
+use common::models::{DailyForecast, WeatherData};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use std::time::Duration;
@@ -12,6 +12,7 @@ use tracing::{info, instrument, warn};
 #[derive(Debug, Serialize, Deserialize)]
 struct OpenMeteoResponse {
     current: CurrentWeather,
+    daily: Option<DailyWeather>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -22,6 +23,14 @@ struct CurrentWeather {
     weather_code: Option<u32>,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+struct DailyWeather {
+    time: Vec<String>,
+    temperature_2m_max: Vec<f64>,
+    temperature_2m_min: Vec<f64>,
+    weather_code: Vec<Option<u32>>,
+}
+
 pub struct OpenMeteoClient {
     http_client: HttpClient,
     cache: Arc<WeatherCache>,
@@ -46,10 +55,20 @@ impl OpenMeteoClient {
         }
     }
 
+    /// Current weather for `city`, with a daily forecast when `forecast_days` is non-zero
     #[instrument(skip(self), fields(city = %city))]
-    pub async fn get_weather(&self, city: &str) -> Result<WeatherData, AppError> {
+    pub async fn get_weather(
+        &self,
+        city: &str,
+        forecast_days: u32,
+    ) -> Result<WeatherData, AppError> {
+        let cache_key = match forecast_days {
+            0 => city.to_string(),
+            days => format!("{}#{}", city, days),
+        };
+
         // Check cache first
-        if let Some(cached) = self.cache.get(city).await {
+        if let Some(cached) = self.cache.get(&cache_key).await {
             info!(city = %city, "Cache hit");
             return Ok(cached);
         }
@@ -67,12 +86,18 @@ impl OpenMeteoClient {
         info!(city = %city, "Fetching weather from API");
 
         // Build URL with city coordinates (simplified - better solution would be to use geocoding, but for now we'll use this)
-        let url = format!(
+        let mut url = format!(
             "{}?latitude={}&longitude={}&current=temperature_2m,relative_humidity_2m,wind_speed_10m,weather_code",
             self.base_url,
             self.get_latitude(city),
             self.get_longitude(city)
         );
+        if forecast_days > 0 {
+            url.push_str(&format!(
+                "&daily=temperature_2m_max,temperature_2m_min,weather_code&forecast_days={}&timezone=auto",
+                forecast_days
+            ));
+        }
 
         let response: OpenMeteoResponse = self.http_client.get_json(&url).await?;
 
@@ -81,14 +106,34 @@ impl OpenMeteoClient {
             condition: self.weather_code_to_condition(response.current.weather_code.unwrap_or(0)),
             humidity: response.current.relative_humidity_2m,
             wind_speed: response.current.wind_speed_10m,
+            forecast: response
+                .daily
+                .map(|daily| self.daily_forecast(daily))
+                .unwrap_or_default(),
         };
 
         // Cache the result
-        self.cache.set(city.to_string(), weather.clone()).await;
+        self.cache.set(cache_key, weather.clone()).await;
 
         Ok(weather)
     }
 
+    fn daily_forecast(&self, daily: DailyWeather) -> Vec<DailyForecast> {
+        daily
+            .time
+            .into_iter()
+            .zip(daily.temperature_2m_max)
+            .zip(daily.temperature_2m_min)
+            .zip(daily.weather_code)
+            .map(|(((date, max), min), code)| DailyForecast {
+                date,
+                temperature_max: max,
+                temperature_min: min,
+                condition: self.weather_code_to_condition(code.unwrap_or(0)),
+            })
+            .collect()
+    }
+
     async fn debounce(&self) {
         let mut last_request = self.last_request_time.lock().await;
         if let Some(last) = *last_request {
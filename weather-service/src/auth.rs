@@ -0,0 +1,176 @@
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header::AUTHORIZATION, request::Parts},
+};
+use common::client_ip::client_ip;
+use common::errors::AppError;
+use common::models::Claims;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, errors::ErrorKind};
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+
+use crate::config::Config;
+use crate::handlers::AppState;
+
+/// Checks access tokens issued by auth-service. Session revocation is not
+/// visible here, so `WEATHER_JWT_MAX_AGE_SECONDS`, when set, only accepts
+/// tokens for that long after they were issued: a revoked session cannot
+/// refresh, and its last token stops working here within that time even if
+/// its `exp` is later. By default tokens are accepted until their `exp`.
+pub struct TokenValidator {
+    decoding_key: DecodingKey,
+    validation: Validation,
+    max_age: Option<u64>,
+}
+
+impl TokenValidator {
+    /// `None` when no `JWT_SECRET` is configured and every request is anonymous
+    pub fn from_config(config: &Config) -> Option<Self> {
+        let secret = config.jwt_secret.as_ref()?;
+
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_issuer(&[&config.jwt_issuer]);
+        validation.set_audience(&[&config.jwt_audience]);
+        validation.set_required_spec_claims(&["exp", "nbf", "iat", "iss", "aud", "sub"]);
+        validation.validate_nbf = true;
+        validation.leeway = config.jwt_leeway_seconds;
+
+        Some(Self {
+            decoding_key: DecodingKey::from_secret(secret.as_ref()),
+            validation,
+            max_age: Some(config.jwt_max_age_seconds).filter(|s| *s > 0),
+        })
+    }
+
+    pub fn validate(&self, token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
+        let claims = decode::<Claims>(token, &self.decoding_key, &self.validation)?.claims;
+
+        if let Some(max_age) = self.max_age {
+            let age = jsonwebtoken::get_current_timestamp().saturating_sub(claims.iat as u64);
+            if age > max_age + self.validation.leeway {
+                return Err(ErrorKind::ExpiredSignature.into());
+            }
+        }
+
+        Ok(claims)
+    }
+}
+
+/// The caller's claims, or `None` for a request without a bearer token
+pub struct Principal(pub Option<Claims>);
+
+impl FromRequestParts<AppState> for Principal {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, AppError> {
+        let (Some(header), Some(validator)) = (parts.headers.get(AUTHORIZATION), &state.tokens)
+        else {
+            return Ok(Self(None));
+        };
+
+        let token = header
+            .to_str()
+            .ok()
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .ok_or_else(|| AppError::auth("Invalid Authorization header format"))?;
+
+        validator
+            .validate(token)
+            .map(|claims| Self(Some(claims)))
+            .map_err(|e| AppError::auth(format!("Invalid token: {}", e)))
+    }
+}
+
+/// Address of the caller: the peer, or the client a trusted proxy forwarded
+/// the request for. Anonymous quotas are counted per address.
+pub struct ClientIp(pub Option<IpAddr>);
+
+impl FromRequestParts<AppState> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Infallible> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        Ok(Self(client_ip(
+            peer,
+            &parts.headers,
+            &state.trusted_proxies,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{EncodingKey, Header, encode, get_current_timestamp};
+
+    const SECRET: &str = "test-secret";
+
+    fn config(max_age: u64) -> Config {
+        Config {
+            port: 0,
+            open_meteo_url: String::new(),
+            cache_ttl_seconds: 0,
+            rate_limit_per_minute: 0,
+            time_service_url: String::new(),
+            jwt_secret: Some(SECRET.to_string()),
+            jwt_issuer: "auth-service".to_string(),
+            jwt_audience: "weather-service".to_string(),
+            jwt_leeway_seconds: 60,
+            jwt_max_age_seconds: max_age,
+            trusted_proxies: vec![],
+            anonymous_requests_per_day: 0,
+        }
+    }
+
+    /// A token issued `age` seconds ago that expires in a day
+    fn token(age: u64) -> String {
+        let now = get_current_timestamp();
+        let issued = (now - age) as usize;
+        let claims = Claims {
+            sub: "user-1".to_string(),
+            iss: "auth-service".to_string(),
+            aud: vec!["weather-service".to_string()],
+            exp: (now + 86400) as usize,
+            iat: issued,
+            nbf: issued,
+            sid: None,
+            act: None,
+            org: None,
+            org_role: None,
+            role: "user".to_string(),
+            permissions: vec![],
+            quota: None,
+        };
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(SECRET.as_ref()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn fresh_tokens_are_accepted() {
+        let validator = TokenValidator::from_config(&config(900)).unwrap();
+        assert_eq!(validator.validate(&token(0)).unwrap().sub, "user-1");
+        // Within the leeway
+        assert!(validator.validate(&token(930)).is_ok());
+    }
+
+    #[test]
+    fn tokens_older_than_the_max_age_are_refused_before_exp() {
+        let validator = TokenValidator::from_config(&config(900)).unwrap();
+        let error = validator.validate(&token(3600)).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::ExpiredSignature);
+    }
+
+    #[test]
+    fn zero_max_age_accepts_tokens_until_exp() {
+        let validator = TokenValidator::from_config(&config(0)).unwrap();
+        assert!(validator.validate(&token(3600)).is_ok());
+    }
+}
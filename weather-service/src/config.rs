@@ -1,3 +1,5 @@
+use common::client_ip;
+use ipnet::IpNet;
 use std::env;
 
 pub struct Config {
@@ -6,6 +8,19 @@ pub struct Config {
     pub cache_ttl_seconds: u64,
     pub rate_limit_per_minute: u32,
     pub time_service_url: String,
+    /// Secret auth-service signs access tokens with; requests are anonymous when unset
+    pub jwt_secret: Option<String>,
+    pub jwt_issuer: String,
+    pub jwt_audience: String,
+    pub jwt_leeway_seconds: u64,
+    /// Tokens issued longer ago are refused even before they expire; 0 accepts
+    /// them until `exp`
+    pub jwt_max_age_seconds: u64,
+    /// Proxies whose `X-Forwarded-For` is believed when identifying anonymous callers
+    pub trusted_proxies: Vec<IpNet>,
+    /// Requests each anonymous client address may make per UTC day; 0 refuses
+    /// requests without a token
+    pub anonymous_requests_per_day: u32,
 }
 
 impl Config {
@@ -27,7 +42,30 @@ impl Config {
                 .unwrap_or(60),
             time_service_url: env::var("TIME_SERVICE_URL")
                 .unwrap_or_else(|_| "http://localhost:3003".to_string()),
+            jwt_secret: env::var("JWT_SECRET").ok().filter(|s| !s.is_empty()),
+            jwt_issuer: env::var("JWT_ISSUER").unwrap_or_else(|_| "auth-service".to_string()),
+            // Prefixed: auth-service reads the plain names with other defaults,
+            // and both services may share an environment file
+            jwt_audience: env::var("WEATHER_JWT_AUDIENCE")
+                .unwrap_or_else(|_| "weather-service".to_string()),
+            jwt_leeway_seconds: env::var("WEATHER_JWT_LEEWAY_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(60),
+            jwt_max_age_seconds: env::var("WEATHER_JWT_MAX_AGE_SECONDS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0), // Until the token's exp by default
+            trusted_proxies: env::var("WEATHER_TRUSTED_PROXIES")
+                .map(|s| {
+                    client_ip::parse_trusted_proxies(&s)
+                        .unwrap_or_else(|e| panic!("Invalid WEATHER_TRUSTED_PROXIES: {}", e))
+                })
+                .unwrap_or_default(),
+            anonymous_requests_per_day: env::var("WEATHER_ANONYMOUS_REQUESTS_PER_DAY")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(1000),
         }
     }
 }
-
@@ -1,21 +1,34 @@
 use axum::{
     extract::{Path, State},
+    http::{Method, Uri},
     response::Json,
 };
 use axum_extra::extract::Query;
 use common::errors::AppError;
-use common::models::{AggregateResponse, WeatherData};
+use common::models::{AggregateResponse, QuotaUsage, WeatherData};
+use common::policy::{AccessRequest, PolicyEngine};
+use ipnet::IpNet;
 use serde::Deserialize;
 use std::sync::Arc;
 use tracing::info;
 
-use crate::aggregator::Aggregator;
+use crate::aggregator::{self, Aggregator};
 use crate::api_client::OpenMeteoClient;
+use crate::auth::{ClientIp, Principal, TokenValidator};
+use crate::quota::QuotaTracker;
+
+/// Longest forecast Open-Meteo provides
+const MAX_FORECAST_DAYS: u32 = 16;
 
 #[derive(Clone)]
 pub struct AppState {
     pub client: Arc<OpenMeteoClient>,
     pub aggregator: Arc<Aggregator>,
+    pub tokens: Option<Arc<TokenValidator>>,
+    pub policy: Arc<PolicyEngine>,
+    pub quotas: Arc<QuotaTracker>,
+    /// Proxies whose `X-Forwarded-For` identifies anonymous callers
+    pub trusted_proxies: Arc<[IpNet]>,
 }
 
 #[utoipa::path(
@@ -33,25 +46,51 @@ pub async fn health() -> Json<serde_json::Value> {
     get,
     path = "/api/weather/{city}",
     params(
-        ("city" = String, Path, description = "City name")
+        ("city" = String, Path, description = "City name"),
+        ("days" = Option<u32>, Query, description = "Days of daily forecast to include (1-16)")
     ),
     responses(
         (status = 200, description = "Weather data for the city", body = WeatherData),
         (status = 400, description = "Invalid request"),
+        (status = 401, description = "Invalid bearer token, or one is required because anonymous requests are disabled"),
+        (status = 403, description = "Denied by access policy, or more forecast days than the token's quota allows"),
+        (status = 429, description = "Daily quota exceeded, per user or per address for anonymous callers; `Retry-After` is set when the daily limit is reached"),
         (status = 500, description = "Internal server error")
     )
 )]
 pub async fn get_weather(
     State(state): State<AppState>,
+    Principal(claims): Principal,
+    ClientIp(client): ClientIp,
+    uri: Uri,
     Path(city): Path<String>,
+    Query(params): Query<WeatherQuery>,
 ) -> Result<Json<WeatherData>, AppError> {
     info!(city = %city, "Weather request received");
 
-    let weather = state.client.get_weather(&city).await?;
+    let days = params.days.unwrap_or(0);
+    if days > MAX_FORECAST_DAYS || params.days == Some(0) {
+        return Err(AppError::validation(format!(
+            "Forecast days must be between 1 and {}",
+            MAX_FORECAST_DAYS
+        )));
+    }
+
+    state
+        .policy
+        .authorize(&AccessRequest::new(&Method::GET, &uri, claims.as_ref()))?;
+    state.quotas.consume(claims.as_ref(), client, 1, days)?;
+
+    let weather = state.client.get_weather(&city, days).await?;
 
     Ok(Json(weather))
 }
 
+#[derive(Deserialize)]
+pub struct WeatherQuery {
+    pub days: Option<u32>,
+}
+
 #[derive(Deserialize)]
 pub struct AggregateQuery {
     #[serde(default)]
@@ -66,17 +105,49 @@ pub struct AggregateQuery {
     ),
     responses(
         (status = 200, description = "Aggregated city data", body = AggregateResponse),
-        (status = 400, description = "Invalid request - must provide 1-20 cities")
+        (status = 400, description = "Invalid request - must provide 1-20 cities"),
+        (status = 401, description = "Invalid bearer token, or one is required because anonymous requests are disabled"),
+        (status = 403, description = "Denied by access policy, or more cities than the token's quota allows"),
+        (status = 429, description = "Daily quota exceeded, per user or per address for anonymous callers; `Retry-After` is set when the daily limit is reached")
     ),
     tag = "aggregate"
 )]
 pub async fn aggregate(
     State(state): State<AppState>,
+    Principal(claims): Principal,
+    ClientIp(client): ClientIp,
+    uri: Uri,
     Query(params): Query<AggregateQuery>,
 ) -> Result<Json<AggregateResponse>, AppError> {
     info!(count = params.city.len(), "Aggregate request received");
 
+    aggregator::validate_cities(&params.city)?;
+    state
+        .policy
+        .authorize(&AccessRequest::new(&Method::GET, &uri, claims.as_ref()))?;
+    state
+        .quotas
+        .consume(claims.as_ref(), client, params.city.len(), 0)?;
+
     let response = state.aggregator.aggregate(params.city).await?;
 
     Ok(Json(response))
 }
+
+#[utoipa::path(
+    get,
+    path = "/api/usage",
+    responses(
+        (status = 200, description = "The caller's requests today and daily limit", body = QuotaUsage),
+        (status = 401, description = "Bearer token required")
+    ),
+    tag = "weather"
+)]
+pub async fn usage(
+    State(state): State<AppState>,
+    Principal(claims): Principal,
+) -> Result<Json<QuotaUsage>, AppError> {
+    let claims = claims.ok_or_else(|| AppError::auth("Bearer token required"))?;
+
+    Ok(Json(state.quotas.usage(&claims)))
+}
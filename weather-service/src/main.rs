@@ -1,11 +1,14 @@
 mod aggregator;
 mod api_client;
+mod auth;
 mod cache;
 mod config;
 mod handlers;
 mod openapi;
+mod quota;
 
 use axum::{Router, routing::get};
+use common::policy::PolicyEngine;
 use common::tracing::init_tracing_pretty;
 use std::net::SocketAddr;
 use std::sync::Arc;
@@ -37,12 +40,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let state = handlers::AppState {
         client: api_client,
         aggregator,
+        tokens: auth::TokenValidator::from_config(&config).map(Arc::new),
+        policy: Arc::new(PolicyEngine::from_env()?),
+        quotas: Arc::new(quota::QuotaTracker::new(config.anonymous_requests_per_day)),
+        trusted_proxies: config.trusted_proxies.clone().into(),
     };
 
     let app = Router::new()
         .route("/health", get(handlers::health))
         .route("/api/weather/{city}", get(handlers::get_weather))
         .route("/api/aggregate", get(handlers::aggregate))
+        .route("/api/usage", get(handlers::usage))
         .merge(openapi::swagger_ui())
         .layer(TraceLayer::new_for_http())
         .layer(CorsLayer::permissive())
@@ -52,9 +60,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("Weather service starting on {}", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal(cancellation_token))
-        .await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal(cancellation_token))
+    .await?;
 
     info!("Weather service stopped");
     Ok(())
@@ -2,7 +2,7 @@ use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
 use crate::handlers;
-use common::models::{AggregateResponse, WeatherData};
+use common::models::{AggregateResponse, DailyForecast, QuotaUsage, WeatherData};
 
 #[derive(OpenApi)]
 #[openapi(
@@ -10,10 +10,13 @@ use common::models::{AggregateResponse, WeatherData};
         handlers::health,
         handlers::get_weather,
         handlers::aggregate,
+        handlers::usage,
     ),
     components(schemas(
         WeatherData,
+        DailyForecast,
         AggregateResponse,
+        QuotaUsage,
         common::models::CityData,
         common::models::TimeData,
         common::models::ResponseSummary,
@@ -0,0 +1,278 @@
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use common::errors::AppError;
+use common::models::{Claims, QuotaUsage};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use tracing::info;
+
+/// Counts each user's weather requests against the quota in their token, and
+/// each anonymous client address's against `WEATHER_ANONYMOUS_REQUESTS_PER_DAY`.
+/// Counters live in memory and reset at midnight UTC. Each instance keeps its
+/// own, so behind a load balancer a caller gets the allowance on every
+/// replica, and a restart forgets the day's counts.
+pub struct QuotaTracker {
+    anonymous_requests_per_day: u32,
+    counters: Mutex<DailyCounters>,
+}
+
+struct DailyCounters {
+    day: NaiveDate,
+    requests: HashMap<String, u32>,
+}
+
+impl QuotaTracker {
+    /// Anonymous callers are refused when `anonymous_requests_per_day` is 0
+    pub fn new(anonymous_requests_per_day: u32) -> Self {
+        Self {
+            anonymous_requests_per_day,
+            counters: Mutex::new(DailyCounters {
+                day: Utc::now().date_naive(),
+                requests: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Check a request for `cities` cities with `forecast_days` days of
+    /// forecast against the caller's quota and count it as one request, however
+    /// many cities it covers. Tokens without a quota are unlimited; requests
+    /// without one are counted per `client` address.
+    pub fn consume(
+        &self,
+        claims: Option<&Claims>,
+        client: Option<IpAddr>,
+        cities: usize,
+        forecast_days: u32,
+    ) -> Result<(), AppError> {
+        let Some(claims) = claims else {
+            if self.anonymous_requests_per_day == 0 {
+                return Err(AppError::auth("Bearer token required"));
+            }
+            // Callers whose address is unknown share one allowance
+            let client = client.map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+            return self.count(
+                format!("anonymous:{}", client),
+                self.anonymous_requests_per_day.into(),
+            );
+        };
+        let Some(quota) = &claims.quota else {
+            return Ok(());
+        };
+
+        if let Some(max) = quota.max_cities
+            && cities > max.max(0) as usize
+        {
+            return Err(AppError::authorization(format!(
+                "At most {} cities per aggregate request",
+                max
+            )));
+        }
+        if let Some(max) = quota.forecast_days
+            && forecast_days > max.max(0) as u32
+        {
+            return Err(AppError::authorization(format!(
+                "At most {} forecast days per request",
+                max
+            )));
+        }
+
+        match quota.requests_per_day {
+            Some(limit) => self.count(claims.sub.clone(), limit.max(0) as u64),
+            None => Ok(()),
+        }
+    }
+
+    /// Add a request to today's count under `key`, unless that would exceed `limit`
+    fn count(&self, key: String, limit: u64) -> Result<(), AppError> {
+        let now = Utc::now();
+        let mut counters = self.counters.lock().unwrap();
+        counters.roll_over(now);
+
+        let used = counters.requests.entry(key).or_default();
+        if *used as u64 >= limit {
+            info!(used = *used, limit, "Daily quota exhausted");
+            let retry_after = (next_reset(now) - now).num_seconds().max(1) as u64;
+            return Err(AppError::quota_exceeded(
+                format!("Daily limit of {} requests reached", limit),
+                Some(retry_after),
+            ));
+        }
+        *used += 1;
+
+        Ok(())
+    }
+
+    pub fn usage(&self, claims: &Claims) -> QuotaUsage {
+        let now = Utc::now();
+        let mut counters = self.counters.lock().unwrap();
+        counters.roll_over(now);
+
+        QuotaUsage {
+            requests_today: counters.requests.get(&claims.sub).copied().unwrap_or(0),
+            requests_per_day: claims.quota.as_ref().and_then(|q| q.requests_per_day),
+            resets_at: next_reset(now).to_rfc3339(),
+        }
+    }
+}
+
+impl DailyCounters {
+    /// Forget yesterday's counts once the UTC day changes
+    fn roll_over(&mut self, now: DateTime<Utc>) {
+        let today = now.date_naive();
+        if self.day != today {
+            self.day = today;
+            self.requests.clear();
+        }
+    }
+}
+
+/// Midnight UTC after `now`
+fn next_reset(now: DateTime<Utc>) -> DateTime<Utc> {
+    let tomorrow = now.date_naive() + Days::new(1);
+    tomorrow.and_hms_opt(0, 0, 0).unwrap().and_utc()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::models::Quota;
+
+    fn claims(sub: &str, quota: Option<Quota>) -> Claims {
+        Claims {
+            sub: sub.to_string(),
+            iss: "auth-service".to_string(),
+            aud: vec!["weather-service".to_string()],
+            exp: 0,
+            iat: 0,
+            nbf: 0,
+            sid: None,
+            act: None,
+            org: None,
+            org_role: None,
+            role: "user".to_string(),
+            permissions: vec![],
+            quota,
+        }
+    }
+
+    fn daily(requests_per_day: i32) -> Option<Quota> {
+        Some(Quota {
+            requests_per_day: Some(requests_per_day),
+            max_cities: None,
+            forecast_days: None,
+        })
+    }
+
+    fn ip(s: &str) -> Option<IpAddr> {
+        Some(s.parse().unwrap())
+    }
+
+    fn status(result: Result<(), AppError>) -> u16 {
+        match result {
+            Ok(()) => 200,
+            Err(AppError::QuotaExceeded { .. }) => 429,
+            Err(AppError::AuthError(_)) => 401,
+            Err(AppError::AuthorizationError(_)) => 403,
+            Err(e) => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn anonymous_callers_are_counted_per_address() {
+        let tracker = QuotaTracker::new(3);
+
+        assert_eq!(status(tracker.consume(None, ip("203.0.113.1"), 5, 0)), 200);
+        assert_eq!(status(tracker.consume(None, ip("203.0.113.1"), 1, 0)), 200);
+        assert_eq!(status(tracker.consume(None, ip("203.0.113.1"), 1, 0)), 200);
+        assert_eq!(status(tracker.consume(None, ip("203.0.113.1"), 1, 0)), 429);
+
+        // Another address has its own allowance
+        assert_eq!(status(tracker.consume(None, ip("203.0.113.2"), 3, 0)), 200);
+    }
+
+    #[test]
+    fn dropping_the_token_does_not_escape_the_quota() {
+        let tracker = QuotaTracker::new(1);
+        let user = claims("user-1", daily(100));
+
+        assert_eq!(status(tracker.consume(None, ip("203.0.113.1"), 1, 0)), 200);
+        assert_eq!(status(tracker.consume(None, ip("203.0.113.1"), 1, 0)), 429);
+        // The token's own, larger quota still applies to it
+        assert_eq!(
+            status(tracker.consume(Some(&user), ip("203.0.113.1"), 1, 0)),
+            200
+        );
+    }
+
+    #[test]
+    fn unknown_addresses_share_one_allowance() {
+        let tracker = QuotaTracker::new(1);
+
+        assert_eq!(status(tracker.consume(None, None, 1, 0)), 200);
+        assert_eq!(status(tracker.consume(None, None, 1, 0)), 429);
+    }
+
+    #[test]
+    fn zero_anonymous_allowance_requires_a_token() {
+        let tracker = QuotaTracker::new(0);
+
+        assert_eq!(status(tracker.consume(None, ip("203.0.113.1"), 1, 0)), 401);
+        let user = claims("user-1", None);
+        assert_eq!(
+            status(tracker.consume(Some(&user), ip("203.0.113.1"), 1, 0)),
+            200
+        );
+    }
+
+    #[test]
+    fn users_are_counted_by_subject_and_reset_daily() {
+        let tracker = QuotaTracker::new(0);
+        let alice = claims("alice", daily(2));
+        let bob = claims("bob", daily(2));
+
+        assert_eq!(status(tracker.consume(Some(&alice), None, 2, 0)), 200);
+        assert_eq!(status(tracker.consume(Some(&alice), None, 1, 0)), 200);
+        let Err(AppError::QuotaExceeded { retry_after, .. }) =
+            tracker.consume(Some(&alice), None, 1, 0)
+        else {
+            panic!("the daily limit should be reached");
+        };
+        assert!(retry_after.is_some_and(|s| s > 0 && s <= 86400));
+        assert_eq!(status(tracker.consume(Some(&bob), None, 2, 0)), 200);
+        assert_eq!(tracker.usage(&alice).requests_today, 2);
+
+        tracker.counters.lock().unwrap().day -= chrono::Duration::days(1);
+        assert_eq!(tracker.usage(&alice).requests_today, 0);
+        assert_eq!(status(tracker.consume(Some(&alice), None, 2, 0)), 200);
+    }
+
+    #[test]
+    fn request_size_limits_are_forbidden_and_not_counted() {
+        let tracker = QuotaTracker::new(0);
+        let user = claims(
+            "user-1",
+            Some(Quota {
+                requests_per_day: Some(10),
+                max_cities: Some(5),
+                forecast_days: Some(7),
+            }),
+        );
+
+        assert_eq!(status(tracker.consume(Some(&user), None, 6, 0)), 403);
+        assert_eq!(status(tracker.consume(Some(&user), None, 1, 8)), 403);
+        assert_eq!(tracker.usage(&user).requests_today, 0);
+
+        assert_eq!(status(tracker.consume(Some(&user), None, 5, 7)), 200);
+        assert_eq!(tracker.usage(&user).requests_today, 1);
+    }
+
+    #[test]
+    fn tokens_without_a_quota_are_unlimited() {
+        let tracker = QuotaTracker::new(0);
+        let admin = claims("admin", None);
+
+        for _ in 0..100 {
+            assert_eq!(status(tracker.consume(Some(&admin), None, 20, 16)), 200);
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use serde_json::json;
 use wiremock::{
-    matchers::{method, path, path_regex},
     Mock, MockServer, ResponseTemplate,
+    matchers::{method, path, path_regex},
 };
 
 /// Test that the mock server can serve weather-like responses